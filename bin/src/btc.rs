@@ -0,0 +1,175 @@
+//! Bitcoin message signatures of P2PKH and P2WPKH addresses.
+//!
+//! Signatures follow the compact recoverable format of BIP-137, as produced by
+//! `signmessage` and most hardware wallets: a header byte carrying the recovery
+//! id and the address type, followed by `r` and `s`, encoded in base64.
+use anyhow::{anyhow, ensure, Result};
+use bech32::{u5, ToBase32, Variant};
+use ripemd::Ripemd160;
+use secp256k1::{
+    ecdsa::{RecoverableSignature, RecoveryId},
+    Message, PublicKey, Secp256k1, SecretKey,
+};
+use sha2::{Digest, Sha256};
+
+const MESSAGE_MAGIC: &[u8] = b"Bitcoin Signed Message:\n";
+const BECH32_HRP: &str = "bc";
+
+/// Mainnet address types whose ownership can be attested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    /// Base58 pay-to-public-key-hash address, starting with `1`.
+    P2pkh,
+    /// Bech32 pay-to-witness-public-key-hash address, starting with `bc1q`.
+    P2wpkh,
+}
+
+impl AddressKind {
+    pub fn of(address: &str) -> Result<Self> {
+        if address.starts_with('1') {
+            Ok(Self::P2pkh)
+        } else if address.to_lowercase().starts_with("bc1q") {
+            Ok(Self::P2wpkh)
+        } else {
+            Err(anyhow!("unsupported bitcoin address {}", address))
+        }
+    }
+
+    /// BIP-137 header of a signature by a compressed key, before adding the recovery id.
+    fn header(self) -> u8 {
+        match self {
+            Self::P2pkh => 31,
+            Self::P2wpkh => 39,
+        }
+    }
+}
+
+/// Address of kind `kind` of the compressed public key.
+pub fn address(kind: AddressKind, public_key: &PublicKey) -> String {
+    let hash = hash160(&public_key.serialize());
+    match kind {
+        AddressKind::P2pkh => {
+            let mut payload = vec![0x00];
+            payload.extend_from_slice(&hash);
+            bs58::encode(payload).with_check().into_string()
+        }
+        AddressKind::P2wpkh => {
+            let mut data = vec![u5::try_from_u8(0).expect("witness version 0")];
+            data.extend(hash.to_base32());
+            bech32::encode(BECH32_HRP, data, Variant::Bech32).expect("valid human readable part")
+        }
+    }
+}
+
+/// Sign `message` for the address of kind `kind` of `secret_key`, returning the base64 signature.
+pub fn sign_message(kind: AddressKind, secret_key: &SecretKey, message: &str) -> String {
+    let secp = Secp256k1::signing_only();
+    let digest = Message::from_slice(&message_hash(message)).expect("digest is 32 bytes");
+    let (recovery_id, signature) = secp
+        .sign_ecdsa_recoverable(&digest, secret_key)
+        .serialize_compact();
+
+    let mut bytes = Vec::with_capacity(65);
+    bytes.push(kind.header() + recovery_id.to_i32() as u8);
+    bytes.extend_from_slice(&signature);
+
+    base64::encode(bytes)
+}
+
+/// Verify that the base64 `signature` of `message` was made by the key of `address`.
+pub fn verify_message(address: &str, message: &str, signature: &str) -> Result<()> {
+    let kind = AddressKind::of(address)?;
+    let bytes = base64::decode(signature)
+        .map_err(|e| anyhow!("invalid base64 signature: {}", e))?;
+    ensure!(bytes.len() == 65, "signature of {} bytes instead of 65", bytes.len());
+    let header = bytes[0];
+    ensure!((27..=42).contains(&header), "invalid signature header {}", header);
+    ensure!(header >= 31, "signatures by uncompressed keys are not supported");
+
+    let recovery_id = RecoveryId::from_i32(((header - 27) & 3) as i32)?;
+    let signature = RecoverableSignature::from_compact(&bytes[1..], recovery_id)?;
+    let digest = Message::from_slice(&message_hash(message))?;
+    let public_key = Secp256k1::verification_only().recover_ecdsa(&digest, &signature)?;
+
+    let recovered = self::address(kind, &public_key);
+    let matches = match kind {
+        AddressKind::P2pkh => recovered == address,
+        AddressKind::P2wpkh => recovered == address.to_lowercase(),
+    };
+    ensure!(matches, "signature by {} instead of {}", recovered, address);
+
+    Ok(())
+}
+
+/// Double SHA-256 of the message prefixed with the Bitcoin message magic.
+fn message_hash(message: &str) -> [u8; 32] {
+    let mut preimage = Vec::with_capacity(MESSAGE_MAGIC.len() + message.len() + 10);
+    push_var_int(&mut preimage, MESSAGE_MAGIC.len());
+    preimage.extend_from_slice(MESSAGE_MAGIC);
+    push_var_int(&mut preimage, message.len());
+    preimage.extend_from_slice(message.as_bytes());
+
+    Sha256::digest(Sha256::digest(&preimage)).into()
+}
+
+fn push_var_int(bytes: &mut Vec<u8>, n: usize) {
+    match n {
+        0..=0xfc => bytes.push(n as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+    }
+}
+
+fn hash160(bytes: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(bytes)).into()
+}
+
+#[cfg(test)]
+mod test {
+    use ark_std::{test_rng, rand::Rng};
+
+    use super::*;
+
+    #[test]
+    fn test_addresses() {
+        // The public key of secret key 1 is the generator.
+        let secret_key = SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1]].concat()).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key);
+        assert_eq!(address(AddressKind::P2pkh, &public_key), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+        assert_eq!(address(AddressKind::P2wpkh, &public_key), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let rng = &mut test_rng();
+        let secp = Secp256k1::signing_only();
+        let secret_key = SecretKey::from_slice(&rng.gen::<[u8; 32]>()).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+        let message = "Proof of Solvency";
+
+        for kind in [AddressKind::P2pkh, AddressKind::P2wpkh] {
+            let address = address(kind, &public_key);
+            let signature = sign_message(kind, &secret_key, message);
+            verify_message(&address, message, &signature).unwrap();
+            assert!(verify_message(&address, "Proof of Insolvency", &signature).is_err());
+        }
+        // bech32 addresses are case insensitive, base58 ones are not
+        let signature = sign_message(AddressKind::P2wpkh, &secret_key, message);
+        let upper = address(AddressKind::P2wpkh, &public_key).to_uppercase();
+        verify_message(&upper, message, &signature).unwrap();
+        let signature = sign_message(AddressKind::P2pkh, &secret_key, message);
+        let upper = address(AddressKind::P2pkh, &public_key).to_uppercase();
+        assert!(verify_message(&upper, message, &signature).is_err());
+
+        let other_key = SecretKey::from_slice(&rng.gen::<[u8; 32]>()).unwrap();
+        let other = address(AddressKind::P2wpkh, &PublicKey::from_secret_key(&secp, &other_key));
+        let signature = sign_message(AddressKind::P2wpkh, &secret_key, message);
+        assert!(verify_message(&other, message, &signature).is_err());
+    }
+}
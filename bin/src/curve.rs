@@ -0,0 +1,38 @@
+//! Pairing engines supported by the KZG backend.
+use ark_bls12_381::Bls12_381;
+use ark_bn254::Bn254;
+use ark_ec::PairingEngine;
+use ark_ff::{BigInteger, PrimeField};
+
+/// A pairing engine whose points can be exported coordinate-wise.
+pub trait Curve: PairingEngine {
+    /// Big-endian bytes of the coordinates `[x, y]` of a G1 point.
+    fn g1_to_bytes_be(g1: &Self::G1Affine) -> [Vec<u8>; 2];
+
+    /// Big-endian bytes of the coordinates `[[x.c1, x.c0], [y.c1, y.c0]]` of a G2 point,
+    /// the order expected by the EVM pairing precompile.
+    fn g2_to_bytes_be(g2: &Self::G2Affine) -> [[Vec<u8>; 2]; 2];
+}
+
+macro_rules! impl_curve {
+    ($engine:ty) => {
+        impl Curve for $engine {
+            fn g1_to_bytes_be(g1: &Self::G1Affine) -> [Vec<u8>; 2] {
+                [
+                    g1.x.into_repr().to_bytes_be(),
+                    g1.y.into_repr().to_bytes_be(),
+                ]
+            }
+
+            fn g2_to_bytes_be(g2: &Self::G2Affine) -> [[Vec<u8>; 2]; 2] {
+                [
+                    [g2.x.c1.into_repr().to_bytes_be(), g2.x.c0.into_repr().to_bytes_be()],
+                    [g2.y.c1.into_repr().to_bytes_be(), g2.y.c0.into_repr().to_bytes_be()],
+                ]
+            }
+        }
+    };
+}
+
+impl_curve!(Bn254);
+impl_curve!(Bls12_381);
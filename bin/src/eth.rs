@@ -3,58 +3,67 @@ use ark_poly::GeneralEvaluationDomain;
 use ark_bn254::{Fr, G1Affine, G2Affine, Bn254};
 use futures::executor::block_on;
 use itertools::Itertools;
+use sha3::{Digest, Keccak256};
 use web3::{
     Transport,
-    ethabi::Token,
+    ethabi::{self, Token, ParamType},
     contract::{Options, Contract},
     types::{U256, H256, Address},
 };
 use posol_core::{balance_sum, commitment::KZG10};
 
-type Proof = balance_sum::Proof<Fr, GeneralEvaluationDomain<Fr>, KZG10<Bn254>>;
+use crate::curve::Curve;
 
-fn fmt_fr(f: &mut std::fmt::Formatter<'_>, fr: &Fr) -> std::fmt::Result {
+pub type Proof<E> = balance_sum::Proof<
+    <E as ark_ec::PairingEngine>::Fr,
+    GeneralEvaluationDomain<<E as ark_ec::PairingEngine>::Fr>,
+    KZG10<E>,
+>;
+
+fn fmt_fr<E: Curve>(f: &mut std::fmt::Formatter<'_>, fr: &E::Fr) -> std::fmt::Result {
     write!(f, "0x{}", hex::encode(fr.into_repr().to_bytes_be()))
 }
 
-fn fmt_g1_affine(f: &mut std::fmt::Formatter<'_>, g1: &G1Affine) -> std::fmt::Result {
+fn fmt_g1_affine<E: Curve>(f: &mut std::fmt::Formatter<'_>, g1: &E::G1Affine) -> std::fmt::Result {
+    let [x, y] = E::g1_to_bytes_be(g1);
     write!(
         f,
         "{{ x: 0x{}, y: 0x{} }}",
-        hex::encode(g1.x.into_repr().to_bytes_be()),
-        hex::encode(g1.y.into_repr().to_bytes_be()),
+        hex::encode(x),
+        hex::encode(y),
     )
 }
 
-fn fmt_g2_affine(f: &mut std::fmt::Formatter<'_>, g2: &G2Affine) -> std::fmt::Result {
+fn fmt_g2_affine<E: Curve>(f: &mut std::fmt::Formatter<'_>, g2: &E::G2Affine) -> std::fmt::Result {
+    let [[x_c1, x_c0], [y_c1, y_c0]] = E::g2_to_bytes_be(g2);
     write!(
         f,
         "{{ x: [0x{}, 0x{}], y: [0x{}, 0x{}] }}",
-        hex::encode(g2.x.c1.into_repr().to_bytes_be()),
-        hex::encode(g2.x.c0.into_repr().to_bytes_be()),
-        hex::encode(g2.y.c1.into_repr().to_bytes_be()),
-        hex::encode(g2.y.c0.into_repr().to_bytes_be()),
+        hex::encode(x_c1),
+        hex::encode(x_c0),
+        hex::encode(y_c1),
+        hex::encode(y_c0),
     )
 }
 
-pub enum Param {
-    Fr(Fr),
-    G1Affine(G1Affine),
-    G2Affine(G2Affine),
-    Proof(Proof),
+pub enum Param<E: Curve> {
+    Fr(E::Fr),
+    G1Affine(E::G1Affine),
+    G2Affine(E::G2Affine),
+    Proof(Proof<E>),
 }
 
-impl std::fmt::Display for Param {
+impl<E: Curve> std::fmt::Display for Param<E> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Param::Fr(fr) => fmt_fr(f, fr),
-            Param::G1Affine(g1) => fmt_g1_affine(f, g1),
-            Param::G2Affine(g2) => fmt_g2_affine(f, g2),
+            Param::Fr(fr) => fmt_fr::<E>(f, fr),
+            Param::G1Affine(g1) => fmt_g1_affine::<E>(f, g1),
+            Param::G2Affine(g2) => fmt_g2_affine::<E>(f, g2),
             Param::Proof(proof) => {
                 macro_rules! with_fix {
                     ($func:ident, $prefix:expr, $val:expr, $suffix:expr) => {
                         write!(f, $prefix)?;
-                        $func(f, $val)?;
+                        $func::<E>(f, $val)?;
                         write!(f, $suffix)?;
                     };
                 }
@@ -82,7 +91,7 @@ impl std::fmt::Display for Param {
     }
 }
 
-impl<'a> Into<Token> for &'a Param {
+impl<'a> Into<Token> for &'a Param<Bn254> {
     fn into(self) -> Token {
         match self {
             Param::Fr(fr) => tokenize_fr(fr),
@@ -98,7 +107,7 @@ pub fn call_contract<T: Transport>(
     options: Options,
     contract: &Contract<T>,
     func_name: &str,
-    params: &[Param],
+    params: &[Param<Bn254>],
 ) -> H256 {
     let params = params.iter().map(Into::into).collect_vec();
     let call = async {
@@ -116,6 +125,47 @@ pub fn call_contract<T: Transport>(
     block_on(call)
 }
 
+/// Calldata of `PoSolVerifier.individualVerify` for the user at `user_index`.
+pub fn encode_individual_verify(
+    asset_name: &str,
+    data_index: usize,
+    user_index: usize,
+    balance: u128,
+    tag: &Fr,
+    tag_opening: &G1Affine,
+    balance_opening: &G1Affine,
+) -> Vec<u8> {
+    let fr_type = ParamType::Tuple(vec![ParamType::Uint(256)]);
+    let g1_type = ParamType::Tuple(vec![ParamType::Uint(256), ParamType::Uint(256)]);
+    let selector = ethabi::short_signature(
+        "individualVerify",
+        &[
+            ParamType::FixedBytes(32),
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Uint(224),
+            fr_type,
+            g1_type.clone(),
+            g1_type,
+        ],
+    );
+    // same as `PoSolVerifier.computeAssetKey`
+    let asset_key = Keccak256::digest(asset_name.as_bytes());
+    let params = ethabi::encode(&[
+        Token::FixedBytes(asset_key.to_vec()),
+        Token::Uint(U256::from(data_index)),
+        Token::Uint(U256::from(user_index)),
+        Token::Uint(U256::from(balance)),
+        tokenize_fr(tag),
+        tokenize_g1(tag_opening),
+        tokenize_g1(balance_opening),
+    ]);
+
+    let mut calldata = selector.to_vec();
+    calldata.extend(params);
+    calldata
+}
+
 // pub fn tokenize_bytes32(bytes: &[u8]) -> Token {
 //     Token::FixedBytes(bytes.to_vec())
 // }
@@ -153,7 +203,7 @@ fn tokenize_g2(g2: &G2Affine) -> Token {
     ])
 }
 
-fn tokenize_sum_proof(proof: &Proof) -> Token {
+fn tokenize_sum_proof(proof: &Proof<Bn254>) -> Token {
     Token::Tuple(vec![
         tokenize_fr(&proof.evaluations.b),
         tokenize_fr(&proof.evaluations.t),
@@ -174,3 +224,26 @@ fn tokenize_sum_proof(proof: &Proof) -> Token {
         tokenize_g1(&proof.sw_opening.w),
     ])
 }
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::g1::{G1_GENERATOR_X, G1_GENERATOR_Y};
+    use super::*;
+
+    #[test]
+    fn test_encode_individual_verify() {
+        // the generator of G1 is (1, 2)
+        let g1 = G1Affine::new(G1_GENERATOR_X, G1_GENERATOR_Y, false);
+        let calldata = encode_individual_verify("BTC", 5, 6, 7, &Fr::from(8u64), &g1, &g1);
+        let word = |k: usize| &calldata[4 + 32 * k..4 + 32 * (k + 1)];
+
+        let signature = "individualVerify(bytes32,uint256,uint256,uint224,(uint256),(uint256,uint256),(uint256,uint256))";
+        assert_eq!(calldata[..4], Keccak256::digest(signature.as_bytes())[..4]);
+        assert_eq!(calldata.len(), 4 + 32 * 9);
+        assert_eq!(word(0), &Keccak256::digest(b"BTC")[..]);
+        for (k, value) in [5u8, 6, 7, 8, 1, 2, 1, 2].into_iter().enumerate() {
+            assert!(word(k + 1)[..31].iter().all(|&b| b == 0));
+            assert_eq!(word(k + 1)[31], value);
+        }
+    }
+}
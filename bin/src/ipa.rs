@@ -0,0 +1,142 @@
+//! Transparent backend: IPA commitments over Pallas, which need no trusted setup.
+//!
+//! Proofs are not verifiable on chain, so they are exported as hex-encoded
+//! canonical serializations and bound to a Merlin transcript.
+use std::path::PathBuf;
+use ark_ff::{FromBytes, PrimeField, BigInteger};
+use ark_pallas::{Affine, Fr};
+use ark_poly::{GeneralEvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::{PolynomialCommitment, LabeledPolynomial};
+use ark_serialize::*;
+use blake2::Blake2s;
+use rand_core::{CryptoRng, RngCore};
+use posol_core::{balance_sum::{self, MerlinTranscript}, tag, inclusion, commitment::*};
+
+use crate::{UserInfo, mem, parser::*};
+
+type PC = IPA<Affine, Blake2s>;
+type Domain = GeneralEvaluationDomain<Fr>;
+
+#[derive(Debug, CanonicalSerialize, CanonicalDeserialize)]
+struct Witness {
+    pub tag_commit: IPACommitment<Affine, Blake2s>,
+    pub labeled_tag_poly: LabeledPolynomial<Fr, DensePolynomial<Fr>>,
+    pub b_commit: IPACommitment<Affine, Blake2s>,
+    pub labeled_b_poly: LabeledPolynomial<Fr, DensePolynomial<Fr>>,
+}
+
+fn to_hex<S: CanonicalSerialize>(item: &S) -> String {
+    let mut bytes = Vec::new();
+    item.serialize(&mut bytes).expect("serialization failed");
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn fr_to_hex(fr: &Fr) -> String {
+    format!("0x{}", hex::encode(fr.into_repr().to_bytes_be()))
+}
+
+pub fn setup<R: RngCore>(domain_size: usize, ck_path: &PathBuf, cvk_path: &PathBuf, rng: &mut R) {
+    let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+    // Generators are derived by hashing, the rng is not used.
+    let pp = PC::setup(max_degree, None, rng)
+        .expect("invalid max degree");
+    let (ck, cvk) = PC::trim(
+        &pp,
+        max_degree,
+        0,
+        None,
+    ).unwrap();
+
+    ser_to_file(&ck, ck_path);
+    ser_to_file(&cvk, cvk_path);
+}
+
+pub fn prove_and_commit<R: CryptoRng + RngCore>(
+    domain_size: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    tags: &[[u8; 32]],
+    balances: &[u64],
+    witness_path: &PathBuf,
+    rng: &mut R,
+) {
+    let ck: IPACommitterKey<Affine, Blake2s> = deser_from_file(ck_path);
+    let cvk: IPAVerifierKey<Affine, Blake2s> = deser_from_file(cvk_path);
+    let tags = tags.iter().map(|t| &t[..]).collect::<Vec<_>>();
+
+    let (labeled_t_poly, t_commit) =
+        balance_sum::precompute::<_, Domain, PC>(&ck, domain_size)
+            .expect("precompute failed");
+
+    // commit for tags first
+    let (tag_commit, labeled_tag_poly) =
+        tag::commit::<_, Domain, PC>(&ck, domain_size, &tags)
+            .expect("commit to tags failed");
+
+    // prove and commit for balances sum
+    let (m, proof, labeled_b_poly) =
+        balance_sum::prove::<_, Domain, PC, MerlinTranscript, _>(
+            &ck,
+            domain_size,
+            &labeled_t_poly,
+            &t_commit,
+            balances,
+            rng,
+        ).expect("prove for balances sum failed");
+
+    proof.verify::<MerlinTranscript>(&cvk, domain_size, &t_commit, m)
+        .expect("proof verification failed");
+
+    let witness = Witness {
+        tag_commit,
+        labeled_tag_poly,
+        b_commit: proof.b_commit,
+        labeled_b_poly,
+    };
+    ser_to_file(&witness, witness_path);
+    mem::print_peak_memory();
+
+    println!("tag commitment: {}", to_hex(&tag_commit));
+    println!("proof: {}", to_hex(&proof));
+    println!("balance sum: {}", fr_to_hex(&m));
+}
+
+pub fn supply_witness(
+    domain_size: usize,
+    user_index: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    user_info: &UserInfo,
+    witness_path: &PathBuf,
+) {
+    let ck: IPACommitterKey<Affine, Blake2s> = deser_from_file(ck_path);
+    let cvk: IPAVerifierKey<Affine, Blake2s> = deser_from_file(cvk_path);
+    let witness: Witness = deser_from_file(witness_path);
+
+    let opening = inclusion::joint_open::<_, Domain, PC, MerlinTranscript>(
+        &ck,
+        domain_size,
+        user_index,
+        &witness.labeled_tag_poly,
+        &witness.tag_commit,
+        &witness.labeled_b_poly,
+        &witness.b_commit,
+    ).expect("joint open for tag and balance failed");
+
+    inclusion::joint_verify::<_, Domain, PC, MerlinTranscript>(
+        &cvk,
+        domain_size,
+        user_index,
+        &user_info.tag,
+        user_info.balance as u128,
+        &witness.tag_commit,
+        &witness.b_commit,
+        &opening,
+    ).expect("joint verify for tag and balance failed");
+
+    let tag = Fr::read(&user_info.tag[..]).expect("invalid tag");
+    println!("user index: {}", user_index);
+    println!("tag: {}", fr_to_hex(&tag));
+    println!("balance: {}", user_info.balance);
+    println!("opening: {}", to_hex(&opening));
+}
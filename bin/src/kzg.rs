@@ -0,0 +1,597 @@
+//! KZG backend, generic over the pairing engine.
+use std::{fs::File, io::{BufReader, BufWriter, Seek, SeekFrom}, path::PathBuf};
+use ark_ff::FromBytes;
+use ark_poly::{GeneralEvaluationDomain, univariate::DensePolynomial, EvaluationDomain};
+use ark_poly_commit::{PolynomialCommitment, LabeledPolynomial};
+use ark_serialize::*;
+use rand_core::{CryptoRng, RngCore};
+use sha3::{Digest, Keccak256};
+use posol_core::{
+    balance_sum::{self, MerlinTranscript},
+    fk20,
+    tag,
+    inclusion,
+    srs::{ceremony::Ceremony, lagrange::LagrangeBasis, ptau},
+    util::EvaluationDomainExt,
+    commitment::*,
+};
+use posol_verifier::{json::JsonBundle, Bundle, BundlesHeader};
+
+use crate::{UserInfo, mem, curve::Curve, eth::{self, Param}, parser::*, reserves, solvency, transcript::Transcript};
+
+#[derive(Debug, CanonicalSerialize, CanonicalDeserialize)]
+struct Witness<E: Curve> {
+    pub tag_commit: KZG10Commitment<E>,
+    pub labeled_tag_poly: LabeledPolynomial<E::Fr, DensePolynomial<E::Fr>>,
+    pub b_commit: KZG10Commitment<E>,
+    pub labeled_b_poly: LabeledPolynomial<E::Fr, DensePolynomial<E::Fr>>,
+}
+
+/// Openings of the tag and balance checked separately, as `PoSolVerifier.individualVerify` does.
+pub struct SeparateOpenings<E: Curve> {
+    pub tag: E::Fr,
+    pub tag_opening: E::G1Affine,
+    pub b_opening: E::G1Affine,
+}
+
+fn to_bytes<S: CanonicalSerialize>(item: &S) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    item.serialize_unchecked(&mut bytes).expect("serialization failed");
+    bytes
+}
+
+pub fn setup<E: Curve, R: RngCore>(domain_size: usize, ck_path: &PathBuf, cvk_path: &PathBuf, rng: &mut R) {
+    let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+    let pp = KZG10::<E>::setup(max_degree, None, rng)
+        .expect("invalid max degree");
+    let (ck, cvk) = KZG10::<E>::trim(
+        &pp,
+        max_degree,
+        0,
+        None,
+    ).unwrap();
+
+    ser_to_file(&ck, ck_path);
+    ser_to_file(&cvk, cvk_path);
+}
+
+/// Build the keys from a snarkjs `.ptau` file instead of a locally sampled secret.
+pub fn import_ptau<E: Curve, R: RngCore + CryptoRng>(
+    domain_size: usize,
+    ptau_path: &PathBuf,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    rng: &mut R,
+) {
+    let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+    let file = File::open(ptau_path).expect("failed to open ptau file");
+    let powers = ptau::read::<E, _>(BufReader::new(file), max_degree)
+        .expect("failed to read ptau file");
+    powers.verify(rng).expect("invalid powers of tau");
+    let (ck, cvk) = powers.trim(max_degree).unwrap();
+
+    ser_to_file(&ck, ck_path);
+    ser_to_file(&cvk, cvk_path);
+}
+
+/// Start a ceremony whose keys will support `domain_size`.
+pub fn ceremony_init<E: Curve>(domain_size: usize, ceremony_path: &PathBuf) {
+    let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+    ser_compressed_to_file(&Ceremony::<E>::new(max_degree), ceremony_path);
+}
+
+/// Contribute a fresh secret to the ceremony at `prev_path`, writing the result to `ceremony_path`.
+pub fn ceremony_contribute<E: Curve, R: RngCore + CryptoRng>(prev_path: &PathBuf, ceremony_path: &PathBuf, rng: &mut R) {
+    let mut ceremony: Ceremony<E> = deser_checked_from_file(prev_path);
+    ceremony.contribute::<MerlinTranscript, _>(rng)
+        .expect("contribution failed");
+    let contribution = ceremony.contributions.last().unwrap();
+
+    ser_compressed_to_file(&ceremony, ceremony_path);
+
+    println!("contribution index: {}", ceremony.contributions.len() - 1);
+    println!("s G: {}", Param::<E>::G1Affine(contribution.s_g));
+}
+
+/// Verify the ceremony at `ceremony_path`, either its last contribution on top of
+/// `prev_path` or all of it, and optionally that it produced the given keys.
+pub fn ceremony_verify<E: Curve, R: RngCore + CryptoRng>(
+    domain_size: usize,
+    ceremony_path: &PathBuf,
+    prev_path: Option<&PathBuf>,
+    keys_path: Option<(&PathBuf, &PathBuf)>,
+    rng: &mut R,
+) {
+    let ceremony: Ceremony<E> = deser_checked_from_file(ceremony_path);
+    if let Some(prev_path) = prev_path {
+        let prev: Ceremony<E> = deser_checked_from_file(prev_path);
+        ceremony.verify_contribution::<MerlinTranscript, _>(&prev, rng)
+            .expect("invalid contribution");
+        println!("contribution {} is valid", prev.contributions.len());
+    } else {
+        ceremony.verify::<MerlinTranscript, _>(rng)
+            .expect("invalid ceremony");
+        println!("all {} contributions are valid", ceremony.contributions.len());
+    }
+
+    if let Some((ck_path, cvk_path)) = keys_path {
+        let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+        let (ck, cvk) = ceremony.powers.trim(max_degree)
+            .expect("trim powers of tau failed");
+        let expected_ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+        let expected_cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+        assert!(
+            to_bytes(&ck) == to_bytes(&expected_ck) && to_bytes(&cvk) == to_bytes(&expected_cvk),
+            "keys were not produced by the ceremony",
+        );
+        println!("keys match the ceremony");
+    }
+}
+
+/// Verify the whole ceremony and write the keys of its final powers.
+pub fn ceremony_finalize<E: Curve, R: RngCore + CryptoRng>(
+    domain_size: usize,
+    ceremony_path: &PathBuf,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    rng: &mut R,
+) {
+    let max_degree = if cfg!(feature = "blinding") { domain_size + 3 } else { domain_size };
+    let ceremony: Ceremony<E> = deser_checked_from_file(ceremony_path);
+    let (ck, cvk) = ceremony.finalize::<MerlinTranscript, _>(max_degree, rng)
+        .expect("finalize ceremony failed");
+
+    ser_to_file(&ck, ck_path);
+    ser_to_file(&cvk, cvk_path);
+}
+
+/// Print the parameters of the on-chain verifier, and write the commitment to
+/// t(X) to `t_commit_path` if given, to be published next to the verifier key.
+pub fn print_params<E: Curve>(domain_size: usize, ck_path: &PathBuf, cvk_path: &PathBuf, t_commit_path: Option<&PathBuf>) {
+    let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+
+    let domain = GeneralEvaluationDomain::<E::Fr>::new(domain_size)
+        .expect("invalid domain size");
+
+    let (_, t_commit) =
+        balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<E>>(&ck, domain_size)
+            .expect("precompute failed");
+
+    println!("domain group gen: {}", Param::<E>::Fr(domain.group_gen()));
+    println!("domain group gen inv: {}", Param::<E>::Fr(domain.group_gen_inv()));
+
+    println!("G: {}", Param::<E>::G1Affine(cvk.g));
+    println!("H: {}", Param::<E>::G2Affine(cvk.h));
+    println!("Beta H: {}", Param::<E>::G2Affine(cvk.beta_h));
+
+    println!("t commit: {}", Param::<E>::G1Affine(t_commit.0));
+    if let Some(t_commit_path) = t_commit_path {
+        ser_compressed_to_file(&t_commit, t_commit_path);
+    }
+}
+
+/// Prove the balance sum of the users with `tags` and `balances`, returning the tag commitment, the proof and the sum.
+pub fn prove_and_commit<E: Curve, R: CryptoRng + RngCore>(
+    domain_size: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    tags: &[[u8; 32]],
+    balances: &[u64],
+    witness_path: &PathBuf,
+    rng: &mut R,
+) -> (KZG10Commitment<E>, eth::Proof<E>, E::Fr) {
+    let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+    let tags = tags.iter().map(|t| &t[..]).collect::<Vec<_>>();
+
+    let prover = balance_sum::Prover::<_, GeneralEvaluationDomain<_>, KZG10<E>>::new(ck, domain_size)
+        .expect("precompute failed");
+
+    // commit for tags first
+    let (tag_commit, labeled_tag_poly) =
+        tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+            prover.committer_key(),
+            domain_size,
+            &tags,
+        )
+        .expect("commit to tags failed");
+
+    // prove and commit for balances sum
+    let (m, proof, labeled_b_poly) = prover
+        .prove::<Transcript<E>, _>(balances, rng)
+        .expect("prove for balances sum failed");
+
+    prover
+        .verifier(cvk)
+        .verify::<Transcript<E>>(&proof, m)
+        .expect("proof verification failed");
+
+    let witness = Witness::<E> {
+        tag_commit,
+        labeled_tag_poly,
+        b_commit: proof.b_commit,
+        labeled_b_poly,
+    };
+    ser_to_file(&witness, witness_path);
+    mem::print_peak_memory();
+
+    (tag_commit, proof, m)
+}
+
+/// Prove and verify the total of the balances of several assets weighted by
+/// their `prices`, given the witnesses of their balance sum proofs.
+pub fn priced_sum<E: Curve, R: CryptoRng + RngCore>(
+    domain_size: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    witness_paths: &[PathBuf],
+    prices: &[u64],
+    rng: &mut R,
+) -> E::Fr {
+    assert_eq!(witness_paths.len(), prices.len(), "one price per witness required");
+    let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+    let witnesses = witness_paths
+        .iter()
+        .map(deser_from_file::<Witness<E>>)
+        .collect::<Vec<_>>();
+    let labeled_b_polys = witnesses.iter().map(|w| &w.labeled_b_poly).collect::<Vec<_>>();
+    let b_commits = witnesses.iter().map(|w| w.b_commit).collect::<Vec<_>>();
+
+    let (total, proof) =
+        balance_sum::priced::prove::<_, GeneralEvaluationDomain<_>, KZG10<E>, Transcript<E>, _>(
+            &ck,
+            domain_size,
+            prices,
+            &labeled_b_polys,
+            &b_commits,
+            rng,
+        )
+        .expect("prove for priced sum failed");
+
+    proof
+        .verify::<Transcript<E>>(&cvk, domain_size, prices, &b_commits, total)
+        .expect("priced sum verification failed");
+    mem::print_peak_memory();
+
+    total
+}
+
+/// Keccak digest of the tag and balance commitments of the witnesses, which
+/// reserve attestations are bound to.
+pub fn liabilities_digest<E: Curve>(witness_paths: &[PathBuf]) -> [u8; 32] {
+    let commits = witness_paths
+        .iter()
+        .map(|path| {
+            let witness: Witness<E> = deser_from_file(path);
+            (witness.tag_commit, witness.b_commit)
+        })
+        .collect::<Vec<_>>();
+
+    commitments_digest::<E>(&commits)
+}
+
+/// Keccak digest of pairs of tag and balance commitments.
+pub fn commitments_digest<E: Curve>(commits: &[(KZG10Commitment<E>, KZG10Commitment<E>)]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    for (tag_commit, b_commit) in commits {
+        hasher.update(to_bytes(tag_commit));
+        hasher.update(to_bytes(b_commit));
+    }
+
+    hasher.finalize().into()
+}
+
+/// Issue a solvency certificate of `assets` from the witnesses and the
+/// balance sum proofs written by `prove-and-commit`.
+#[allow(clippy::too_many_arguments)]
+pub fn issue_solvency<E: Curve>(
+    domain_size: usize,
+    cvk_path: &PathBuf,
+    t_commit_path: &PathBuf,
+    epoch: u64,
+    ratio_bps: u64,
+    assets: &[solvency::AssetFiles],
+    attestations: Vec<reserves::Attestation>,
+    snapshot: Vec<reserves::ReserveBalance>,
+    key: &secp256k1::SecretKey,
+) -> solvency::SolvencyCertificate {
+    let verifier = solvency_verifier::<E>(domain_size, cvk_path, t_commit_path);
+    let assets = assets
+        .iter()
+        .map(|files| {
+            let witness: Witness<E> = deser_from_file(&files.witness_path);
+            let (m, proof): (E::Fr, eth::Proof<E>) = deser_from_file(&files.proof_path);
+            assert_eq!(witness.b_commit, proof.b_commit, "proof of {} is not for its witness", files.asset);
+            solvency::SolvencyAsset {
+                asset: files.asset.clone(),
+                chain: files.chain,
+                tag_commit: witness.tag_commit,
+                proof: solvency::LiabilityProof::Sum(m, proof),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    solvency::issue(&verifier, epoch, ratio_bps, &assets, attestations, snapshot, key)
+        .expect("issue solvency certificate failed")
+}
+
+/// Check a solvency certificate end to end, signed by `signer` if given.
+pub fn verify_solvency<E: Curve>(
+    domain_size: usize,
+    cvk_path: &PathBuf,
+    t_commit_path: &PathBuf,
+    certificate: &solvency::SolvencyCertificate,
+    signer: Option<&str>,
+) {
+    let verifier = solvency_verifier::<E>(domain_size, cvk_path, t_commit_path);
+    solvency::verify(&verifier, certificate, signer).expect("solvency certificate verification failed");
+}
+
+/// Balance sum verifier, given the commitment to t(X) written by `print-params`.
+fn solvency_verifier<E: Curve>(domain_size: usize, cvk_path: &PathBuf, t_commit_path: &PathBuf) -> solvency::Verifier<E> {
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+    let t_commit: KZG10Commitment<E> = deser_checked_from_file(t_commit_path);
+
+    solvency::Verifier::<E>::new(cvk, domain_size, t_commit).expect("invalid domain size")
+}
+
+/// Update the witness of the previous snapshot to the users of `users_data`,
+/// touching only the changed users. The Lagrange basis is read from
+/// `lagrange_path`, or derived from the committer key and cached there.
+/// The balance sum of the new snapshot must still be proved again.
+pub fn update_commit<E: Curve>(
+    domain_size: usize,
+    ck_path: &PathBuf,
+    lagrange_path: &PathBuf,
+    prev_users_data: &[UserInfo],
+    users_data: &[UserInfo],
+    witness_path: &PathBuf,
+) -> (KZG10Commitment<E>, KZG10Commitment<E>) {
+    assert_eq!(prev_users_data.len(), users_data.len(), "users must be updated in place");
+
+    let basis: LagrangeBasis<KZG10Commitment<E>> = if lagrange_path.exists() {
+        deser_from_file(lagrange_path)
+    } else {
+        let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+        let basis = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, domain_size)
+            .expect("derive lagrange basis failed");
+        ser_to_file(&basis, lagrange_path);
+        basis
+    };
+    assert_eq!(basis.size(), domain_size, "lagrange basis is for another domain size");
+    let witness: Witness<E> = deser_from_file(witness_path);
+
+    let changed = prev_users_data
+        .iter()
+        .zip(users_data)
+        .enumerate()
+        .filter(|(_, (prev, ui))| prev.tag != ui.tag || prev.balance != ui.balance)
+        .collect::<Vec<_>>();
+    let tag_updates = changed
+        .iter()
+        .filter(|(_, (prev, ui))| prev.tag != ui.tag)
+        .map(|&(i, (prev, ui))| (i, &prev.tag[..], &ui.tag[..]))
+        .collect::<Vec<_>>();
+    let b_updates = changed
+        .iter()
+        .filter(|(_, (prev, ui))| prev.balance != ui.balance)
+        .map(|&(i, (prev, ui))| (i, prev.balance, ui.balance))
+        .collect::<Vec<_>>();
+    println!("changed users: {}", changed.len());
+
+    let tag_commit =
+        tag::update_commit::<_, KZG10<E>>(&basis, &witness.tag_commit, &tag_updates)
+            .expect("update tag commitment failed");
+    let labeled_tag_poly =
+        tag::update_poly::<_, GeneralEvaluationDomain<_>>(domain_size, &witness.labeled_tag_poly, &tag_updates)
+            .expect("update tag polynomial failed");
+    let b_commit =
+        balance_sum::update_commit::<_, KZG10<E>>(&basis, &witness.b_commit, &b_updates)
+            .expect("update balance commitment failed");
+    let labeled_b_poly =
+        balance_sum::update_poly::<_, GeneralEvaluationDomain<_>>(domain_size, &witness.labeled_b_poly, &b_updates)
+            .expect("update balance polynomial failed");
+
+    let witness = Witness::<E> {
+        tag_commit,
+        labeled_tag_poly,
+        b_commit,
+        labeled_b_poly,
+    };
+    ser_to_file(&witness, witness_path);
+    mem::print_peak_memory();
+
+    (tag_commit, b_commit)
+}
+
+/// Open the tag and balance of every user at once and write their bundles to
+/// the store at `bundles_path`.
+pub fn publish_bundles<E: Curve>(
+    domain_size: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    users_data: &[UserInfo],
+    witness_path: &PathBuf,
+    bundles_path: &PathBuf,
+) {
+    let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+    let witness: Witness<E> = deser_from_file(witness_path);
+
+    let mut openings = fk20::open_all::<_, GeneralEvaluationDomain<_>>(
+        &ck,
+        domain_size,
+        &[&witness.labeled_tag_poly, &witness.labeled_b_poly],
+    ).expect("open all users failed");
+    let b_openings = openings.pop().expect("balance openings");
+    let tag_openings = openings.pop().expect("tag openings");
+
+    // Spot check a few of the openings.
+    let step = (users_data.len() / 16).max(1);
+    for i in (0..users_data.len()).step_by(step) {
+        tag::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+            &cvk,
+            domain_size,
+            i,
+            &users_data[i].tag,
+            &witness.tag_commit,
+            &tag_openings[i],
+        ).expect("individual verify for tag failed");
+        balance_sum::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+            &cvk,
+            domain_size,
+            i,
+            users_data[i].balance as u128,
+            &witness.b_commit,
+            &b_openings[i],
+        ).expect("individual verify for balance failed");
+    }
+
+    let file = File::create(bundles_path)
+        .unwrap_or_else(|_| panic!("unable to open file {:?}", bundles_path));
+    let mut writer = BufWriter::new(file);
+    let header = BundlesHeader::<E> {
+        users: users_data.len() as u64,
+        tag_commit: witness.tag_commit,
+        b_commit: witness.b_commit,
+    };
+    header.serialize(&mut writer).expect("unable to write bundles");
+    for (i, user_info) in users_data.iter().enumerate() {
+        let bundle = Bundle::<E> {
+            tag: E::Fr::read(&user_info.tag[..]).expect("invalid tag"),
+            balance: user_info.balance,
+            tag_opening: tag_openings[i].w,
+            b_opening: b_openings[i].w,
+        };
+        bundle.serialize(&mut writer).expect("unable to write bundles");
+    }
+    println!("published bundles: {}", users_data.len());
+    mem::print_peak_memory();
+}
+
+/// Read the bundle of the user at `user_index` from the store at `bundles_path`
+/// and check it against the commitments of the store, writing it along with
+/// the verifier key and commitments as a JSON bundle to `json_path` if given.
+pub fn supply_bundle<E: Curve>(
+    domain_size: usize,
+    user_index: usize,
+    cvk_path: &PathBuf,
+    bundles_path: &PathBuf,
+    json_path: Option<&PathBuf>,
+) -> Bundle<E> {
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+
+    let file = File::open(bundles_path)
+        .unwrap_or_else(|_| panic!("unable to open file {:?}", bundles_path));
+    let mut reader = BufReader::new(file);
+    let header = BundlesHeader::<E>::read(&mut reader).expect("unable to read bundles header");
+    let offset = header.bundle_offset(user_index).expect("user index out of range");
+    reader.seek(SeekFrom::Start(offset as u64)).expect("unable to seek bundle");
+    let bundle = Bundle::<E>::read(&mut reader).expect("unable to read bundle");
+
+    bundle
+        .verify(&cvk, domain_size, user_index, &header)
+        .expect("individual verify for tag and balance failed");
+    if let Some(json_path) = json_path {
+        json_to_file(&JsonBundle::new(&cvk, domain_size, user_index, &header, &bundle), json_path);
+    }
+
+    println!("user index: {}", user_index);
+    println!("tag: {}", Param::<E>::Fr(bundle.tag));
+    println!("balance: {}", bundle.balance);
+    println!("tag commitment: {}", Param::<E>::G1Affine(header.tag_commit.0));
+    println!("balance commitment: {}", Param::<E>::G1Affine(header.b_commit.0));
+    println!("tag opening: {}", Param::<E>::G1Affine(bundle.tag_opening));
+    println!("balance opening: {}", Param::<E>::G1Affine(bundle.b_opening));
+
+    bundle
+}
+
+/// Print the joint opening of the user at `user_index`, and return the separate
+/// openings too if `separate` is set.
+pub fn supply_witness<E: Curve>(
+    domain_size: usize,
+    user_index: usize,
+    ck_path: &PathBuf,
+    cvk_path: &PathBuf,
+    user_info: &UserInfo,
+    witness_path: &PathBuf,
+    separate: bool,
+) -> Option<SeparateOpenings<E>> {
+    let ck: KZG10CommitterKey<E> = deser_from_file(ck_path);
+    let cvk: KZG10VerifierKey<E> = deser_from_file(cvk_path);
+    let witness: Witness<E> = deser_from_file(witness_path);
+
+    let opening = inclusion::joint_open::<_, GeneralEvaluationDomain<_>, KZG10<E>, Transcript<E>>(
+        &ck,
+        domain_size,
+        user_index,
+        &witness.labeled_tag_poly,
+        &witness.tag_commit,
+        &witness.labeled_b_poly,
+        &witness.b_commit,
+    ).expect("joint open for tag and balance failed");
+
+    inclusion::joint_verify::<_, GeneralEvaluationDomain<_>, KZG10<E>, Transcript<E>>(
+        &cvk,
+        domain_size,
+        user_index,
+        &user_info.tag,
+        user_info.balance as u128,
+        &witness.tag_commit,
+        &witness.b_commit,
+        &opening,
+    ).expect("joint verify for tag and balance failed");
+
+    let tag = E::Fr::read(&user_info.tag[..]).expect("invalid tag");
+    println!("user index: {}", user_index);
+    println!("tag: {}", Param::<E>::Fr(tag));
+    println!("balance: {}", user_info.balance);
+    println!("opening: {}", Param::<E>::G1Affine(opening.w));
+
+    if !separate {
+        return None;
+    }
+
+    let tag_opening = tag::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+        &ck,
+        domain_size,
+        user_index,
+        &witness.labeled_tag_poly,
+        &witness.tag_commit,
+    ).expect("individual open for tag failed");
+
+    tag::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+        &cvk,
+        domain_size,
+        user_index,
+        &user_info.tag,
+        &witness.tag_commit,
+        &tag_opening,
+    ).expect("individual verify for tag failed");
+
+    let b_opening = balance_sum::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+        &ck,
+        domain_size,
+        user_index,
+        &witness.labeled_b_poly,
+        &witness.b_commit,
+    ).expect("individual open for balance failed");
+
+    balance_sum::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<E>>(
+        &cvk,
+        domain_size,
+        user_index,
+        user_info.balance as u128,
+        &witness.b_commit,
+        &b_opening,
+    ).expect("individual verify for balance failed");
+
+    Some(SeparateOpenings {
+        tag,
+        tag_opening: tag_opening.w,
+        b_opening: b_opening.w,
+    })
+}
@@ -1,5 +1,13 @@
+mod btc;
+mod curve;
 mod eth;
+mod ipa;
+mod kzg;
+mod mem;
 mod parser;
+mod reserves;
+mod salt;
+mod solvency;
 mod transcript;
 #[cfg(feature = "xs-rng")]
 mod xs_rng;
@@ -7,13 +15,12 @@ mod xs_rng;
 use std::path::PathBuf;
 use ark_ff::{UniformRand, ToBytes};
 use ark_bn254::{Fr, Bn254};
-use ark_poly::{GeneralEvaluationDomain, univariate::DensePolynomial, EvaluationDomain};
-use ark_poly_commit::{PolynomialCommitment, LabeledPolynomial};
-use ark_serialize::*;
-use clap::Parser;
+use ark_bls12_381::Bls12_381;
+use clap::{Parser, ValueEnum};
 use serde::{Serialize, Deserialize};
 use rand::Rng;
 use itertools::Itertools;
+use posol_core::tag;
 use web3::{
     ethabi,
     api::{Eth, Namespace},
@@ -21,10 +28,27 @@ use web3::{
     contract::Contract,
     transports::Http,
 };
-use posol_core::{balance_sum, tag, util::EvaluationDomainExt, commitment::*};
-use transcript::Transcript;
 use parser::*;
 
+/// Polynomial commitment scheme of the proofs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Backend {
+    /// KZG over a pairing-friendly curve, needs a trusted setup.
+    Kzg,
+    /// IPA over Pallas, transparent.
+    Ipa,
+}
+
+/// Pairing-friendly curve of the KZG backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CurveKind {
+    /// BN254, verifiable on chain.
+    Bn254,
+    /// BLS12-381.
+    #[value(name = "bls12-381")]
+    Bls12381,
+}
+
 #[derive(Debug, Parser)]
 #[command(name = "Proof of Solvency", version = "0.0.1", about = "Proof of Solvency Simulator", long_about = "")]
 enum Args {
@@ -36,6 +60,32 @@ enum Args {
         #[arg(long = "users-path")]
         users_path: PathBuf,
     },
+    ImportUsers {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "accounts-path")]
+        accounts_path: PathBuf,
+        #[arg(long = "users-path")]
+        users_path: PathBuf,
+        #[arg(long = "master-key-path", requires = "epoch")]
+        master_key_path: Option<PathBuf>,
+        #[arg(long = "epoch")]
+        epoch: Option<u64>,
+    },
+    GenMasterKey {
+        #[arg(long = "master-key-path")]
+        master_key_path: PathBuf,
+    },
+    ExportSalts {
+        #[arg(long = "master-key-path")]
+        master_key_path: PathBuf,
+        #[arg(long = "epoch")]
+        epoch: u64,
+        #[arg(long = "accounts-path")]
+        accounts_path: PathBuf,
+        #[arg(long = "salts-path")]
+        salts_path: PathBuf,
+    },
     SetupKZG {
         #[arg(long = "domain-size", default_value = "134217728")]
         domain_size: usize,
@@ -43,6 +93,70 @@ enum Args {
         ck_path: PathBuf,
         #[arg(long = "cvk-path")]
         cvk_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    ImportPtau {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ptau-path")]
+        ptau_path: PathBuf,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    CeremonyInit {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ceremony-path")]
+        ceremony_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    CeremonyContribute {
+        #[arg(long = "prev-path")]
+        prev_path: PathBuf,
+        #[arg(long = "ceremony-path")]
+        ceremony_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    CeremonyVerify {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ceremony-path")]
+        ceremony_path: PathBuf,
+        #[arg(long = "prev-path")]
+        prev_path: Option<PathBuf>,
+        #[arg(long = "ck-path", requires = "cvk_path")]
+        ck_path: Option<PathBuf>,
+        #[arg(long = "cvk-path", requires = "ck_path")]
+        cvk_path: Option<PathBuf>,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    CeremonyFinalize {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ceremony-path")]
+        ceremony_path: PathBuf,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    SetupIPA {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
     },
     PrintParams {
         #[arg(long = "domain-size", default_value = "134217728")]
@@ -51,6 +165,10 @@ enum Args {
         ck_path: PathBuf,
         #[arg(long = "cvk-path")]
         cvk_path: PathBuf,
+        #[arg(long = "t-commit-path")]
+        t_commit_path: Option<PathBuf>,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
     },
     ProveAndCommit {
         #[arg(long = "domain-size", default_value = "134217728")]
@@ -65,6 +183,142 @@ enum Args {
         witness_path: PathBuf,
         #[arg(long = "eth-path")]
         eth_path: Option<PathBuf>,
+        #[arg(long = "proof-path")]
+        proof_path: Option<PathBuf>,
+        #[arg(long = "backend", value_enum, default_value = "kzg")]
+        backend: Backend,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    PricedSum {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "witness-path", required = true)]
+        witness_paths: Vec<PathBuf>,
+        #[arg(long = "prices", value_delimiter = ',', required = true)]
+        prices: Vec<u64>,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    AttestReserves {
+        #[arg(long = "epoch")]
+        epoch: u64,
+        #[arg(long = "witness-path", required = true)]
+        witness_paths: Vec<PathBuf>,
+        #[arg(long = "addresses-path")]
+        addresses_path: PathBuf,
+        #[arg(long = "keys-path")]
+        keys_path: PathBuf,
+        #[arg(long = "attestations-path")]
+        attestations_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    VerifyReserves {
+        #[arg(long = "epoch")]
+        epoch: u64,
+        #[arg(long = "witness-path", required = true)]
+        witness_paths: Vec<PathBuf>,
+        #[arg(long = "attestations-path")]
+        attestations_path: PathBuf,
+        #[arg(long = "snapshot-path")]
+        snapshot_path: PathBuf,
+        #[arg(long = "report-path")]
+        report_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    IssueSolvency {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "t-commit-path")]
+        t_commit_path: PathBuf,
+        #[arg(long = "epoch")]
+        epoch: u64,
+        #[arg(long = "ratio-bps", default_value = "10000")]
+        ratio_bps: u64,
+        #[arg(long = "assets-path")]
+        assets_path: PathBuf,
+        #[arg(long = "attestations-path")]
+        attestations_path: PathBuf,
+        #[arg(long = "snapshot-path")]
+        snapshot_path: PathBuf,
+        #[arg(long = "key-path")]
+        key_path: PathBuf,
+        #[arg(long = "certificate-path")]
+        certificate_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    VerifySolvency {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "t-commit-path")]
+        t_commit_path: PathBuf,
+        #[arg(long = "certificate-path")]
+        certificate_path: PathBuf,
+        #[arg(long = "signer")]
+        signer: Option<String>,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    UpdateCommit {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "lagrange-path")]
+        lagrange_path: PathBuf,
+        #[arg(long = "prev-users-path")]
+        prev_users_path: PathBuf,
+        #[arg(long = "users-path")]
+        users_path: PathBuf,
+        #[arg(long = "witness-path")]
+        witness_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    PublishBundles {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "ck-path")]
+        ck_path: PathBuf,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "users-path")]
+        users_path: PathBuf,
+        #[arg(long = "witness-path")]
+        witness_path: PathBuf,
+        #[arg(long = "bundles-path")]
+        bundles_path: PathBuf,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
+    },
+    SupplyBundle {
+        #[arg(long = "domain-size", default_value = "134217728")]
+        domain_size: usize,
+        #[arg(long = "user-index")]
+        user_index: usize,
+        #[arg(long = "cvk-path")]
+        cvk_path: PathBuf,
+        #[arg(long = "bundles-path")]
+        bundles_path: PathBuf,
+        #[arg(long = "json-path")]
+        json_path: Option<PathBuf>,
+        #[arg(long = "asset-name")]
+        asset_name: Option<String>,
+        #[arg(long = "data-index", default_value = "0")]
+        data_index: usize,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
     },
     SupplyWitness {
         #[arg(long = "domain-size", default_value = "134217728")]
@@ -79,6 +333,14 @@ enum Args {
         users_path: PathBuf,
         #[arg(long = "witness-path")]
         witness_path: PathBuf,
+        #[arg(long = "asset-name")]
+        asset_name: Option<String>,
+        #[arg(long = "data-index", default_value = "0")]
+        data_index: usize,
+        #[arg(long = "backend", value_enum, default_value = "kzg")]
+        backend: Backend,
+        #[arg(long = "curve", value_enum, default_value = "bn254")]
+        curve: CurveKind,
     }
 }
 
@@ -109,52 +371,167 @@ fn main() {
                 .collect_vec();
             json_to_file(&users_info, &users_path);
         }
+        Args::ImportUsers {
+            domain_size,
+            accounts_path,
+            users_path,
+            master_key_path,
+            epoch,
+        } => {
+            let salts = master_key_path.map(|path| {
+                let master_key: String = json_from_file(&path);
+                salt::SaltManager::from_hex(&master_key).expect("invalid master key")
+            });
+
+            let mut users_info = Vec::new();
+            json_seq_from_file(&accounts_path, |i, account: AccountInfo| {
+                assert!(i < domain_size, "more users than the domain size");
+                let tag = match (&salts, &account.salt) {
+                    (Some(salts), None) => salts.tag(epoch.unwrap(), &account.id),
+                    (None, Some(salt)) => tag::derive(account.id.as_bytes(), salt.as_bytes()),
+                    (Some(_), Some(_)) => panic!("salt of {} given with a master key", account.id),
+                    (None, None) => panic!("no salt for {}", account.id),
+                };
+                users_info.push(UserInfo { tag, balance: account.balance });
+            });
+            json_to_file(&users_info, &users_path);
+        }
+        Args::GenMasterKey { master_key_path } => {
+            let salts = salt::SaltManager::generate(&mut rand::rngs::OsRng);
+            json_to_private_file(&salts.to_hex(), &master_key_path);
+        }
+        Args::ExportSalts {
+            master_key_path,
+            epoch,
+            accounts_path,
+            salts_path,
+        } => {
+            let master_key: String = json_from_file(&master_key_path);
+            let salts = salt::SaltManager::from_hex(&master_key).expect("invalid master key");
+
+            let mut ids = Vec::new();
+            json_seq_from_file(&accounts_path, |_, account: AccountInfo| ids.push(account.id));
+            json_to_file(&salts.export(epoch, &ids), &salts_path);
+        }
         Args::SetupKZG {
             domain_size,
             ck_path,
             cvk_path,
+            curve,
         } => {
             #[cfg(feature = "xs-rng")]
             let rng = &mut xs_rng::get_xorshift_rng();
             #[cfg(not(feature = "xs-rng"))]
             let rng = &mut rand::thread_rng();
 
-            let max_degree = if cfg!(blinding) { domain_size + 3 } else { domain_size };
-            let pp = KZG10::<Bn254>::setup(max_degree, None, rng)
-                .expect("invalid max degree");
-            let (ck, cvk) = KZG10::<Bn254>::trim(
-                &pp,
-                max_degree,
-                0,
-                None,
-            ).unwrap();
-
-            ser_to_file(&ck, &ck_path);
-            ser_to_file(&cvk, &cvk_path);
+            match curve {
+                CurveKind::Bn254 => kzg::setup::<Bn254, _>(domain_size, &ck_path, &cvk_path, rng),
+                CurveKind::Bls12381 => kzg::setup::<Bls12_381, _>(domain_size, &ck_path, &cvk_path, rng),
+            }
         }
-        Args::PrintParams {
+        Args::ImportPtau {
             domain_size,
+            ptau_path,
             ck_path,
             cvk_path,
+            curve,
         } => {
-            let ck: KZG10CommitterKey<Bn254> = deser_from_file(&ck_path);
-            let cvk: KZG10VerifierKey<Bn254> = deser_from_file(&cvk_path);
+            // The scalars batching the pairing checks must be unpredictable even with `xs-rng`.
+            let rng = &mut rand::thread_rng();
 
-            let domain = GeneralEvaluationDomain::<Fr>::new(domain_size)
-                .expect("invalid domain size");
+            match curve {
+                CurveKind::Bn254 => kzg::import_ptau::<Bn254, _>(domain_size, &ptau_path, &ck_path, &cvk_path, rng),
+                CurveKind::Bls12381 => kzg::import_ptau::<Bls12_381, _>(domain_size, &ptau_path, &ck_path, &cvk_path, rng),
+            }
+        }
+        Args::CeremonyInit {
+            domain_size,
+            ceremony_path,
+            curve,
+        } => {
+            match curve {
+                CurveKind::Bn254 => kzg::ceremony_init::<Bn254>(domain_size, &ceremony_path),
+                CurveKind::Bls12381 => kzg::ceremony_init::<Bls12_381>(domain_size, &ceremony_path),
+            }
+        }
+        Args::CeremonyContribute {
+            prev_path,
+            ceremony_path,
+            curve,
+        } => {
+            // The secret must come from a cryptographic rng even with `xs-rng`.
+            let rng = &mut rand::thread_rng();
 
-            let (_, t_commit) =
-                balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, domain_size)
-                    .expect("precompute failed");
+            match curve {
+                CurveKind::Bn254 => kzg::ceremony_contribute::<Bn254, _>(&prev_path, &ceremony_path, rng),
+                CurveKind::Bls12381 => kzg::ceremony_contribute::<Bls12_381, _>(&prev_path, &ceremony_path, rng),
+            }
+        }
+        Args::CeremonyVerify {
+            domain_size,
+            ceremony_path,
+            prev_path,
+            ck_path,
+            cvk_path,
+            curve,
+        } => {
+            let rng = &mut rand::thread_rng();
+            let keys_path = ck_path.as_ref().zip(cvk_path.as_ref());
 
-            println!("domain group gen: {}", eth::Param::Fr(domain.group_gen()));
-            println!("domain group gen inv: {}", eth::Param::Fr(domain.group_gen_inv()));
+            match curve {
+                CurveKind::Bn254 => kzg::ceremony_verify::<Bn254, _>(
+                    domain_size,
+                    &ceremony_path,
+                    prev_path.as_ref(),
+                    keys_path,
+                    rng,
+                ),
+                CurveKind::Bls12381 => kzg::ceremony_verify::<Bls12_381, _>(
+                    domain_size,
+                    &ceremony_path,
+                    prev_path.as_ref(),
+                    keys_path,
+                    rng,
+                ),
+            }
+        }
+        Args::CeremonyFinalize {
+            domain_size,
+            ceremony_path,
+            ck_path,
+            cvk_path,
+            curve,
+        } => {
+            let rng = &mut rand::thread_rng();
 
-            println!("G: {}", eth::Param::G1Affine(cvk.g));
-            println!("H: {}", eth::Param::G2Affine(cvk.h));
-            println!("Beta H: {}", eth::Param::G2Affine(cvk.beta_h));
+            match curve {
+                CurveKind::Bn254 => kzg::ceremony_finalize::<Bn254, _>(domain_size, &ceremony_path, &ck_path, &cvk_path, rng),
+                CurveKind::Bls12381 => kzg::ceremony_finalize::<Bls12_381, _>(domain_size, &ceremony_path, &ck_path, &cvk_path, rng),
+            }
+        }
+        Args::SetupIPA {
+            domain_size,
+            ck_path,
+            cvk_path,
+        } => {
+            #[cfg(feature = "xs-rng")]
+            let rng = &mut xs_rng::get_xorshift_rng();
+            #[cfg(not(feature = "xs-rng"))]
+            let rng = &mut rand::thread_rng();
 
-            println!("t commit: {}", eth::Param::G1Affine(t_commit.0));
+            ipa::setup(domain_size, &ck_path, &cvk_path, rng);
+        }
+        Args::PrintParams {
+            domain_size,
+            ck_path,
+            cvk_path,
+            t_commit_path,
+            curve,
+        } => {
+            match curve {
+                CurveKind::Bn254 => kzg::print_params::<Bn254>(domain_size, &ck_path, &cvk_path, t_commit_path.as_ref()),
+                CurveKind::Bls12381 => kzg::print_params::<Bls12_381>(domain_size, &ck_path, &cvk_path, t_commit_path.as_ref()),
+            }
         }
         Args::ProveAndCommit {
             domain_size,
@@ -163,59 +540,66 @@ fn main() {
             users_path,
             witness_path,
             eth_path,
+            proof_path,
+            backend,
+            curve,
         } => {
             #[cfg(feature = "xs-rng")]
             let rng = &mut xs_rng::get_xorshift_rng();
             #[cfg(not(feature = "xs-rng"))]
             let rng = &mut rand::thread_rng();
 
-            let ck: KZG10CommitterKey<Bn254> = deser_from_file(&ck_path);
-            let cvk: KZG10VerifierKey<Bn254> = deser_from_file(&cvk_path);
-            let users_data: Vec<UserInfo> = json_from_file(&users_path);
-            assert!(users_data.len() <= domain_size);
-            let (tags, balances): (Vec<_>, Vec<_>) = users_data
-                .iter()
-                .map(|ui| (&ui.tag[..], ui.balance))
-                .unzip();
-
-            let (labeled_t_poly, t_commit) =
-                balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, domain_size)
-                    .expect("precompute failed");
+            // Only the tags and balances are kept from the snapshot.
+            let mut tags = Vec::new();
+            let mut balances = Vec::new();
+            json_seq_from_file(&users_path, |i, user_info: UserInfo| {
+                assert!(i < domain_size, "more users than the domain size");
+                tags.push(user_info.tag);
+                balances.push(user_info.balance);
+            });
+            if backend == Backend::Ipa {
+                assert!(eth_path.is_none(), "on-chain verification requires the kzg backend");
+                assert!(proof_path.is_none(), "solvency certificates require the kzg backend");
+                ipa::prove_and_commit(domain_size, &ck_path, &cvk_path, &tags, &balances, &witness_path, rng);
+                return;
+            }
 
-            // commit for tags first
-            let (tag_commit, labeled_tag_poly) =
-                tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
-                    &ck,
+            if curve == CurveKind::Bls12381 {
+                assert!(eth_path.is_none(), "on-chain verification requires the bn254 curve");
+                let (tag_commit, proof, m) = kzg::prove_and_commit::<Bls12_381, _>(
                     domain_size,
+                    &ck_path,
+                    &cvk_path,
                     &tags,
-                )
-                .expect("commit to tags failed");
-                    
-            // prove and commit for balances sum
-            let (m, proof, labeled_b_poly) =
-                balance_sum::prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, Transcript, _>(
-                    &ck,
-                    domain_size,
-                    &labeled_t_poly,
-                    &t_commit,
                     &balances,
+                    &witness_path,
                     rng,
-                ).expect("prove for balances sum failed");
-
-            proof.verify::<Transcript>(&cvk, domain_size, &t_commit, m)
-                .expect("proof verification failed");
+                );
+                if let Some(proof_path) = &proof_path {
+                    ser_to_file(&(m, proof.clone()), proof_path);
+                }
+                println!("tag commitment: {}", eth::Param::<Bls12_381>::G1Affine(tag_commit.0));
+                println!("proof: {}", eth::Param::<Bls12_381>::Proof(proof));
+                println!("balance sum: {}", eth::Param::<Bls12_381>::Fr(m));
+                return;
+            }
 
-            let witness = Witness {
-                tag_commit: tag_commit.clone(),
-                labeled_tag_poly,
-                b_commit: proof.b_commit.clone(),
-                labeled_b_poly,
-            };
-            ser_to_file(&witness, &witness_path);
+            let (tag_commit, proof, m) = kzg::prove_and_commit::<Bn254, _>(
+                domain_size,
+                &ck_path,
+                &cvk_path,
+                &tags,
+                &balances,
+                &witness_path,
+                rng,
+            );
+            if let Some(proof_path) = &proof_path {
+                ser_to_file(&(m, proof.clone()), proof_path);
+            }
 
-            let tag_commit = eth::Param::G1Affine(tag_commit.0);
-            let proof = eth::Param::Proof(proof);
-            let m = eth::Param::Fr(m);
+            let tag_commit = eth::Param::<Bn254>::G1Affine(tag_commit.0);
+            let proof = eth::Param::<Bn254>::Proof(proof);
+            let m = eth::Param::<Bn254>::Fr(m);
             if let Some(eth_path) = eth_path {
                 let eth_config: EthConfig = json_from_file(&eth_path);
                 let transport = Http::new(&eth_config.url).expect("failed to connect to eth network");
@@ -240,59 +624,366 @@ fn main() {
                 println!("balance sum: {}", m);
             }
         }
-        Args::SupplyWitness {
+        Args::PricedSum {
             domain_size,
-            user_index,
             ck_path,
             cvk_path,
+            witness_paths,
+            prices,
+            curve,
+        } => {
+            #[cfg(feature = "xs-rng")]
+            let rng = &mut xs_rng::get_xorshift_rng();
+            #[cfg(not(feature = "xs-rng"))]
+            let rng = &mut rand::thread_rng();
+
+            match curve {
+                CurveKind::Bn254 => {
+                    let total = kzg::priced_sum::<Bn254, _>(
+                        domain_size,
+                        &ck_path,
+                        &cvk_path,
+                        &witness_paths,
+                        &prices,
+                        rng,
+                    );
+                    println!("priced sum: {}", eth::Param::<Bn254>::Fr(total));
+                }
+                CurveKind::Bls12381 => {
+                    let total = kzg::priced_sum::<Bls12_381, _>(
+                        domain_size,
+                        &ck_path,
+                        &cvk_path,
+                        &witness_paths,
+                        &prices,
+                        rng,
+                    );
+                    println!("priced sum: {}", eth::Param::<Bls12_381>::Fr(total));
+                }
+            }
+        }
+        Args::AttestReserves {
+            epoch,
+            witness_paths,
+            addresses_path,
+            keys_path,
+            attestations_path,
+            curve,
+        } => {
+            let liabilities = match curve {
+                CurveKind::Bn254 => kzg::liabilities_digest::<Bn254>(&witness_paths),
+                CurveKind::Bls12381 => kzg::liabilities_digest::<Bls12_381>(&witness_paths),
+            };
+            let addresses: Vec<reserves::ReserveAddress> = json_from_file(&addresses_path);
+            let keys: Vec<String> = json_from_file(&keys_path);
+            let keys = keys
+                .iter()
+                .map(|key| reserves::parse_secret_key(key).expect("invalid secret key"))
+                .collect_vec();
+
+            let attestations = reserves::attest(epoch, &liabilities, &addresses, &keys)
+                .expect("attest reserves failed");
+            json_to_file(&attestations, &attestations_path);
+            println!("liabilities digest: 0x{}", hex::encode(liabilities));
+            println!("attested addresses: {}", attestations.len());
+        }
+        Args::VerifyReserves {
+            epoch,
+            witness_paths,
+            attestations_path,
+            snapshot_path,
+            report_path,
+            curve,
+        } => {
+            let liabilities = match curve {
+                CurveKind::Bn254 => kzg::liabilities_digest::<Bn254>(&witness_paths),
+                CurveKind::Bls12381 => kzg::liabilities_digest::<Bls12_381>(&witness_paths),
+            };
+            let attestations: Vec<reserves::Attestation> = json_from_file(&attestations_path);
+            let snapshot: Vec<reserves::ReserveBalance> = json_from_file(&snapshot_path);
+
+            let report = reserves::report(epoch, &liabilities, &attestations, &snapshot)
+                .expect("reserves report failed");
+            json_to_file(&report, &report_path);
+            for entry in report.entries.iter().filter(|e| !e.attested) {
+                println!(
+                    "not attested: {} ({})",
+                    entry.address,
+                    entry.error.as_deref().unwrap_or_default(),
+                );
+            }
+            for total in &report.totals {
+                println!("{} reserves on {:?}: {}", total.asset, total.chain, total.balance);
+            }
+        }
+        Args::IssueSolvency {
+            domain_size,
+            cvk_path,
+            t_commit_path,
+            epoch,
+            ratio_bps,
+            assets_path,
+            attestations_path,
+            snapshot_path,
+            key_path,
+            certificate_path,
+            curve,
+        } => {
+            let assets: Vec<solvency::AssetFiles> = json_from_file(&assets_path);
+            let attestations: Vec<reserves::Attestation> = json_from_file(&attestations_path);
+            let snapshot: Vec<reserves::ReserveBalance> = json_from_file(&snapshot_path);
+            let key: String = json_from_file(&key_path);
+            let key = reserves::parse_secret_key(&key).expect("invalid secret key");
+
+            let certificate = match curve {
+                CurveKind::Bn254 => kzg::issue_solvency::<Bn254>(
+                    domain_size,
+                    &cvk_path,
+                    &t_commit_path,
+                    epoch,
+                    ratio_bps,
+                    &assets,
+                    attestations,
+                    snapshot,
+                    &key,
+                ),
+                CurveKind::Bls12381 => kzg::issue_solvency::<Bls12_381>(
+                    domain_size,
+                    &cvk_path,
+                    &t_commit_path,
+                    epoch,
+                    ratio_bps,
+                    &assets,
+                    attestations,
+                    snapshot,
+                    &key,
+                ),
+            };
+            json_to_file(&certificate, &certificate_path);
+            println!("signer: {}", certificate.signer);
+            for asset in &certificate.statement.assets {
+                println!("{}: liabilities {}, reserves {}", asset.asset, asset.liabilities, asset.reserves);
+            }
+        }
+        Args::VerifySolvency {
+            domain_size,
+            cvk_path,
+            t_commit_path,
+            certificate_path,
+            signer,
+            curve,
+        } => {
+            let certificate: solvency::SolvencyCertificate = json_from_file(&certificate_path);
+            match curve {
+                CurveKind::Bn254 => kzg::verify_solvency::<Bn254>(
+                    domain_size,
+                    &cvk_path,
+                    &t_commit_path,
+                    &certificate,
+                    signer.as_deref(),
+                ),
+                CurveKind::Bls12381 => kzg::verify_solvency::<Bls12_381>(
+                    domain_size,
+                    &cvk_path,
+                    &t_commit_path,
+                    &certificate,
+                    signer.as_deref(),
+                ),
+            }
+            let statement = &certificate.statement;
+            println!("epoch {} solvent at {} bps, signed by {}", statement.epoch, statement.ratio_bps, certificate.signer);
+            for asset in &statement.assets {
+                println!("{}: liabilities {}, reserves {}", asset.asset, asset.liabilities, asset.reserves);
+            }
+        }
+        Args::UpdateCommit {
+            domain_size,
+            ck_path,
+            lagrange_path,
+            prev_users_path,
             users_path,
             witness_path,
+            curve,
         } => {
-            let ck: KZG10CommitterKey<Bn254> = deser_from_file(&ck_path);
-            let cvk: KZG10VerifierKey<Bn254> = deser_from_file(&cvk_path);
-            let witness: Witness = deser_from_file(&witness_path);
+            let prev_users_data: Vec<UserInfo> = json_from_file(&prev_users_path);
             let users_data: Vec<UserInfo> = json_from_file(&users_path);
-            assert!(users_data.len() <= domain_size);
-
-            let tag_opening = tag::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
-                &ck,
-                domain_size,
-                user_index,
-                &witness.labeled_tag_poly,
-                &witness.tag_commit,
-            ).expect("individual open for tag failed");
+            assert!(users_data.len() <= domain_size, "more users than the domain size");
 
-            tag::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
-                &cvk,
-                domain_size,
-                user_index,
-                &users_data[user_index].tag,
-                &witness.tag_commit,
-                &tag_opening,
-            ).expect("individual verify for tag failed");
+            match curve {
+                CurveKind::Bn254 => {
+                    let (tag_commit, b_commit) = kzg::update_commit::<Bn254>(
+                        domain_size,
+                        &ck_path,
+                        &lagrange_path,
+                        &prev_users_data,
+                        &users_data,
+                        &witness_path,
+                    );
+                    println!("tag commitment: {}", eth::Param::<Bn254>::G1Affine(tag_commit.0));
+                    println!("balance commitment: {}", eth::Param::<Bn254>::G1Affine(b_commit.0));
+                }
+                CurveKind::Bls12381 => {
+                    let (tag_commit, b_commit) = kzg::update_commit::<Bls12_381>(
+                        domain_size,
+                        &ck_path,
+                        &lagrange_path,
+                        &prev_users_data,
+                        &users_data,
+                        &witness_path,
+                    );
+                    println!("tag commitment: {}", eth::Param::<Bls12_381>::G1Affine(tag_commit.0));
+                    println!("balance commitment: {}", eth::Param::<Bls12_381>::G1Affine(b_commit.0));
+                }
+            }
+        }
+        Args::PublishBundles {
+            domain_size,
+            ck_path,
+            cvk_path,
+            users_path,
+            witness_path,
+            bundles_path,
+            curve,
+        } => {
+            let mut users_data = Vec::new();
+            json_seq_from_file(&users_path, |i, user_info: UserInfo| {
+                assert!(i < domain_size, "more users than the domain size");
+                users_data.push(user_info);
+            });
 
-            let tag_opening = eth::Param::G1Affine(tag_opening.w);
-            println!("tag opening: {}", tag_opening);
+            match curve {
+                CurveKind::Bn254 => kzg::publish_bundles::<Bn254>(
+                    domain_size,
+                    &ck_path,
+                    &cvk_path,
+                    &users_data,
+                    &witness_path,
+                    &bundles_path,
+                ),
+                CurveKind::Bls12381 => kzg::publish_bundles::<Bls12_381>(
+                    domain_size,
+                    &ck_path,
+                    &cvk_path,
+                    &users_data,
+                    &witness_path,
+                    &bundles_path,
+                ),
+            }
+        }
+        Args::SupplyBundle {
+            domain_size,
+            user_index,
+            cvk_path,
+            bundles_path,
+            json_path,
+            asset_name,
+            data_index,
+            curve,
+        } => {
+            if curve == CurveKind::Bls12381 {
+                assert!(asset_name.is_none(), "on-chain verification requires the bn254 curve");
+                kzg::supply_bundle::<Bls12_381>(
+                    domain_size,
+                    user_index,
+                    &cvk_path,
+                    &bundles_path,
+                    json_path.as_ref(),
+                );
+                return;
+            }
 
-            let b_opening = balance_sum::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
-                &ck,
+            let bundle = kzg::supply_bundle::<Bn254>(
                 domain_size,
                 user_index,
-                &witness.labeled_b_poly,
-                &witness.b_commit,
-            ).expect("individual open for balance failed");
+                &cvk_path,
+                &bundles_path,
+                json_path.as_ref(),
+            );
+            if let Some(asset_name) = asset_name {
+                let calldata = eth::encode_individual_verify(
+                    &asset_name,
+                    data_index,
+                    user_index,
+                    bundle.balance as u128,
+                    &bundle.tag,
+                    &bundle.tag_opening,
+                    &bundle.b_opening,
+                );
+                println!("individualVerify calldata: 0x{}", hex::encode(calldata));
+            }
+        }
+        Args::SupplyWitness {
+            domain_size,
+            user_index,
+            ck_path,
+            cvk_path,
+            users_path,
+            witness_path,
+            asset_name,
+            data_index,
+            backend,
+            curve,
+        } => {
+            // Only the requested user is kept in memory
+            let mut user_info = None;
+            json_seq_from_file(&users_path, |i, ui: UserInfo| {
+                assert!(i < domain_size, "more users than the domain size");
+                if i == user_index {
+                    user_info = Some(ui);
+                }
+            });
+            let user_info = user_info.expect("user index out of range");
+            if backend == Backend::Ipa {
+                assert!(asset_name.is_none(), "on-chain verification requires the kzg backend");
+                ipa::supply_witness(
+                    domain_size,
+                    user_index,
+                    &ck_path,
+                    &cvk_path,
+                    &user_info,
+                    &witness_path,
+                );
+                return;
+            }
 
-            balance_sum::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
-                &cvk,
+            let user_info = &user_info;
+            if curve == CurveKind::Bls12381 {
+                assert!(asset_name.is_none(), "on-chain verification requires the bn254 curve");
+                kzg::supply_witness::<Bls12_381>(
+                    domain_size,
+                    user_index,
+                    &ck_path,
+                    &cvk_path,
+                    user_info,
+                    &witness_path,
+                    false,
+                );
+                return;
+            }
+
+            let openings = kzg::supply_witness::<Bn254>(
                 domain_size,
                 user_index,
-                users_data[user_index].balance,
-                &witness.b_commit,
-                &b_opening,
-            ).expect("individual verify for balance failed");
+                &ck_path,
+                &cvk_path,
+                user_info,
+                &witness_path,
+                asset_name.is_some(),
+            );
 
-            let b_opening = eth::Param::G1Affine(b_opening.w);
-            println!("balance opening: {}", b_opening);
+            // `individualVerify` on chain checks the tag and balance openings separately.
+            if let (Some(asset_name), Some(openings)) = (asset_name, openings) {
+                let calldata = eth::encode_individual_verify(
+                    &asset_name,
+                    data_index,
+                    user_index,
+                    user_info.balance as u128,
+                    &openings.tag,
+                    &openings.tag_opening,
+                    &openings.b_opening,
+                );
+                println!("individualVerify calldata: 0x{}", hex::encode(calldata));
+            }
         }
     }
 }
@@ -303,12 +994,14 @@ struct UserInfo {
     pub balance: u64,
 }
 
-#[derive(Debug, CanonicalSerialize, CanonicalDeserialize)]
-struct Witness {
-    pub tag_commit: KZG10Commitment<Bn254>,
-    pub labeled_tag_poly: LabeledPolynomial<Fr, DensePolynomial<Fr>>,
-    pub b_commit: KZG10Commitment<Bn254>,
-    pub labeled_b_poly: LabeledPolynomial<Fr, DensePolynomial<Fr>>,
+/// Account of a user, whose tag is derived from the id and salt as on chain.
+/// The salt is left out when it is derived from a master key.
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountInfo {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    pub balance: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -321,7 +1014,7 @@ struct EthConfig {
 
 // fn max_domain_size() -> usize {
 //     let two_adicity = <FrParameters as FftParameters>::TWO_ADICITY;
-//     if cfg!(blinding) {
+//     if cfg!(feature = "blinding") {
 //         (1usize << two_adicity) / 4
 //     } else {
 //         (1usize << two_adicity) / 2
@@ -0,0 +1,17 @@
+//! Memory usage reporting.
+use std::fs;
+
+/// Peak resident set size of the process in KiB, only available on Linux.
+pub fn peak_rss_kib() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+pub fn print_peak_memory() {
+    if let Some(kib) = peak_rss_kib() {
+        println!("peak memory: {} MiB", kib / 1024);
+    }
+}
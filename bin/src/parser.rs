@@ -1,6 +1,6 @@
-use std::{fs::OpenOptions, path::PathBuf};
+use std::{fmt, fs::OpenOptions, io::BufReader, marker::PhantomData, path::PathBuf};
 use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
-use serde::{Serialize, de::DeserializeOwned};
+use serde::{Serialize, Deserializer, de::{DeserializeOwned, SeqAccess, Visitor}};
 use serde_json::{from_reader, to_writer};
 
 pub fn json_from_file<De: DeserializeOwned>(path: &PathBuf) -> De {
@@ -8,7 +8,37 @@ pub fn json_from_file<De: DeserializeOwned>(path: &PathBuf) -> De {
         .read(true)
         .open(path)
         .unwrap_or_else(|_| panic!("unable to open file {:?}", path));
-    from_reader(&file)
+    from_reader(BufReader::new(file))
+        .unwrap_or_else(|_| panic!("unable to parse json file {:?}", path))
+}
+
+/// Stream the elements of a json array one at a time, without loading the whole array.
+pub fn json_seq_from_file<De: DeserializeOwned, F: FnMut(usize, De)>(path: &PathBuf, f: F) {
+    struct SeqVisitor<De, F>(F, PhantomData<De>);
+
+    impl<'de, De: DeserializeOwned, F: FnMut(usize, De)> Visitor<'de> for SeqVisitor<De, F> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a json array")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+            let mut i = 0;
+            while let Some(item) = seq.next_element()? {
+                (self.0)(i, item);
+                i += 1;
+            }
+            Ok(())
+        }
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .unwrap_or_else(|_| panic!("unable to open file {:?}", path));
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+    deserializer.deserialize_seq(SeqVisitor(f, PhantomData))
         .unwrap_or_else(|_| panic!("unable to parse json file {:?}", path))
 }
 
@@ -22,6 +52,19 @@ pub fn json_to_file<Se: Serialize>(se: &Se, path: &PathBuf) {
         .unwrap_or_else(|_| panic!("unable to unparse json file {:?}", path))
 }
 
+/// Write a secret as json to a new file that only the owner can read and write.
+pub fn json_to_private_file<Se: Serialize>(se: &Se, path: &PathBuf) {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .unwrap_or_else(|_| panic!("unable to create file {:?}", path));
+    to_writer(&mut file, se)
+        .unwrap_or_else(|_| panic!("unable to unparse json file {:?}", path))
+}
+
 pub fn deser_from_file<De: CanonicalDeserialize>(path: &PathBuf) -> De {
     let file = OpenOptions::new()
         .read(true)
@@ -40,3 +83,24 @@ pub fn ser_to_file<Se: CanonicalSerialize>(se: &Se, path: &PathBuf) {
     se.serialize_unchecked(&mut file)
         .unwrap_or_else(|_| panic!("unable to serialize file {:?}", path))
 }
+
+/// Deserialize a compressed file from an untrusted source, checking every point.
+pub fn deser_checked_from_file<De: CanonicalDeserialize>(path: &PathBuf) -> De {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .unwrap_or_else(|_| panic!("unable to open file {:?}", path));
+    CanonicalDeserialize::deserialize(BufReader::new(file))
+        .unwrap_or_else(|_| panic!("unable to deserialize file {:?}", path))
+}
+
+pub fn ser_compressed_to_file<Se: CanonicalSerialize>(se: &Se, path: &PathBuf) {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap_or_else(|_| panic!("unable to open file {:?}", path));
+    se.serialize(&mut file)
+        .unwrap_or_else(|_| panic!("unable to serialize file {:?}", path))
+}
@@ -0,0 +1,355 @@
+//! Ownership attestations of reserve addresses.
+//!
+//! The exchange signs an ownership message with the key of every reserve
+//! address, binding the address to an epoch and to the digest of the liability
+//! commitments, so that an attestation can not be replayed for another
+//! snapshot. EVM addresses sign with EIP-191 `personal_sign`, Bitcoin addresses
+//! with BIP-137 message signatures. Attestations are verified offline, and the
+//! balances of the attested addresses in a supplied snapshot are summed per
+//! chain and asset into a reserves report.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use anyhow::{anyhow, ensure, Result};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Serialize, Deserialize};
+use web3::{signing::{self, Key, SecretKeyRef}, types::Address};
+
+use crate::btc::{self, AddressKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chain {
+    Evm,
+    Bitcoin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReserveAddress {
+    pub chain: Chain,
+    pub address: String,
+}
+
+/// Signature of the ownership message of an address, hex-encoded for EVM
+/// addresses and base64-encoded for Bitcoin addresses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attestation {
+    pub chain: Chain,
+    pub address: String,
+    pub signature: String,
+}
+
+/// Balance of an asset held by an address, in the smallest unit of the asset,
+/// e.g. wei, satoshi or the base unit of an ERC-20 token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReserveBalance {
+    pub chain: Chain,
+    pub asset: String,
+    pub address: String,
+    pub balance: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetBalance {
+    pub asset: String,
+    pub balance: u128,
+}
+
+/// Attestation of an address along with the balances of the assets it holds
+/// in the snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportEntry {
+    pub chain: Chain,
+    pub address: String,
+    pub balances: Vec<AssetBalance>,
+    pub attested: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reserves of an epoch, counting the balances of attested addresses only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReservesReport {
+    pub epoch: u64,
+    pub liabilities: String,
+    pub entries: Vec<ReportEntry>,
+    pub totals: Vec<ReserveTotal>,
+}
+
+impl ReservesReport {
+    /// Attested reserves of `asset` on `chain`.
+    pub fn total(&self, chain: Chain, asset: &str) -> u128 {
+        self.totals
+            .iter()
+            .find(|total| total.chain == chain && total.asset == asset)
+            .map_or(0, |total| total.balance)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReserveTotal {
+    pub chain: Chain,
+    pub asset: String,
+    pub balance: u128,
+}
+
+/// Message signed by the key of `address`, binding it to `epoch` and to the
+/// digest of the liability commitments.
+pub fn ownership_message(epoch: u64, liabilities: &[u8; 32], address: &str) -> String {
+    format!(
+        "Proof of Solvency reserves attestation\nepoch: {}\naddress: {}\nliabilities: 0x{}",
+        epoch,
+        address,
+        hex::encode(liabilities),
+    )
+}
+
+/// Attest the ownership of every address of `addresses` with the matching key of `keys`.
+pub fn attest(
+    epoch: u64,
+    liabilities: &[u8; 32],
+    addresses: &[ReserveAddress],
+    keys: &[SecretKey],
+) -> Result<Vec<Attestation>> {
+    let secp = Secp256k1::signing_only();
+    addresses
+        .iter()
+        .map(|reserve| {
+            let message = ownership_message(epoch, liabilities, &reserve.address);
+            let signature = match reserve.chain {
+                Chain::Evm => {
+                    let address = parse_evm_address(&reserve.address)?;
+                    let key = keys
+                        .iter()
+                        .find(|key| SecretKeyRef::new(key).address() == address)
+                        .ok_or_else(|| anyhow!("no key for {}", reserve.address))?;
+                    sign_evm(key, &message)
+                }
+                Chain::Bitcoin => {
+                    let kind = AddressKind::of(&reserve.address)?;
+                    let address = normalize(Chain::Bitcoin, &reserve.address);
+                    let key = keys
+                        .iter()
+                        .find(|key| btc::address(kind, &PublicKey::from_secret_key(&secp, key)) == address)
+                        .ok_or_else(|| anyhow!("no key for {}", reserve.address))?;
+                    btc::sign_message(kind, key, &message)
+                }
+            };
+
+            Ok(Attestation {
+                chain: reserve.chain,
+                address: reserve.address.clone(),
+                signature,
+            })
+        })
+        .collect()
+}
+
+/// Verify that the attestation was signed by the key of its address for
+/// `epoch` and the liability commitments of digest `liabilities`.
+pub fn verify(epoch: u64, liabilities: &[u8; 32], attestation: &Attestation) -> Result<()> {
+    let message = ownership_message(epoch, liabilities, &attestation.address);
+    match attestation.chain {
+        Chain::Evm => verify_evm(&attestation.address, &message, &attestation.signature),
+        Chain::Bitcoin => btc::verify_message(&attestation.address, &message, &attestation.signature),
+    }
+}
+
+/// Verify every attestation and sum the balances of the attested addresses in
+/// `snapshot` per chain and asset. Addresses which fail verification, are
+/// attested twice or are missing from the snapshot are reported but not
+/// counted. A snapshot listing the same asset of an address twice is rejected.
+pub fn report(
+    epoch: u64,
+    liabilities: &[u8; 32],
+    attestations: &[Attestation],
+    snapshot: &[ReserveBalance],
+) -> Result<ReservesReport> {
+    let mut listed = HashSet::new();
+    let mut balances = HashMap::<_, Vec<AssetBalance>>::new();
+    for b in snapshot {
+        let key = (b.chain, normalize(b.chain, &b.address));
+        ensure!(
+            listed.insert((key.clone(), b.asset.clone())),
+            "{} balance of {} listed twice",
+            b.asset,
+            b.address,
+        );
+        balances.entry(key).or_default().push(AssetBalance { asset: b.asset.clone(), balance: b.balance });
+    }
+
+    let mut attested = HashSet::new();
+    let mut totals = BTreeMap::new();
+    let mut entries = Vec::with_capacity(attestations.len());
+    for attestation in attestations {
+        let key = (attestation.chain, normalize(attestation.chain, &attestation.address));
+        let held = balances.get(&key).cloned().unwrap_or_default();
+        let result = verify(epoch, liabilities, attestation).and_then(|_| {
+            ensure!(!held.is_empty(), "no balance in snapshot");
+            ensure!(!attested.contains(&key), "address attested twice");
+            Ok(())
+        });
+
+        let error = match result {
+            Ok(()) => {
+                for b in &held {
+                    let total: &mut u128 = totals.entry((attestation.chain, b.asset.clone())).or_default();
+                    *total = total
+                        .checked_add(b.balance)
+                        .ok_or_else(|| anyhow!("{} reserves on {:?} overflow", b.asset, attestation.chain))?;
+                }
+                attested.insert(key);
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        entries.push(ReportEntry {
+            chain: attestation.chain,
+            address: attestation.address.clone(),
+            balances: held,
+            attested: error.is_none(),
+            error,
+        });
+    }
+
+    Ok(ReservesReport {
+        epoch,
+        liabilities: format!("0x{}", hex::encode(liabilities)),
+        entries,
+        totals: totals
+            .into_iter()
+            .map(|((chain, asset), balance)| ReserveTotal { chain, asset, balance })
+            .collect(),
+    })
+}
+
+/// Parse a hex secret key, with or without the `0x` prefix.
+pub fn parse_secret_key(key: &str) -> Result<SecretKey> {
+    let bytes = hex::decode(key.trim_start_matches("0x"))?;
+
+    Ok(SecretKey::from_slice(&bytes)?)
+}
+
+/// Canonical form of an address, as case is not significant for EVM and bech32 addresses.
+fn normalize(chain: Chain, address: &str) -> String {
+    match chain {
+        Chain::Evm => address.to_lowercase(),
+        Chain::Bitcoin => match AddressKind::of(address) {
+            Ok(AddressKind::P2wpkh) => address.to_lowercase(),
+            _ => address.to_owned(),
+        },
+    }
+}
+
+fn parse_evm_address(address: &str) -> Result<Address> {
+    let bytes = hex::decode(address.trim_start_matches("0x"))?;
+    ensure!(bytes.len() == 20, "invalid evm address {}", address);
+
+    Ok(Address::from_slice(&bytes))
+}
+
+/// Sign `message` with `personal_sign`, returning the hex signature `r || s || v`.
+pub(crate) fn sign_evm(key: &SecretKey, message: &str) -> String {
+    let hash = signing::hash_message(message);
+    let signature = SecretKeyRef::new(key)
+        .sign_message(hash.as_bytes())
+        .expect("message hash is 32 bytes");
+
+    let mut bytes = signature.r.as_bytes().to_vec();
+    bytes.extend_from_slice(signature.s.as_bytes());
+    bytes.push(27 + signature.v as u8);
+
+    format!("0x{}", hex::encode(bytes))
+}
+
+pub(crate) fn verify_evm(address: &str, message: &str, signature: &str) -> Result<()> {
+    let address = parse_evm_address(address)?;
+    let bytes = hex::decode(signature.trim_start_matches("0x"))?;
+    ensure!(bytes.len() == 65, "signature of {} bytes instead of 65", bytes.len());
+    let recovery_id = match bytes[64] {
+        v @ (0 | 1) => v,
+        v @ (27 | 28) => v - 27,
+        v => return Err(anyhow!("invalid signature v {}", v)),
+    };
+
+    let hash = signing::hash_message(message);
+    let recovered = signing::recover(hash.as_bytes(), &bytes[..64], recovery_id as i32)?;
+    ensure!(recovered == address, "signature by {:?} instead of {:?}", recovered, address);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use ark_std::{test_rng, rand::Rng};
+
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let rng = &mut test_rng();
+        let secp = Secp256k1::signing_only();
+        let keys = (0..3)
+            .map(|_| SecretKey::from_slice(&rng.gen::<[u8; 32]>()).unwrap())
+            .collect::<Vec<_>>();
+        let evm = format!("{:?}", SecretKeyRef::new(&keys[0]).address());
+        let p2pkh = btc::address(AddressKind::P2pkh, &PublicKey::from_secret_key(&secp, &keys[1]));
+        let p2wpkh = btc::address(AddressKind::P2wpkh, &PublicKey::from_secret_key(&secp, &keys[2]));
+        let addresses = [(Chain::Evm, &evm), (Chain::Bitcoin, &p2pkh), (Chain::Bitcoin, &p2wpkh)]
+            .into_iter()
+            .map(|(chain, address)| ReserveAddress { chain, address: address.clone() })
+            .collect::<Vec<_>>();
+
+        let epoch = 7;
+        let liabilities = rng.gen::<[u8; 32]>();
+        let attestations = attest(epoch, &liabilities, &addresses, &keys).unwrap();
+        for attestation in &attestations {
+            verify(epoch, &liabilities, attestation).unwrap();
+            assert!(verify(epoch + 1, &liabilities, attestation).is_err());
+            assert!(verify(epoch, &[0; 32], attestation).is_err());
+        }
+
+        let balance = |chain, asset: &str, address: &str, balance| ReserveBalance {
+            chain,
+            asset: asset.into(),
+            address: address.into(),
+            balance,
+        };
+        let snapshot = vec![
+            balance(Chain::Evm, "ETH", &evm.to_uppercase().replace("0X", "0x"), 10u128.pow(20)),
+            balance(Chain::Evm, "USDC", &evm, 2 * 10u128.pow(12)),
+            balance(Chain::Bitcoin, "BTC", &p2pkh, 50_000),
+            balance(Chain::Bitcoin, "BTC", &p2wpkh, 25_000),
+        ];
+        let report = report(epoch, &liabilities, &attestations, &snapshot).unwrap();
+        assert!(report.entries.iter().all(|e| e.attested));
+        assert_eq!(report.entries[0].balances.len(), 2);
+        assert_eq!(report.totals.len(), 3);
+        assert_eq!(report.total(Chain::Evm, "ETH"), 10u128.pow(20));
+        assert_eq!(report.total(Chain::Evm, "USDC"), 2 * 10u128.pow(12));
+        assert_eq!(report.total(Chain::Bitcoin, "BTC"), 75_000);
+        assert_eq!(report.total(Chain::Bitcoin, "ETH"), 0);
+
+        // the same asset of an address listed twice
+        let mut listed_twice = snapshot.clone();
+        listed_twice.push(balance(Chain::Bitcoin, "BTC", &p2wpkh, 25_000));
+        assert!(super::report(epoch, &liabilities, &attestations, &listed_twice).is_err());
+
+        // forged, duplicated and unknown attestations are not counted
+        let mut forged = attestations[1].clone();
+        forged.signature = attestations[2].signature.clone();
+        let duplicated = attestations[2].clone();
+        let unknown = attest(epoch, &liabilities, &addresses[..1], &keys).unwrap().remove(0);
+        let report = super::report(
+            epoch,
+            &liabilities,
+            &[forged, attestations[2].clone(), duplicated, unknown],
+            &snapshot[2..],
+        ).unwrap();
+        let attested = report.entries.iter().map(|e| e.attested).collect::<Vec<_>>();
+        assert_eq!(attested, vec![false, true, false, false]);
+        assert_eq!(report.total(Chain::Evm, "ETH"), 0);
+        assert_eq!(report.total(Chain::Bitcoin, "BTC"), 25_000);
+
+        // no key for an address
+        assert!(attest(epoch, &liabilities, &addresses, &keys[1..]).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Salts of the user tags, derived from a master key of the exchange.
+//!
+//! The salt of a user in an epoch is the keyed hash
+//! `keccak256("PoSol user salt" || master key || epoch || id)`, so that the
+//! exchange only has to keep the master key, and every epoch rotates all the
+//! salts, unlinking the tags of a user across snapshots. The salt is
+//! hex-encoded and fed to [`tag::derive`] as a string, as users pass it to
+//! `computeUserTag` on chain.
+use anyhow::{ensure, Result};
+use rand::{CryptoRng, RngCore};
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Keccak256};
+use posol_core::tag;
+
+const SALT_DOMAIN: &[u8] = b"PoSol user salt";
+
+/// Salt of a user in an epoch, to be handed to the user with their inclusion proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSalt {
+    pub id: String,
+    pub epoch: u64,
+    pub salt: String,
+}
+
+pub struct SaltManager {
+    master_key: [u8; 32],
+}
+
+impl SaltManager {
+    pub fn new(master_key: [u8; 32]) -> Self {
+        Self { master_key }
+    }
+
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut master_key = [0u8; 32];
+        rng.fill_bytes(&mut master_key);
+
+        Self::new(master_key)
+    }
+
+    /// Parse a hex master key, with or without the `0x` prefix.
+    pub fn from_hex(master_key: &str) -> Result<Self> {
+        let bytes = hex::decode(master_key.trim_start_matches("0x"))?;
+        ensure!(bytes.len() == 32, "master key of {} bytes instead of 32", bytes.len());
+
+        let mut master_key = [0u8; 32];
+        master_key.copy_from_slice(&bytes);
+
+        Ok(Self::new(master_key))
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.master_key))
+    }
+
+    /// Hex salt of user `id` in `epoch`.
+    pub fn salt(&self, epoch: u64, id: &str) -> String {
+        let salt = Keccak256::new()
+            .chain_update(SALT_DOMAIN)
+            .chain_update(self.master_key)
+            .chain_update(epoch.to_be_bytes())
+            .chain_update(id.as_bytes())
+            .finalize();
+
+        format!("0x{}", hex::encode(salt))
+    }
+
+    /// Tag of user `id` in `epoch`, derived from its salt.
+    pub fn tag(&self, epoch: u64, id: &str) -> [u8; 32] {
+        tag::derive(id.as_bytes(), self.salt(epoch, id).as_bytes())
+    }
+
+    /// Salts of users `ids` in `epoch`.
+    pub fn export<S: AsRef<str>>(&self, epoch: u64, ids: &[S]) -> Vec<UserSalt> {
+        ids
+            .iter()
+            .map(|id| UserSalt {
+                id: id.as_ref().to_owned(),
+                epoch,
+                salt: self.salt(epoch, id.as_ref()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn test_salts() {
+        let manager = SaltManager::generate(&mut thread_rng());
+        let restored = SaltManager::from_hex(&manager.to_hex()).unwrap();
+
+        // deterministic, and rotated every epoch
+        assert_eq!(manager.salt(3, "alice"), restored.salt(3, "alice"));
+        assert_ne!(manager.salt(3, "alice"), manager.salt(4, "alice"));
+        assert_ne!(manager.salt(3, "alice"), manager.salt(3, "bob"));
+        assert_ne!(manager.tag(3, "alice"), manager.tag(4, "alice"));
+
+        // another master key
+        let other = SaltManager::new([7; 32]);
+        assert_ne!(manager.salt(3, "alice"), other.salt(3, "alice"));
+
+        // users recompute their tags from the exported salts
+        let ids = ["alice", "bob"];
+        let salts = manager.export(3, &ids);
+        for (user, id) in salts.iter().zip(ids) {
+            assert_eq!((user.id.as_str(), user.epoch), (id, 3));
+            assert_eq!(tag::derive(id.as_bytes(), user.salt.as_bytes()), manager.tag(3, id));
+        }
+
+        assert!(SaltManager::from_hex("0x1234").is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Solvency certificates of an epoch.
+//!
+//! A certificate states, for every asset, the liabilities proven by a balance
+//! sum proof and the reserves of the asset attested on its chain, and that
+//! `reserves * 10000 >= liabilities * ratio_bps`. It carries the proofs, the
+//! tag commitments, the reserve attestations and the balance snapshot, so that
+//! a verifier holding the KZG keys can check it end to end, and it is signed by
+//! the exchange with an EIP-191 signature over the statement.
+use std::{collections::HashSet, path::PathBuf};
+use anyhow::{anyhow, ensure, Result};
+use ark_ff::{BigInteger, PrimeField};
+use ark_poly::GeneralEvaluationDomain;
+use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
+use secp256k1::SecretKey;
+use serde::{Serialize, Deserialize};
+use web3::signing::{Key, SecretKeyRef};
+use posol_core::{balance_sum, commitment::*};
+
+use crate::{
+    curve::Curve,
+    eth,
+    kzg,
+    reserves::{self, Attestation, Chain, ReserveBalance},
+    transcript::Transcript,
+};
+
+/// Denominator of the reserve ratio, which is given in basis points.
+pub const RATIO_DENOMINATOR: u64 = 10_000;
+
+pub type LimbsProof<E> = balance_sum::LimbsProof<
+    <E as ark_ec::PairingEngine>::Fr,
+    GeneralEvaluationDomain<<E as ark_ec::PairingEngine>::Fr>,
+    KZG10<E>,
+>;
+
+pub type Verifier<E> = balance_sum::Verifier<
+    <E as ark_ec::PairingEngine>::Fr,
+    GeneralEvaluationDomain<<E as ark_ec::PairingEngine>::Fr>,
+    KZG10<E>,
+>;
+
+/// Balance sum proof of the liabilities of an asset.
+pub enum LiabilityProof<E: Curve> {
+    /// Proof of the balance sum `m` of balances below the domain size.
+    Sum(E::Fr, eth::Proof<E>),
+    /// Proof of full-width balances decomposed into base-`n` limbs.
+    Limbs(LimbsProof<E>),
+}
+
+impl<E: Curve> LiabilityProof<E> {
+    fn b_commit(&self, n: usize) -> KZG10Commitment<E> {
+        match self {
+            Self::Sum(_, proof) => proof.b_commit,
+            Self::Limbs(proof) => proof.aggregate_b_commit(n),
+        }
+    }
+
+    /// Verify the proof, returning the proven liabilities.
+    fn verify(&self, verifier: &Verifier<E>) -> Result<u128> {
+        let sum = match self {
+            Self::Sum(m, proof) => {
+                verifier.verify::<Transcript<E>>(proof, *m)?;
+                *m
+            }
+            Self::Limbs(proof) => verifier.verify_limbs::<Transcript<E>>(proof)?,
+        };
+
+        fr_to_u128::<E>(&sum)
+    }
+}
+
+/// Witness and balance sum proof files of an asset, as written by `prove-and-commit`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetFiles {
+    pub asset: String,
+    pub chain: Chain,
+    pub witness_path: PathBuf,
+    pub proof_path: PathBuf,
+}
+
+/// Liabilities of an asset, held on `chain`.
+pub struct SolvencyAsset<E: Curve> {
+    pub asset: String,
+    pub chain: Chain,
+    pub tag_commit: KZG10Commitment<E>,
+    pub proof: LiabilityProof<E>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofKind {
+    Sum,
+    Limbs,
+}
+
+/// Hex-encoded compressed tag commitment and liability proof of an asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetEvidence {
+    pub asset: String,
+    pub chain: Chain,
+    pub kind: ProofKind,
+    pub tag_commit: String,
+    pub proof: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetStatement {
+    pub asset: String,
+    pub chain: Chain,
+    pub liabilities: u128,
+    pub reserves: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolvencyStatement {
+    pub epoch: u64,
+    /// Digest of the liability commitments the reserve attestations are bound to.
+    pub liabilities: String,
+    pub ratio_bps: u64,
+    pub assets: Vec<AssetStatement>,
+}
+
+impl SolvencyStatement {
+    /// Check that `reserves * 10000 >= liabilities * ratio_bps` for every asset.
+    pub fn check_ratio(&self) -> Result<()> {
+        for asset in &self.assets {
+            let reserves = asset.reserves.checked_mul(RATIO_DENOMINATOR as u128);
+            let required = asset.liabilities.checked_mul(self.ratio_bps as u128);
+            match (reserves, required) {
+                (Some(reserves), Some(required)) => ensure!(
+                    reserves >= required,
+                    "{} reserves {} below {} bps of liabilities {}",
+                    asset.asset,
+                    asset.reserves,
+                    self.ratio_bps,
+                    asset.liabilities,
+                ),
+                _ => return Err(anyhow!("{} ratio overflows", asset.asset)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Message signed by the exchange, the statement in json.
+    fn message(&self) -> String {
+        serde_json::to_string(self).expect("statement serialization failed")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolvencyCertificate {
+    pub statement: SolvencyStatement,
+    pub evidence: Vec<AssetEvidence>,
+    pub attestations: Vec<Attestation>,
+    pub snapshot: Vec<ReserveBalance>,
+    /// EVM address of the exchange key signing the statement.
+    pub signer: String,
+    pub signature: String,
+}
+
+/// Verify the liabilities of `assets` and the reserve attestations against
+/// `snapshot`, and issue a certificate signed with `key` if the reserves of
+/// every asset cover `ratio_bps` of its liabilities.
+pub fn issue<E: Curve>(
+    verifier: &Verifier<E>,
+    epoch: u64,
+    ratio_bps: u64,
+    assets: &[SolvencyAsset<E>],
+    attestations: Vec<Attestation>,
+    snapshot: Vec<ReserveBalance>,
+    key: &SecretKey,
+) -> Result<SolvencyCertificate> {
+    let statement = statement(verifier, epoch, ratio_bps, assets, &attestations, &snapshot)?;
+    statement.check_ratio()?;
+
+    let evidence = assets
+        .iter()
+        .map(|asset| {
+            let (kind, proof) = match &asset.proof {
+                LiabilityProof::Sum(m, proof) => (ProofKind::Sum, to_hex(&(*m, proof.clone()))),
+                LiabilityProof::Limbs(proof) => (ProofKind::Limbs, to_hex(proof)),
+            };
+            AssetEvidence {
+                asset: asset.asset.clone(),
+                chain: asset.chain,
+                kind,
+                tag_commit: to_hex(&asset.tag_commit),
+                proof,
+            }
+        })
+        .collect();
+    let signature = reserves::sign_evm(key, &statement.message());
+
+    Ok(SolvencyCertificate {
+        statement,
+        evidence,
+        attestations,
+        snapshot,
+        signer: format!("{:?}", SecretKeyRef::new(key).address()),
+        signature,
+    })
+}
+
+/// Check the certificate end to end: the liability proofs, the reserve
+/// attestations, the ratio and the signature of the statement, by `signer` if given.
+pub fn verify<E: Curve>(
+    verifier: &Verifier<E>,
+    certificate: &SolvencyCertificate,
+    signer: Option<&str>,
+) -> Result<()> {
+    let assets = certificate.evidence
+        .iter()
+        .map(|evidence| {
+            let proof = match evidence.kind {
+                ProofKind::Sum => {
+                    let (m, proof) = from_hex(&evidence.proof)?;
+                    LiabilityProof::Sum(m, proof)
+                }
+                ProofKind::Limbs => LiabilityProof::Limbs(from_hex(&evidence.proof)?),
+            };
+
+            Ok(SolvencyAsset {
+                asset: evidence.asset.clone(),
+                chain: evidence.chain,
+                tag_commit: from_hex(&evidence.tag_commit)?,
+                proof,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let expected = &certificate.statement;
+    let statement = statement(
+        verifier,
+        expected.epoch,
+        expected.ratio_bps,
+        &assets,
+        &certificate.attestations,
+        &certificate.snapshot,
+    )?;
+    ensure!(&statement == expected, "statement mismatches its evidence");
+    statement.check_ratio()?;
+
+    if let Some(signer) = signer {
+        ensure!(
+            signer.eq_ignore_ascii_case(&certificate.signer),
+            "certificate signed by {} instead of {}",
+            certificate.signer,
+            signer,
+        );
+    }
+    reserves::verify_evm(&certificate.signer, &statement.message(), &certificate.signature)
+}
+
+/// Statement of the verified liabilities of `assets` and of their reserves
+/// attested on their chains.
+fn statement<E: Curve>(
+    verifier: &Verifier<E>,
+    epoch: u64,
+    ratio_bps: u64,
+    assets: &[SolvencyAsset<E>],
+    attestations: &[Attestation],
+    snapshot: &[ReserveBalance],
+) -> Result<SolvencyStatement> {
+    ensure!(!assets.is_empty(), "no asset provided");
+    let mut names = HashSet::new();
+    for asset in assets {
+        ensure!(names.insert(&asset.asset), "asset {} listed twice", asset.asset);
+    }
+
+    let n = verifier.size();
+    let commits = assets
+        .iter()
+        .map(|asset| (asset.tag_commit, asset.proof.b_commit(n)))
+        .collect::<Vec<_>>();
+    let digest = kzg::commitments_digest::<E>(&commits);
+    let report = reserves::report(epoch, &digest, attestations, snapshot)?;
+
+    let assets = assets
+        .iter()
+        .map(|asset| {
+            let liabilities = asset.proof
+                .verify(verifier)
+                .map_err(|e| anyhow!("liabilities of {}: {}", asset.asset, e))?;
+            let reserves = report.total(asset.chain, &asset.asset);
+
+            Ok(AssetStatement {
+                asset: asset.asset.clone(),
+                chain: asset.chain,
+                liabilities,
+                reserves,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(SolvencyStatement {
+        epoch,
+        liabilities: report.liabilities,
+        ratio_bps,
+        assets,
+    })
+}
+
+fn fr_to_u128<E: Curve>(fr: &E::Fr) -> Result<u128> {
+    let bytes = fr.into_repr().to_bytes_le();
+    ensure!(bytes[16..].iter().all(|&b| b == 0), "liabilities exceed 128 bits");
+
+    Ok(u128::from_le_bytes(bytes[..16].try_into().unwrap()))
+}
+
+fn to_hex<S: CanonicalSerialize>(item: &S) -> String {
+    let mut bytes = Vec::new();
+    item.serialize(&mut bytes).expect("serialization failed");
+    hex::encode(bytes)
+}
+
+/// Decode an item from an untrusted hex string, checking every point.
+fn from_hex<De: CanonicalDeserialize>(item: &str) -> Result<De> {
+    let bytes = hex::decode(item)?;
+
+    CanonicalDeserialize::deserialize(&bytes[..]).map_err(|e| anyhow!("invalid evidence: {:?}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Bn254;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use secp256k1::{PublicKey, Secp256k1};
+    use posol_core::{balance_sum::Prover, tag};
+
+    use crate::btc::{self, AddressKind};
+    use super::*;
+
+    #[test]
+    fn test_certificate() {
+        let rng = &mut test_rng();
+        let n = 16;
+        let pp = KZG10::<Bn254>::setup(n + 3, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(&pp, n + 3, 0, None).unwrap();
+        let prover = Prover::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>::new(ck, n).unwrap();
+        let verifier = prover.verifier(cvk);
+
+        // liabilities of 8 users in satoshi and in the base unit of USDC
+        let tags = (0..8u8).map(|i| vec![i; 32]).collect::<Vec<_>>();
+        let tags = tags.iter().map(|t| &t[..]).collect::<Vec<_>>();
+        let (tag_commit, _) =
+            tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(prover.committer_key(), n, &tags).unwrap();
+        let mut liabilities = Vec::new();
+        let mut assets = Vec::new();
+        for (asset, chain) in [("BTC", Chain::Bitcoin), ("USDC", Chain::Evm)] {
+            let balances = (0..8).map(|_| rng.gen_range(1..n as u64)).collect::<Vec<_>>();
+            let (m, proof, _) = prover.prove::<Transcript<Bn254>, _>(&balances, rng).unwrap();
+            liabilities.push(balances.iter().sum::<u64>() as u128);
+            assets.push(SolvencyAsset {
+                asset: asset.into(),
+                chain,
+                tag_commit,
+                proof: LiabilityProof::Sum(m, proof),
+            });
+        }
+
+        // reserves attested on a bitcoin and an evm address, the latter also
+        // holding ether which must not count as USDC
+        let epoch = 3;
+        let reserve_keys = (0..2)
+            .map(|_| SecretKey::from_slice(&rng.gen::<[u8; 32]>()).unwrap())
+            .collect::<Vec<_>>();
+        let btc_address = btc::address(
+            AddressKind::P2wpkh,
+            &PublicKey::from_secret_key(&Secp256k1::signing_only(), &reserve_keys[0]),
+        );
+        let evm_address = format!("{:?}", SecretKeyRef::new(&reserve_keys[1]).address());
+        let commits = assets
+            .iter()
+            .map(|asset| (asset.tag_commit, asset.proof.b_commit(n)))
+            .collect::<Vec<_>>();
+        let digest = kzg::commitments_digest::<Bn254>(&commits);
+        let addresses = [
+            reserves::ReserveAddress { chain: Chain::Bitcoin, address: btc_address.clone() },
+            reserves::ReserveAddress { chain: Chain::Evm, address: evm_address.clone() },
+        ];
+        let attestations = reserves::attest(epoch, &digest, &addresses, &reserve_keys).unwrap();
+        let snapshot = |btc, usdc| {
+            let balance = |chain, asset: &str, address: &String, balance| ReserveBalance {
+                chain,
+                asset: asset.into(),
+                address: address.clone(),
+                balance,
+            };
+            vec![
+                balance(Chain::Bitcoin, "BTC", &btc_address, btc),
+                balance(Chain::Evm, "ETH", &evm_address, 10u128.pow(20)),
+                balance(Chain::Evm, "USDC", &evm_address, usdc),
+            ]
+        };
+
+        // issue and verify a certificate of 110%
+        let key = SecretKey::from_slice(&rng.gen::<[u8; 32]>()).unwrap();
+        let reserves = liabilities.iter().map(|l| l * 11 / 10 + 1).collect::<Vec<_>>();
+        let certificate = issue(
+            &verifier,
+            epoch,
+            11_000,
+            &assets,
+            attestations.clone(),
+            snapshot(reserves[0], reserves[1]),
+            &key,
+        ).unwrap();
+        for (i, asset) in certificate.statement.assets.iter().enumerate() {
+            assert_eq!(asset.liabilities, liabilities[i]);
+            assert_eq!(asset.reserves, reserves[i]);
+        }
+        let json = serde_json::to_string(&certificate).unwrap();
+        let certificate: SolvencyCertificate = serde_json::from_str(&json).unwrap();
+        verify(&verifier, &certificate, None).unwrap();
+        verify(&verifier, &certificate, Some(&certificate.signer.to_uppercase().replace("0X", "0x"))).unwrap();
+        assert!(verify(&verifier, &certificate, Some("0x0000000000000000000000000000000000000000")).is_err());
+
+        // reserves of an asset below the ratio, despite the ether on its chain
+        let res = issue(&verifier, epoch, 11_000, &assets, attestations.clone(), snapshot(reserves[0], liabilities[1]), &key);
+        assert!(res.is_err());
+
+        // a tampered statement or snapshot
+        let mut tampered = certificate.clone();
+        tampered.statement.ratio_bps = 12_000;
+        assert!(verify(&verifier, &tampered, None).is_err());
+        let mut tampered = certificate.clone();
+        tampered.snapshot[0].balance += 1;
+        assert!(verify(&verifier, &tampered, None).is_err());
+        let mut tampered = certificate.clone();
+        tampered.snapshot[1].asset = "USDC".into();
+        tampered.snapshot[2].asset = "ETH".into();
+        assert!(verify(&verifier, &tampered, None).is_err());
+        let mut tampered = certificate;
+        tampered.statement.assets[0].liabilities -= 1;
+        assert!(verify(&verifier, &tampered, None).is_err());
+    }
+}
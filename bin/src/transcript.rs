@@ -1,20 +1,23 @@
-use ark_bn254::{Fr, Bn254};
-use ark_ff::{BigInteger, PrimeField, FromBytes};
+use std::marker::PhantomData;
+use ark_ff::{BigInteger, PrimeField, FpParameters, FromBytes};
 use ethereum_types::H256;
 use sha3::{Digest, Keccak256};
 use posol_core::{commitment::KZG10Commitment, balance_sum::TranscriptProtocol};
 
+use crate::curve::Curve;
+
 const DST_0: u8 = 0;
 const DST_1: u8 = 1;
 const DST_CHALLENGE: u8 = 2;
 
-pub struct Transcript {
+pub struct Transcript<E: Curve> {
     state_0: H256,
     state_1: H256,
     counter: u32,
+    _e: PhantomData<E>,
 }
 
-impl Transcript {
+impl<E: Curve> Transcript<E> {
     fn append_bytes_without_label(&mut self, item: &[u8]) {
         let old_state = self.state_0.clone();
 
@@ -34,12 +37,13 @@ impl Transcript {
     }
 }
 
-impl TranscriptProtocol<Fr, KZG10Commitment<Bn254>> for Transcript {
+impl<E: Curve> TranscriptProtocol<E::Fr, KZG10Commitment<E>> for Transcript<E> {
     fn new(_label: &'static str) -> Self {
         Self {
             state_0: H256::zero(),
             state_1: H256::zero(),
             counter: 0,
+            _e: PhantomData,
         }
     }
 
@@ -47,16 +51,17 @@ impl TranscriptProtocol<Fr, KZG10Commitment<Bn254>> for Transcript {
         self.append_bytes_without_label(&item.to_be_bytes());
     }
 
-    fn append_scalar(&mut self, _label: &'static str, item: &Fr) {
+    fn append_scalar(&mut self, _label: &'static str, item: &E::Fr) {
         self.append_bytes_without_label(&item.into_repr().to_bytes_be());
     }
 
-    fn append_commitment(&mut self, _label: &'static str, item: &KZG10Commitment<Bn254>) {
-        self.append_bytes_without_label(&item.0.x.into_repr().to_bytes_be());
-        self.append_bytes_without_label(&item.0.y.into_repr().to_bytes_be());
+    fn append_commitment(&mut self, _label: &'static str, item: &KZG10Commitment<E>) {
+        let [x, y] = E::g1_to_bytes_be(&item.0);
+        self.append_bytes_without_label(&x);
+        self.append_bytes_without_label(&y);
     }
 
-    fn challenge_scalar(&mut self, _label: &'static str) -> Fr {
+    fn challenge_scalar(&mut self, _label: &'static str) -> E::Fr {
         let mut data = Vec::with_capacity(1 + 32 + 32 + 4);
         data.push(DST_CHALLENGE);
         data.extend_from_slice(self.state_0.as_bytes());
@@ -66,14 +71,24 @@ impl TranscriptProtocol<Fr, KZG10Commitment<Bn254>> for Transcript {
         
         let mut query = Keccak256::digest(&data);
         query.reverse();
-        query[31] &= 0x1f;
-        Fr::read(&query[..32]).unwrap()
+        // Keep the bits below `MODULUS_BITS - 1` so the query is a canonical scalar,
+        // i.e. the top 3 bits for BN254 as the Solidity transcript does.
+        let num_bits = <E::Fr as PrimeField>::Params::MODULUS_BITS as usize - 1;
+        for (i, byte) in query.iter_mut().enumerate() {
+            let low = i * 8;
+            if low >= num_bits {
+                *byte = 0;
+            } else if low + 8 > num_bits {
+                *byte &= (1u8 << (num_bits - low)) - 1;
+            }
+        }
+        E::Fr::read(&query[..32]).unwrap()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use ark_bn254::{G1Affine, Fq};
+    use ark_bn254::{Bn254, Fr, G1Affine, Fq};
     use ark_poly_commit::kzg10::Commitment;
     use hex_literal::hex;
 
@@ -81,7 +96,7 @@ mod test {
 
     #[test]
     fn test_transcript() {
-        let mut transcript = Transcript::new("test");
+        let mut transcript = Transcript::<Bn254>::new("test");
         transcript.append_u64("a", 1);
         let a = transcript.challenge_scalar("a");
 
@@ -0,0 +1,452 @@
+//! Liabilities and debts of users, e.g. of margin and lending accounts.
+//!
+//! Every user has a liability, what the exchange owes them, and a debt, what
+//! they owe the exchange. Both are proven in range as the two balance columns
+//! of a [`MultiAssetProof`] sharing the tags, and the net value `L_i - D_i` of
+//! every user is proven in range as well, with a balance sum proof of the
+//! column `N(X)` whose commitment the verifier checks to be `[L] - [D]`. The
+//! published sum is then the net liabilities `Σ L_i - Σ D_i`. Each user can
+//! verify both of their figures and their net value.
+//!
+//! A debt only offsets the liabilities of the same user: a user in net debt can
+//! not be proven, since otherwise the exchange could add debtors no real user
+//! ever checks and lower the net liabilities at will. Such users must be
+//! proven with their debt capped at their liabilities, and the debt beyond is
+//! not counted. The commitment check requires `N(X)` to be exactly
+//! `L(X) - D(X)`, so the proof is not available with the `blinding` feature.
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::LabeledPolynomial;
+use ark_serialize::*;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::{domain, PosolError},
+    util::EvaluationDomainExt,
+    commitment::HomomorphicCommitment,
+    label_polynomial,
+};
+use super::{
+    individual_open_in,
+    individual_verify_in,
+    multi_asset::{self, MultiAssetProof},
+    Proof,
+    TranscriptProtocol,
+};
+
+/// Proof of the liabilities and the debts of every user.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct NetProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Sum of the liabilities.
+    pub liabilities_sum: F,
+    /// Sum of the debts.
+    pub debts_sum: F,
+    /// Balance sum proof of the liability and the debt columns, in that order.
+    pub proof: MultiAssetProof<F, D, PC>,
+    /// Balance sum proof of the net values, whose commitment is the
+    /// difference of the liability and the debt commitments.
+    pub net_proof: Proof<F, D, PC>,
+}
+
+impl<F, D, PC> NetProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Net liabilities, the sum of the liabilities minus the sum of the debts.
+    pub fn net_sum(&self) -> F {
+        self.liabilities_sum - self.debts_sum
+    }
+
+    pub fn liabilities_commit(&self) -> &PC::Commitment {
+        &self.proof.b_commits[0]
+    }
+
+    pub fn debts_commit(&self) -> &PC::Commitment {
+        &self.proof.b_commits[1]
+    }
+
+    /// Commitment to the net values `L(X) - D(X)`.
+    pub fn net_commit(&self) -> PC::Commitment {
+        PC::multi_scalar_mul(&self.proof.b_commits, &[F::one(), -F::one()])
+    }
+
+    /// Verify both columns and that the net value of every user is in range,
+    /// returning the net liabilities.
+    pub fn verify<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        n: usize,
+        t_commit: &PC::Commitment,
+    ) -> Result<F, PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+        PC::Commitment: PartialEq,
+    {
+        if self.proof.num_assets() != 2 {
+            return Err(PosolError::Verification(format!(
+                "{} balance columns instead of liabilities and debts",
+                self.proof.num_assets(),
+            )));
+        }
+        self.proof.verify::<T>(cvk, n, t_commit, &[self.liabilities_sum, self.debts_sum])?;
+
+        // N(X) is L(X) - D(X), and its evaluations sum up to the net liabilities.
+        if self.net_proof.b_commit != self.net_commit() {
+            return Err(PosolError::Verification("net values are not the liabilities minus the debts".into()));
+        }
+        self.net_proof
+            .verify::<T>(cvk, n, t_commit, self.net_sum())
+            .map_err(|e| PosolError::Verification(format!("net values: {}", e)))?;
+
+        Ok(self.net_sum())
+    }
+}
+
+/// Opening proofs of the liability and the debt of a user.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Proof: Clone"),
+    Debug(bound = "PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Proof: Eq"),
+    PartialEq(bound = "PC::Proof: PartialEq")
+)]
+pub struct UserOpening<F, PC>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    pub liability: PC::Proof,
+    pub debt: PC::Proof,
+}
+
+/// Prove the liabilities and the debts of the users of `tags`, each below `n`
+/// and with a debt not above their liabilities.
+///
+/// Returns the proof, the tag polynomial, and the liability and the debt
+/// polynomials.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn prove<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    labeled_t_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: &PC::Commitment,
+    tags: &[&[u8]],
+    liabilities: &[u64],
+    debts: &[u64],
+    rng: &mut R,
+) -> Result<(
+    NetProof<F, D, PC>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    if cfg!(feature = "blinding") {
+        return Err(PosolError::Commitment("net values cannot be proven on blinded columns".into()));
+    }
+    if liabilities.len() != debts.len() {
+        return Err(PosolError::InputRange(format!(
+            "{} liabilities for {} debts",
+            liabilities.len(),
+            debts.len(),
+        )));
+    }
+    let nets = liabilities
+        .iter()
+        .zip(debts)
+        .enumerate()
+        .map(|(i, (&liability, &debt))| {
+            liability
+                .checked_sub(debt)
+                .ok_or_else(|| PosolError::InputRange(format!("debt of user {} above their liabilities", i)))
+        })
+        .collect::<Result<Vec<_>, PosolError>>()?;
+
+    let timer = start_timer!(|| "Net Balance Sum: Proving");
+
+    let columns = [liabilities.to_vec(), debts.to_vec()];
+    let (sums, proof, labeled_tag_poly, mut labeled_b_polys) =
+        multi_asset::prove::<F, D, PC, T, R>(ck, n, labeled_t_poly, t_commit, tags, &columns, rng)?;
+    let labeled_debt_poly = labeled_b_polys.pop().unwrap();
+    let labeled_liability_poly = labeled_b_polys.pop().unwrap();
+
+    let (_, net_proof, _) = super::prove::<F, D, PC, T, R>(ck, n, labeled_t_poly, t_commit, &nets, rng)?;
+
+    let net_proof = NetProof {
+        liabilities_sum: sums[0],
+        debts_sum: sums[1],
+        proof,
+        net_proof,
+    };
+
+    end_timer!(timer);
+
+    Ok((net_proof, labeled_tag_poly, labeled_liability_poly, labeled_debt_poly))
+}
+
+/// Open the liability and the debt of the `i`-th user.
+pub fn individual_open<F, D, PC>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    i: usize,
+    labeled_liability_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    labeled_debt_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    proof: &NetProof<F, D, PC>,
+) -> Result<UserOpening<F, PC>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    let liability = individual_open_in::<F, D, PC>(
+        ck,
+        &domain,
+        i,
+        labeled_liability_poly,
+        proof.liabilities_commit(),
+    )?;
+    let debt = individual_open_in::<F, D, PC>(ck, &domain, i, labeled_debt_poly, proof.debts_commit())?;
+
+    Ok(UserOpening { liability, debt })
+}
+
+/// Verify the liability and the debt of the `i`-th user.
+pub fn individual_verify<F, D, PC>(
+    vk: &PC::VerifierKey,
+    n: usize,
+    i: usize,
+    liability: u64,
+    debt: u64,
+    proof: &NetProof<F, D, PC>,
+    opening: &UserOpening<F, PC>,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    individual_verify_in::<F, D, PC>(
+        vk,
+        &domain,
+        i,
+        F::from(liability),
+        proof.liabilities_commit(),
+        &opening.liability,
+    )
+    .map_err(|e| PosolError::Verification(format!("liability: {}", e)))?;
+    individual_verify_in::<F, D, PC>(vk, &domain, i, F::from(debt), proof.debts_commit(), &opening.debt)
+        .map_err(|e| PosolError::Verification(format!("debt: {}", e)))
+}
+
+/// Open the net value `L(X) - D(X)` of the `i`-th user.
+pub fn net_open<F, D, PC>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    i: usize,
+    labeled_liability_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    labeled_debt_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    proof: &NetProof<F, D, PC>,
+) -> Result<PC::Proof, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    let net_poly = labeled_liability_poly.polynomial() - labeled_debt_poly.polynomial();
+    let labeled_net_poly = label_polynomial!(net_poly);
+
+    individual_open_in::<F, D, PC>(ck, &domain, i, &labeled_net_poly, &proof.net_commit())
+}
+
+/// Verify the net value of the `i`-th user.
+pub fn net_verify<F, D, PC>(
+    vk: &PC::VerifierKey,
+    n: usize,
+    i: usize,
+    net: u64,
+    proof: &NetProof<F, D, PC>,
+    opening: &PC::Proof,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    individual_verify_in::<F, D, PC>(vk, &domain, i, F::from(net), &proof.net_commit(), opening)
+}
+
+#[cfg(all(test, not(feature = "blinding")))]
+mod test {
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Bn254, Fr};
+    use itertools::Itertools;
+
+    use crate::{
+        commitment::KZG10,
+        balance_sum::{precompute, MerlinTranscript},
+    };
+    use super::*;
+
+    type Domain = GeneralEvaluationDomain<Fr>;
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let num_users = 13;
+        // setup
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            n,
+            0,
+            None,
+        ).unwrap();
+
+        // precompute
+        let (labeled_t_poly, t_commit) =
+            precompute::<_, Domain, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // generate random users, with debts up to their liabilities
+        let tags = (0..num_users)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let liabilities = (0..num_users).map(|_| rng.gen_range(0..n as u64)).collect_vec();
+        let debts = liabilities.iter().map(|&l| rng.gen_range(0..=l)).collect_vec();
+
+        // prove
+        let (proof, _, labeled_liability_poly, labeled_debt_poly) =
+            prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &tags_ref,
+                &liabilities,
+                &debts,
+                rng,
+            ).unwrap();
+
+        // verify
+        let net_sum = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit).unwrap();
+        let nets = liabilities.iter().zip(&debts).map(|(l, d)| l - d).collect_vec();
+        assert_eq!(net_sum, Fr::from(nets.iter().sum::<u64>()));
+
+        // verify with a lower debt sum
+        let mut wrong_proof = proof.clone();
+        wrong_proof.debts_sum -= Fr::from(1u64);
+        assert!(wrong_proof.verify::<MerlinTranscript>(&cvk, n, &t_commit).is_err());
+
+        // verify with a net proof of other values
+        let other_nets = nets.iter().map(|&v| (v + 1) % n as u64).collect_vec();
+        let (_, other_proof, _) = super::super::prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+            &ck,
+            n,
+            &labeled_t_poly,
+            &t_commit,
+            &other_nets,
+            rng,
+        ).unwrap();
+        let mut wrong_proof = proof.clone();
+        wrong_proof.net_proof = other_proof;
+        let res = wrong_proof.verify::<MerlinTranscript>(&cvk, n, &t_commit);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // every user verifies both figures and the net value
+        for (i, &net) in nets.iter().enumerate() {
+            let opening = individual_open::<_, Domain, _>(
+                &ck,
+                n,
+                i,
+                &labeled_liability_poly,
+                &labeled_debt_poly,
+                &proof,
+            ).unwrap();
+            let res = individual_verify::<_, Domain, _>(
+                &cvk,
+                n,
+                i,
+                liabilities[i],
+                debts[i],
+                &proof,
+                &opening,
+            );
+            assert!(res.is_ok());
+            let res = individual_verify::<_, Domain, _>(
+                &cvk,
+                n,
+                i,
+                liabilities[i],
+                debts[i] + 1,
+                &proof,
+                &opening,
+            );
+            assert!(matches!(res, Err(PosolError::Verification(_))));
+
+            let net_opening = net_open::<_, Domain, _>(
+                &ck,
+                n,
+                i,
+                &labeled_liability_poly,
+                &labeled_debt_poly,
+                &proof,
+            ).unwrap();
+            let res = net_verify::<_, Domain, _>(&cvk, n, i, net, &proof, &net_opening);
+            assert!(res.is_ok());
+            let res = net_verify::<_, Domain, _>(&cvk, n, i, net + 1, &proof, &net_opening);
+            assert!(res.is_err());
+        }
+
+        // a user in net debt
+        let mut wrong_debts = debts.clone();
+        wrong_debts[0] = liabilities[0] + 1;
+        let res = prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+            &ck,
+            n,
+            &labeled_t_poly,
+            &t_commit,
+            &tags_ref,
+            &liabilities,
+            &wrong_debts,
+            rng,
+        );
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+    }
+}
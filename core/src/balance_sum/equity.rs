@@ -0,0 +1,372 @@
+//! Non-negative net equity of margin accounts under public prices.
+//!
+//! Every user holds collateral and borrowed amounts of several assets, proven
+//! in range as the columns `C_k(X)` and `D_k(X)` of a [`MultiAssetProof`]
+//! sharing the tags. Given the public price `p_k` of each asset, the net
+//! equity of a user is
+//!
+//! ```text
+//! E_i = sum_k p_k (C_k_i - D_k_i)
+//! ```
+//!
+//! It is proven non-negative by decomposing it into base-`n` limbs with a
+//! [`LimbsProof`]: the verifier checks that the aggregated limb commitment is
+//! `sum_k p_k ([C_k] - [D_k])`, so that `E(X)` is the priced combination of the
+//! committed positions and each of its evaluations lies in `[0, n^limbs)`. The
+//! priced total `sum_i E_i` is the aggregated sum of the limbs, which must also
+//! match the priced sums of the positions.
+//!
+//! The commitment check requires `E(X)` to be exactly the combination of the
+//! position polynomials, so the proof is not available with the `blinding`
+//! feature, where every column is randomised on its own.
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::LabeledPolynomial;
+use ark_serialize::*;
+use itertools::Itertools;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::PosolError,
+    util::EvaluationDomainExt,
+    commitment::HomomorphicCommitment,
+};
+use super::{
+    multi_asset::{self, MultiAssetProof},
+    prove_limbs,
+    LimbsProof,
+    TranscriptProtocol,
+};
+
+/// Largest number of bits of a limb decomposition, far below the field size so
+/// that a negative equity can not be decomposed.
+const MAX_EQUITY_BITS: u32 = 128;
+
+/// Proof that the priced net equity of every user is non-negative.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct EquityProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Sum of the collateral of each asset.
+    pub collateral_sums: Vec<F>,
+    /// Sum of the borrowed amounts of each asset.
+    pub borrowed_sums: Vec<F>,
+    /// Balance sum proof of the collateral columns followed by the borrowed columns.
+    pub positions: MultiAssetProof<F, D, PC>,
+    /// Limbs proof of the net equity column.
+    pub equity: LimbsProof<F, D, PC>,
+}
+
+impl<F, D, PC> EquityProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Number of assets covered by the proof.
+    pub fn num_assets(&self) -> usize {
+        self.collateral_sums.len()
+    }
+
+    pub fn collateral_commits(&self) -> &[PC::Commitment] {
+        &self.positions.b_commits[..self.num_assets()]
+    }
+
+    pub fn borrowed_commits(&self) -> &[PC::Commitment] {
+        &self.positions.b_commits[self.num_assets()..]
+    }
+
+    /// Verify the positions and the non-negativity of the net equity of every
+    /// user under `prices`, returning the priced total of the net equities.
+    pub fn verify<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        n: usize,
+        t_commit: &PC::Commitment,
+        prices: &[u64],
+    ) -> Result<F, PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+        PC::Commitment: PartialEq,
+    {
+        let num_assets = self.num_assets();
+        if num_assets == 0
+            || prices.len() != num_assets
+            || self.borrowed_sums.len() != num_assets
+            || self.positions.num_assets() != 2 * num_assets
+        {
+            return Err(PosolError::Verification("number of assets mismatches".into()));
+        }
+        let bits = n.trailing_zeros() * self.equity.proofs.len() as u32;
+        if bits > MAX_EQUITY_BITS {
+            return Err(PosolError::Verification(format!(
+                "net equity of {} bits above {}",
+                bits,
+                MAX_EQUITY_BITS,
+            )));
+        }
+
+        let timer = start_timer!(|| "Net Equity: Verifying");
+
+        let sums = self.collateral_sums
+            .iter()
+            .chain(self.borrowed_sums.iter())
+            .copied()
+            .collect_vec();
+        self.positions.verify::<T>(cvk, n, t_commit, &sums)?;
+        let total = self.equity.verify::<T>(cvk, n, t_commit)?;
+
+        // E(X) is the priced combination of the positions.
+        let prices = prices.iter().map(|&p| F::from(p)).collect_vec();
+        let scalars = prices
+            .iter()
+            .copied()
+            .chain(prices.iter().map(|&p| -p))
+            .collect_vec();
+        if self.equity.aggregate_b_commit(n) != PC::multi_scalar_mul(&self.positions.b_commits, &scalars) {
+            return Err(PosolError::Verification("net equity is not the priced positions".into()));
+        }
+        let priced_sum = itertools::izip!(&prices, &self.collateral_sums, &self.borrowed_sums)
+            .map(|(&p, &c, &b)| p * (c - b))
+            .sum::<F>();
+        if total != priced_sum {
+            return Err(PosolError::Verification("net equity sum is not the priced sums".into()));
+        }
+
+        end_timer!(timer);
+
+        Ok(total)
+    }
+}
+
+/// Net equity `sum_k p_k (c_k - d_k)` of every user, failing on a negative one.
+pub fn net_equities(
+    prices: &[u64],
+    collateral: &[Vec<u64>],
+    borrowed: &[Vec<u64>],
+) -> Result<Vec<u128>, PosolError> {
+    if prices.is_empty() || collateral.len() != prices.len() || borrowed.len() != prices.len() {
+        return Err(PosolError::InputRange(format!(
+            "{} prices for {} collateral and {} borrowed columns",
+            prices.len(),
+            collateral.len(),
+            borrowed.len(),
+        )));
+    }
+    let num_users = collateral[0].len();
+    if let Some(column) = collateral.iter().chain(borrowed).find(|c| c.len() != num_users) {
+        return Err(PosolError::InputRange(format!("{} positions mismatch {} users", column.len(), num_users)));
+    }
+
+    let overflow = || PosolError::InputRange("priced positions overflow".into());
+    let priced = |columns: &[Vec<u64>], i: usize| {
+        prices.iter().zip(columns).try_fold(0u128, |acc, (&p, column)| {
+            (p as u128)
+                .checked_mul(column[i] as u128)
+                .and_then(|v| acc.checked_add(v))
+        })
+    };
+    (0..num_users)
+        .map(|i| {
+            let value = priced(collateral, i).ok_or_else(overflow)?;
+            let debt = priced(borrowed, i).ok_or_else(overflow)?;
+            value
+                .checked_sub(debt)
+                .ok_or_else(|| PosolError::InputRange(format!("negative net equity of user {}", i)))
+        })
+        .collect()
+}
+
+/// Prove that the net equity of every user of `tags` under `prices` is
+/// non-negative, given their collateral and borrowed amounts of each asset,
+/// each below `n`.
+///
+/// Returns the priced total of the net equities, the proof, the tag
+/// polynomial, the collateral and the borrowed polynomials of each asset,
+/// and the net equity polynomial.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn prove<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    labeled_t_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: &PC::Commitment,
+    tags: &[&[u8]],
+    prices: &[u64],
+    collateral: &[Vec<u64>],
+    borrowed: &[Vec<u64>],
+    rng: &mut R,
+) -> Result<(
+    F,
+    EquityProof<F, D, PC>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+    Vec<LabeledPolynomial<F, DensePolynomial<F>>>,
+    Vec<LabeledPolynomial<F, DensePolynomial<F>>>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    if cfg!(feature = "blinding") {
+        return Err(PosolError::Commitment("net equity cannot be proven on blinded positions".into()));
+    }
+    let equities = net_equities(prices, collateral, borrowed)?;
+
+    let timer = start_timer!(|| "Net Equity: Proving");
+
+    let num_assets = prices.len();
+    let columns = collateral.iter().chain(borrowed).cloned().collect_vec();
+    let (mut sums, positions, labeled_tag_poly, mut labeled_collateral_polys) =
+        multi_asset::prove::<F, D, PC, T, R>(ck, n, labeled_t_poly, t_commit, tags, &columns, rng)?;
+    let labeled_borrowed_polys = labeled_collateral_polys.split_off(num_assets);
+    let borrowed_sums = sums.split_off(num_assets);
+
+    let (total, equity, labeled_equity_poly) =
+        prove_limbs::<F, D, PC, T, R>(ck, n, labeled_t_poly, t_commit, &equities, rng)?;
+
+    let proof = EquityProof {
+        collateral_sums: sums,
+        borrowed_sums,
+        positions,
+        equity,
+    };
+
+    end_timer!(timer);
+
+    Ok((
+        total,
+        proof,
+        labeled_tag_poly,
+        labeled_collateral_polys,
+        labeled_borrowed_polys,
+        labeled_equity_poly,
+    ))
+}
+
+#[cfg(all(test, not(feature = "blinding")))]
+mod test {
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Bn254, Fr};
+
+    use crate::{
+        commitment::KZG10,
+        balance_sum::{precompute, individual_open, individual_verify, MerlinTranscript},
+    };
+    use super::*;
+
+    type Domain = GeneralEvaluationDomain<Fr>;
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let num_users = 13;
+        let prices = [3u64, 1000, 70_000];
+        // setup
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(&pp, n, 0, None).unwrap();
+
+        // precompute
+        let (labeled_t_poly, t_commit) =
+            precompute::<_, Domain, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // generate random margin accounts, borrowing at most their collateral
+        let tags = (0..num_users)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let collateral = prices
+            .iter()
+            .map(|_| (0..num_users).map(|_| rng.gen_range(0..n as u64)).collect_vec())
+            .collect_vec();
+        let mut borrowed = collateral
+            .iter()
+            .map(|column| column.iter().map(|&c| rng.gen_range(0..=c)).collect_vec())
+            .collect_vec();
+        // a user borrowing an expensive asset against many cheap ones
+        borrowed[2][0] = 0;
+        borrowed[1][0] = 2;
+        let mut collateral = collateral;
+        collateral[1][0] = 0;
+        collateral[2][0] = 1;
+
+        // prove
+        let (total, proof, _, labeled_collateral_polys, _, _) =
+            prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &tags_ref,
+                &prices,
+                &collateral,
+                &borrowed,
+                rng,
+            ).unwrap();
+        let expected = net_equities(&prices, &collateral, &borrowed).unwrap();
+        assert_eq!(total, Fr::from(expected.iter().sum::<u128>()));
+
+        // verify
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit, &prices);
+        assert_eq!(res.unwrap(), total);
+
+        // verify under other prices
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit, &[3, 1000, 70_001]);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // users still open their positions
+        let b_commit = &proof.collateral_commits()[1];
+        let opening = individual_open::<_, Domain, KZG10<Bn254>>(
+            &ck,
+            n,
+            4,
+            &labeled_collateral_polys[1],
+            b_commit,
+        ).unwrap();
+        let res = individual_verify::<_, Domain, KZG10<Bn254>>(
+            &cvk,
+            n,
+            4,
+            collateral[1][4] as u128,
+            b_commit,
+            &opening,
+        );
+        assert!(res.is_ok());
+
+        // a user under water
+        borrowed[2][0] = 2;
+        let res = prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+            &ck,
+            n,
+            &labeled_t_poly,
+            &t_commit,
+            &tags_ref,
+            &prices,
+            &collateral,
+            &borrowed,
+            rng,
+        );
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+    }
+}
@@ -0,0 +1,338 @@
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::LabeledPolynomial;
+use ark_serialize::*;
+use itertools::Itertools;
+use num_traits::Zero;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::PosolError,
+    util::EvaluationDomainExt,
+    commitment::HomomorphicCommitment,
+    label_polynomial,
+};
+use super::{Proof, TranscriptProtocol};
+
+/// Balance sum proof over the base-`n` limbs of full-width balances.
+///
+/// Limb `j` of every balance is proven in its own [`Proof`], exactly as
+/// `PoSolVerifier.verifyProof` expects, and the limbs are aggregated with
+/// multiplier `n^j`.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct LimbsProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Balance sum of each limb column, least significant limb first.
+    pub sums: Vec<F>,
+    /// Balance sum proof of each limb column, least significant limb first.
+    pub proofs: Vec<Proof<F, D, PC>>,
+}
+
+impl<F, D, PC> LimbsProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Aggregated balance sum `Σ sum_j * n^j`.
+    pub fn aggregate_sum(&self, n: usize) -> F {
+        let multipliers = limb_multipliers::<F>(n, self.sums.len());
+        self.sums
+            .iter()
+            .zip(multipliers)
+            .map(|(&sum, multiplier)| sum * multiplier)
+            .sum()
+    }
+
+    /// Aggregated balance commitment `Σ b_commit_j * n^j`.
+    pub fn aggregate_b_commit(&self, n: usize) -> PC::Commitment {
+        let multipliers = limb_multipliers::<F>(n, self.proofs.len());
+        let commitments = self.proofs
+            .iter()
+            .map(|proof| proof.b_commit.clone())
+            .collect_vec();
+
+        PC::multi_scalar_mul(&commitments, &multipliers)
+    }
+
+    /// Verify every limb proof, returning the aggregated balance sum.
+    pub fn verify<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        n: usize,
+        t_commit: &PC::Commitment,
+    ) -> Result<F, PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        self.verify_with(n, |proof, sum| proof.verify::<T>(cvk, n, t_commit, sum))
+    }
+
+    /// Verify every limb proof with `verify`, given the limb base.
+    pub(super) fn verify_with<V>(&self, base: usize, verify: V) -> Result<F, PosolError>
+    where
+        V: Fn(&Proof<F, D, PC>, F) -> Result<(), PosolError>,
+    {
+        if self.proofs.is_empty() {
+            return Err(PosolError::Verification("no limb proof provided".into()));
+        }
+        if self.proofs.len() != self.sums.len() {
+            return Err(PosolError::Verification(format!(
+                "number of limb proofs {} mismatches number of limb sums {}",
+                self.proofs.len(),
+                self.sums.len(),
+            )));
+        }
+
+        // With more limbs than 128-bit balances need, the aggregated balance
+        // of a user could wrap around the field modulus and hide liabilities.
+        let max_limbs = num_limbs(base, u128::MAX)?;
+        if self.proofs.len() > max_limbs {
+            return Err(PosolError::Verification(format!(
+                "{} limb proofs exceed the {} limbs of 128-bit balances",
+                self.proofs.len(),
+                max_limbs,
+            )));
+        }
+
+        let timer = start_timer!(|| "Balance Sum: Verifying Limbs");
+
+        for (j, (proof, &sum)) in self.proofs.iter().zip(self.sums.iter()).enumerate() {
+            verify(proof, sum)
+                .map_err(|e| PosolError::Verification(format!("verification of limb {} failed: {}", j, e)))?;
+        }
+
+        end_timer!(timer);
+
+        Ok(self.aggregate_sum(base))
+    }
+}
+
+/// Prove the sum of full-width balances by decomposing them into base-`n` limbs.
+///
+/// Returns the aggregated balance sum, the limbs proof and the aggregated
+/// balance polynomial `Σ B_j(X) * n^j`, which opens to the full balance of each user.
+#[allow(clippy::type_complexity)]
+pub fn prove_limbs<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    labeled_t_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: &PC::Commitment,
+    balances: &[u128],
+    rng: &mut R,
+) -> Result<(F, LimbsProof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    prove_limbs_with(n, balances, |limb| {
+        super::prove::<F, D, PC, T, R>(ck, n, labeled_t_poly, t_commit, limb, rng)
+    })
+}
+
+/// Prove the limbs of `balances` in base `base` with `prove`.
+#[allow(clippy::type_complexity)]
+pub(super) fn prove_limbs_with<F, D, PC, P>(
+    base: usize,
+    balances: &[u128],
+    mut prove: P,
+) -> Result<(F, LimbsProof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    P: FnMut(&[u64]) -> Result<(F, Proof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>,
+{
+    let timer = start_timer!(|| "Balance Sum: Proving Limbs");
+
+    let max_balance = balances.iter().copied().max().unwrap_or(0);
+    let num_limbs = num_limbs(base, max_balance)?;
+    let limbs = decompose_balances(base, num_limbs, balances);
+
+    let mut sums = Vec::with_capacity(num_limbs);
+    let mut proofs = Vec::with_capacity(num_limbs);
+    let mut b_poly = DensePolynomial::zero();
+    for (limb, multiplier) in limbs.iter().zip(limb_multipliers::<F>(base, num_limbs)) {
+        let (sum, proof, labeled_limb_poly) = prove(limb)?;
+        b_poly += (multiplier, labeled_limb_poly.polynomial());
+        sums.push(sum);
+        proofs.push(proof);
+    }
+    let labeled_b_poly = label_polynomial!(b_poly);
+
+    let proof = LimbsProof { sums, proofs };
+    let m = proof.aggregate_sum(base);
+
+    end_timer!(timer);
+
+    Ok((m, proof, labeled_b_poly))
+}
+
+/// Number of base-`n` limbs needed to represent `max_balance`, at least one.
+pub fn num_limbs(n: usize, max_balance: u128) -> Result<usize, PosolError> {
+    if n < 2 {
+        return Err(PosolError::InputRange(format!("limb base {} below 2", n)));
+    }
+    let base = n as u128;
+    let mut num = 1;
+    let mut rest = max_balance / base;
+    while rest > 0 {
+        rest /= base;
+        num += 1;
+    }
+
+    Ok(num)
+}
+
+/// Split balances into `num_limbs` columns of base-`n` limbs, least significant first.
+fn decompose_balances(n: usize, num_limbs: usize, balances: &[u128]) -> Vec<Vec<u64>> {
+    let base = n as u128;
+    let mut limbs = vec![Vec::with_capacity(balances.len()); num_limbs];
+    for &balance in balances {
+        let mut rest = balance;
+        for limb in limbs.iter_mut() {
+            limb.push((rest % base) as u64);
+            rest /= base;
+        }
+        assert_eq!(rest, 0, "balance {} exceeds {} limbs", balance, num_limbs);
+    }
+
+    limbs
+}
+
+/// Multipliers `n^j` of the limb columns.
+fn limb_multipliers<F: FftField>(n: usize, num_limbs: usize) -> Vec<F> {
+    let base = F::from(n as u64);
+    let mut multiplier = F::one();
+    (0..num_limbs)
+        .map(|_| {
+            let current = multiplier;
+            multiplier *= base;
+            current
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Bn254, Fr};
+    use itertools::Itertools;
+
+    use crate::{commitment::KZG10, balance_sum::{precompute, individual_open, individual_verify}};
+    use super::{*, super::transcript::MerlinTranscript};
+
+    #[test]
+    fn test_decompose_balances() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let balances = (0..n)
+            .map(|_| rng.gen::<u64>() as u128)
+            .collect_vec();
+        let num_limbs = num_limbs(n, *balances.iter().max().unwrap()).unwrap();
+        let limbs = decompose_balances(n, num_limbs, &balances);
+
+        for (i, &balance) in balances.iter().enumerate() {
+            let recomposed = limbs
+                .iter()
+                .rev()
+                .fold(0u128, |acc, limb| {
+                    assert!(limb[i] < n as u64);
+                    acc * n as u128 + limb[i] as u128
+                });
+            assert_eq!(recomposed, balance);
+        }
+        assert_eq!(super::num_limbs(n, 0).unwrap(), 1);
+        assert_eq!(super::num_limbs(n, 15).unwrap(), 1);
+        assert_eq!(super::num_limbs(n, 16).unwrap(), 2);
+        assert_eq!(super::num_limbs(n, u128::MAX).unwrap(), 32);
+    }
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        // precompute
+        let (labeled_t_poly, t_commit) =
+            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // generate random full-width balances
+        let balances = (0..n - 3)
+            .map(|_| rng.gen::<u64>() as u128)
+            .collect_vec();
+
+        // prove
+        let (m, proof, labeled_b_poly) =
+            prove_limbs::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+        assert_eq!(m, Fr::from(balances.iter().sum::<u128>()));
+        assert_eq!(proof.proofs.len(), 16);
+
+        // verify
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit);
+        assert_eq!(res.unwrap(), m);
+
+        // more limbs than 128-bit balances need, each of them valid
+        let mut long = proof.clone();
+        while long.proofs.len() <= num_limbs(n, u128::MAX).unwrap() {
+            long.sums.push(proof.sums[0]);
+            long.proofs.push(proof.proofs[0].clone());
+        }
+        let res = long.verify::<MerlinTranscript>(&cvk, n, &t_commit);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // Individual Checking against the aggregated commitment
+        let b_commit = proof.aggregate_b_commit(n);
+        let i = rng.gen_range(0..n - 3);
+        let opening_proof = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &ck,
+            n,
+            i,
+            &labeled_b_poly,
+            &b_commit,
+        ).unwrap();
+        let res = individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &cvk,
+            n,
+            i,
+            balances[i],
+            &b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+    }
+}
@@ -1,11 +1,20 @@
-use core::ops::{AddAssign, SubAssign};
+use core::ops::AddAssign;
 use ark_std::{start_timer, end_timer};
 use ark_ff::FftField;
 use ark_poly::{univariate::DensePolynomial, EvaluationDomain, Polynomial};
 use num_traits::Zero;
 
-use crate::util::{EvaluationDomainExt, compute_lagrange_evaluation};
-use super::proof::Evaluations;
+use crate::{error::PosolError, util::{EvaluationDomainExt, compute_lagrange_evaluation}};
+use super::{
+    ColumnPolys,
+    proof::{
+        Evaluations,
+        ColumnEvaluations,
+        column_linear_scalars,
+        delta_powers,
+        quotient_linear_scalars,
+    },
+};
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn compute<F, D>(
@@ -21,7 +30,53 @@ pub(crate) fn compute<F, D>(
     z_poly: &DensePolynomial<F>,
     q1_poly: &DensePolynomial<F>,
     q2_poly: &DensePolynomial<F>,
-) -> (DensePolynomial<F>, Evaluations<F>)
+) -> Result<(DensePolynomial<F>, Evaluations<F>), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+{
+    let column = ColumnPolys {
+        m: F::zero(),
+        b_poly,
+        s_poly,
+        h1_poly,
+        h2_poly,
+        z_poly,
+    };
+    let (r_poly, t_eval, mut column_evals) =
+        compute_batched(domain, gamma, delta, z, t_poly, &[column], q1_poly, q2_poly)?;
+    let column_eval = column_evals.pop().unwrap();
+
+    let evaluations = Evaluations {
+        b: column_eval.b,
+        t: t_eval,
+        h1: column_eval.h1,
+        h2: column_eval.h2,
+        s_next: column_eval.s_next,
+        z_next: column_eval.z_next,
+        h1_next: column_eval.h1_next,
+        h2_next: column_eval.h2_next,
+    };
+
+    Ok((r_poly, evaluations))
+}
+
+/// Compute the linearisation polynomial of several balance columns sharing t(X),
+/// where the constraints of column `k` are batched with `delta^(8k)`.
+///
+/// Returns the linearisation polynomial, the evaluation of t(X) at `z` and the
+/// evaluations of each column.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn compute_batched<F, D>(
+    domain: &D,
+    gamma: F,
+    delta: F,
+    z: F,
+    t_poly: &DensePolynomial<F>,
+    columns: &[ColumnPolys<F>],
+    q1_poly: &DensePolynomial<F>,
+    q2_poly: &DensePolynomial<F>,
+) -> Result<(DensePolynomial<F>, F, Vec<ColumnEvaluations<F>>), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F> + EvaluationDomainExt<F>,
@@ -30,66 +85,52 @@ where
 
     let n = domain.size();
 
-    let b_eval = b_poly.evaluate(&z);
     let t_eval = t_poly.evaluate(&z);
-    let h1_eval = h1_poly.evaluate(&z);
-    let h2_eval = h2_poly.evaluate(&z);
     let zh_eval = domain.evaluate_vanishing_polynomial(z);
-    let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z);
-    let ln_eval = compute_lagrange_evaluation(n, domain.element(n - 1), zh_eval, z);
-
+    let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z)
+        .ok_or_else(|| PosolError::Opening("challenge z is in the domain".into()))?;
+    let ln_eval = compute_lagrange_evaluation(n, domain.element(n - 1), zh_eval, z)
+        .ok_or_else(|| PosolError::Opening("challenge z is in the domain".into()))?;
     let z_next = z * domain.group_gen();
-    let s_next_eval = s_poly.evaluate(&z_next);
-    let z_next_eval = z_poly.evaluate(&z_next);
-    let h1_next_eval = h1_poly.evaluate(&z_next);
-    let h2_next_eval = h2_poly.evaluate(&z_next);
 
-    let delta_exp_2 = delta.square();
-    let delta_exp_3 = delta_exp_2 * delta;
-    let delta_exp_4 = delta_exp_3 * delta;
-    let delta_exp_5 = delta_exp_4 * delta;
-    let delta_exp_6 = delta_exp_5 * delta;
-    let delta_exp_7 = delta_exp_6 * delta;
+    let deltas = delta_powers(delta);
+    let delta_exp_8 = deltas[6] * delta;
 
     let mut r_poly = DensePolynomial::zero();
-    r_poly.sub_assign(s_poly);
-    r_poly.sub_assign(b_poly);
-    r_poly.add_assign(
-        &(z_poly * ((gamma + b_eval) * (gamma + t_eval) * delta + l0_eval * delta_exp_2))
-    );
-    r_poly.add_assign(
-        &(h1_poly * (
-            l0_eval * delta_exp_6
-            - (h1_next_eval - h1_eval - F::one()) * (ln_eval - F::one()) * delta_exp_3
-            - (h2_next_eval - h1_eval - F::one()) * ln_eval * delta_exp_5
-        ))
-    );
-    r_poly.add_assign(
-        &(h2_poly * (
-            ln_eval * delta_exp_7
-            - z_next_eval * (gamma + h1_eval) * delta
-            - (h2_next_eval - h2_eval - F::one()) * (ln_eval - F::one()) * delta_exp_4
-        ))
-    );
-    r_poly.sub_assign(&(q1_poly * zh_eval));
-    if cfg!(blinding) {
-        r_poly.sub_assign(&(q2_poly * (zh_eval * (zh_eval + F::one()) * z.square() * z)));
-    } else {
-        r_poly.sub_assign(&(q2_poly * (zh_eval * (zh_eval + F::one()))));
+    let mut column_evals = Vec::with_capacity(columns.len());
+    let mut factor = F::one();
+    for column in columns {
+        let evals = ColumnEvaluations {
+            b: column.b_poly.evaluate(&z),
+            h1: column.h1_poly.evaluate(&z),
+            h2: column.h2_poly.evaluate(&z),
+            s_next: column.s_poly.evaluate(&z_next),
+            z_next: column.z_poly.evaluate(&z_next),
+            h1_next: column.h1_poly.evaluate(&z_next),
+            h2_next: column.h2_poly.evaluate(&z_next),
+        };
+
+        let scalars = column_linear_scalars(gamma, &deltas, t_eval, l0_eval, ln_eval, &evals);
+        let polys = [
+            column.s_poly,
+            column.b_poly,
+            column.z_poly,
+            column.h1_poly,
+            column.h2_poly,
+        ];
+        for (poly, scalar) in polys.into_iter().zip(scalars) {
+            r_poly.add_assign((scalar * factor, poly));
+        }
+
+        column_evals.push(evals);
+        factor *= delta_exp_8;
     }
 
-    let evaluations = Evaluations {
-        b: b_eval,
-        t: t_eval,
-        h1: h1_eval,
-        h2: h2_eval,
-        s_next: s_next_eval,
-        z_next: z_next_eval,
-        h1_next: h1_next_eval,
-        h2_next: h2_next_eval,
-    };
+    let [q1_scalar, q2_scalar] = quotient_linear_scalars(z, zh_eval);
+    r_poly.add_assign((q1_scalar, q1_poly));
+    r_poly.add_assign((q2_scalar, q2_poly));
 
     end_timer!(timer);
 
-    (r_poly, evaluations)
+    Ok((r_poly, t_eval, column_evals))
 }
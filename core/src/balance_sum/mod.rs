@@ -1,13 +1,20 @@
 
+pub mod debt;
+pub mod equity;
+mod limbs;
 mod linear_poly;
+pub mod multi_asset;
+pub mod priced;
 mod quotient_poly;
 mod proof;
+mod session;
 mod transcript;
 
+pub use limbs::*;
 pub use proof::*;
+pub use session::*;
 pub use transcript::*;
 
-use anyhow::{anyhow, Result};
 use ark_std::{collections::HashMap, start_timer, end_timer};
 use ark_ff::{FftField, Field};
 use ark_poly::{EvaluationDomain, univariate::DensePolynomial, UVPolynomial};
@@ -17,41 +24,57 @@ use rand_core::{CryptoRng, RngCore};
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+use quotient_poly::ExtendedDomain;
 use crate::{
-    util::{EvaluationDomainExt, poly_from_evals_ref, poly_from_evals},
+    error::{domain, PosolError},
+    util::{EvaluationDomainExt, add_lagrange_deltas, poly_from_evals_ref, poly_from_evals},
     commitment::HomomorphicCommitment,
+    srs::lagrange::LagrangeBasis,
+    opening::{self, MultiOpening},
     label_polynomial, label_commitment,
 };
 
+#[allow(clippy::type_complexity)]
 pub fn precompute<F, D, PC>(
     ck: &PC::CommitterKey,
     n: usize,
-) -> Result<(LabeledPolynomial<F, DensePolynomial<F>>, PC::Commitment)>
+) -> Result<(LabeledPolynomial<F, DensePolynomial<F>>, PC::Commitment), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
+    let domain = domain::<F, D>(n)?;
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    precompute_in::<F, D, PC>(ck, &domain, n)
+}
 
+/// Precompute the range table `{0, ..., table_size - 1}` over `domain`,
+/// padded with its largest value.
+#[allow(clippy::type_complexity)]
+fn precompute_in<F, D, PC>(
+    ck: &PC::CommitterKey,
+    domain: &D,
+    table_size: usize,
+) -> Result<(LabeledPolynomial<F, DensePolynomial<F>>, PC::Commitment), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+{
     let timer = start_timer!(|| "Balance Sum: Precomputing");
 
     // Precompute t(X).
-    let t_evals = (0..n).into_iter().map(|i| F::from(i as u64)).collect_vec();
-    let t_poly = poly_from_evals(&domain, t_evals);
+    let t_evals = (0..domain.size())
+        .map(|i| F::from(i.min(table_size - 1) as u64))
+        .collect_vec();
+    let t_poly = poly_from_evals(domain, t_evals);
     let labeled_t_poly = label_polynomial!(t_poly);
 
     // Commit to t(X).
     let (labeled_t_commit, _) =
         PC::commit(ck, vec![&labeled_t_poly], None)
-            .map_err(|e| anyhow!("commit to t(X) failed: {}", e))?;
+            .map_err(|e| PosolError::Commitment(format!("commit to t(X) failed: {}", e)))?;
 
     end_timer!(timer);
 
@@ -61,6 +84,7 @@ where
     ))
 }
 
+#[allow(clippy::type_complexity)]
 pub fn prove<F, D, PC, T, R>(
     ck: &PC::CommitterKey,
     n: usize,
@@ -68,7 +92,7 @@ pub fn prove<F, D, PC, T, R>(
     t_commit: &PC::Commitment,
     balances: &[u64],
     rng: &mut R,
-) -> Result<(F, Proof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>)>
+) -> Result<(F, Proof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F> + EvaluationDomainExt<F>,
@@ -76,19 +100,47 @@ where
     T: TranscriptProtocol<F, PC::Commitment>,
     R: CryptoRng + RngCore,
 {
-    assert!(n.is_power_of_two());
-    assert!(balances.len() <= n);
+    check_balances(n, n, balances)?;
 
-    for &balance in balances {
-        assert!(balance < n as u64);
-    }
+    let domain = domain::<F, D>(n)?;
+    let extended_domain = ExtendedDomain::new(&domain, labeled_t_poly.polynomial())?;
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    prove_in::<F, D, PC, T, R>(
+        ck,
+        &domain,
+        &extended_domain,
+        n,
+        1,
+        labeled_t_poly,
+        t_commit,
+        balances,
+        rng,
+    )
+}
+
+/// Prove over a domain and its extended domain computed beforehand, given
+/// balances already checked to be below `table_size`. The balance of user `i`
+/// is the `(i * stride)`-th evaluation of B(X) over the domain.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn prove_in<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    domain: &D,
+    extended_domain: &ExtendedDomain<F, D>,
+    table_size: usize,
+    stride: usize,
+    labeled_t_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: &PC::Commitment,
+    balances: &[u64],
+    rng: &mut R,
+) -> Result<(F, Proof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    let n = domain.size();
 
     let timer = start_timer!(|| "Balance Sum: Proving");
 
@@ -96,8 +148,10 @@ where
     transcript.append_u64("n", n as u64);
 
     // Compute balances vector `B`.
-    let mut b_evals = balances.iter().map(|&b| F::from(b)).collect_vec();
-    b_evals.resize(n, F::zero());
+    let mut b_evals = vec![F::zero(); n];
+    for (i, &balance) in balances.iter().enumerate() {
+        b_evals[i * stride] = F::from(balance);
+    }
 
     // Compute aux vector `S`.
     let s_evals = generate_s_evals(&b_evals);
@@ -107,30 +161,30 @@ where
     transcript.append_scalar("m", &m);
 
     // Compute polynomials B(X).
-    let mut b_poly = poly_from_evals_ref(&domain, &b_evals);
-    if cfg!(blinding) {
-        add_blinders_to_poly(rng, 2, &mut b_poly);
+    let mut b_poly = poly_from_evals_ref(domain, &b_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 2, &mut b_poly);
     }
     let labeled_b_poly = label_polynomial!(b_poly);
 
     // Compute aux polynomial S(X).
-    let mut s_poly = poly_from_evals(&domain, s_evals);
-    if cfg!(blinding) {
-        add_blinders_to_poly(rng, 3, &mut s_poly);
+    let mut s_poly = poly_from_evals(domain, s_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 3, &mut s_poly);
     }
     let labeled_s_poly = label_polynomial!(s_poly);
 
     // Compute polynomials h1(X) and h2(X).
-    let (h1_evals, h2_evals) = generate_h_evals(&b_evals);
-    let mut h1_poly = poly_from_evals_ref(&domain, &h1_evals);
-    if cfg!(blinding) {
-        add_blinders_to_poly(rng, 3, &mut h1_poly);
+    let (h1_evals, h2_evals) = generate_h_evals(&b_evals, table_size);
+    let mut h1_poly = poly_from_evals_ref(domain, &h1_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 3, &mut h1_poly);
     }
     let labeled_h1_poly = label_polynomial!(h1_poly);
 
-    let mut h2_poly = poly_from_evals_ref(&domain, &h2_evals);
-    if cfg!(blinding) {
-        add_blinders_to_poly(rng, 3, &mut h2_poly);
+    let mut h2_poly = poly_from_evals_ref(domain, &h2_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 3, &mut h2_poly);
     }
     let labeled_h2_poly = label_polynomial!(h2_poly);
 
@@ -143,7 +197,7 @@ where
             &labeled_h2_poly,
         ], None)
         .map_err(|e| {
-            anyhow!("commit to B(x), S(X), h1(X), h2(X) failed: {}", e)
+            PosolError::Commitment(format!("commit to B(x), S(X), h1(X), h2(X) failed: {}", e))
         })?;
 
     // Add commitments to transcript.
@@ -156,20 +210,20 @@ where
     let gamma = transcript.challenge_scalar("gamma");
 
     // Compute polynomial z(X)
-    let z_evals = generate_z_evals(gamma, &b_evals, &h1_evals, &h2_evals);
+    let z_evals = generate_z_evals(gamma, table_size, &b_evals, &h1_evals, &h2_evals)?;
     drop(b_evals);
     drop(h1_evals);
     drop(h2_evals);
-    let mut z_poly = poly_from_evals(&domain, z_evals);
-    if cfg!(blinding) {
-        add_blinders_to_poly(rng, 3, &mut z_poly);
+    let mut z_poly = poly_from_evals(domain, z_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 3, &mut z_poly);
     }
     let labeled_z_poly = label_polynomial!(z_poly);
 
     // Commit to z(X).
     let (labeled_z_commit, _) =
         PC::commit(ck, vec![&labeled_z_poly], None)
-            .map_err(|e| anyhow!("commit to z(X) failed: {}", e))?;
+            .map_err(|e| PosolError::Commitment(format!("commit to z(X) failed: {}", e)))?;
     
     // Add commitment to transcript.
     transcript.append_commitment("z_commit", labeled_z_commit[0].commitment());
@@ -178,7 +232,9 @@ where
     let delta = transcript.challenge_scalar("delta");
 
     let q_poly = quotient_poly::compute(
-        &domain,
+        domain,
+        extended_domain,
+        table_size,
         m,
         gamma,
         delta,
@@ -191,22 +247,14 @@ where
     )?;
     
     // Split quotient polynomials.
-    let split = if cfg!(blinding) { n + 3 } else { n };
-    let mut q1_poly = DensePolynomial::from_coefficients_slice(&q_poly[..split]);
-    let mut q2_poly = DensePolynomial::from_coefficients_slice(&q_poly[split..]);
-    if cfg!(blinding) {
-        // Add blinding factors for quotient polynomials.
-        let e0 = F::rand(rng);
-        q1_poly.coeffs.push(e0);
-        q2_poly.coeffs[0] -= e0;
-    }
+    let (q1_poly, q2_poly) = split_quotient_poly(n, &q_poly, rng);
     let labeled_q1_poly = label_polynomial!(q1_poly);
     let labeled_q2_poly = label_polynomial!(q2_poly);
     
     // Commit to quotient polynomials.
     let (labeled_q_commits, _) =
         PC::commit(ck, vec![&labeled_q1_poly, &labeled_q2_poly], None)
-            .map_err(|e| anyhow!("commit to q1(X), q2(X) failed: {}", e))?;
+            .map_err(|e| PosolError::Commitment(format!("commit to q1(X), q2(X) failed: {}", e)))?;
 
     // Add commitments to transcript.
     transcript.append_commitment("q1_commit", labeled_q_commits[0].commitment());
@@ -216,7 +264,7 @@ where
     let z = transcript.challenge_scalar("z");
 
     let (r_poly, evaluations) = linear_poly::compute(
-        &domain,
+        domain,
         gamma,
         delta,
         z,
@@ -228,7 +276,7 @@ where
         labeled_z_poly.polynomial(),
         labeled_q1_poly.polynomial(),
         labeled_q2_poly.polynomial(),
-    );
+    )?;
     drop(labeled_q1_poly);
     drop(labeled_q2_poly);
     let labeled_r_poly = label_polynomial!(r_poly);
@@ -248,9 +296,9 @@ where
     // Commit to linear polynomial.
     let (labeled_r_commit, _) =
         PC::commit(ck, vec![&labeled_r_poly], None)
-            .map_err(|e| anyhow!("commit to r(X) failed: {}", e))?;
+            .map_err(|e| PosolError::Commitment(format!("commit to r(X) failed: {}", e)))?;
 
-    let labeled_t_commit = label_commitment!(t_commit);
+    let labeled_t_commit = label_commitment!(t_commit, labeled_t_poly.label());
     let randomness = <PC::Randomness as PCRandomness>::empty();
     // Compute opening proofs.
     let w_opening = PC::open(
@@ -274,7 +322,7 @@ where
         vec![&randomness, &randomness, &randomness, &randomness, &randomness],
         None,
     )
-    .map_err(|e| anyhow!("open W(X) failed: {}", e))?;
+    .map_err(|e| PosolError::Opening(format!("open W(X) failed: {}", e)))?;
     drop(labeled_r_poly);
 
     let sw_opening = PC::open(
@@ -296,7 +344,7 @@ where
         vec![&randomness, &randomness, &randomness, &randomness],
         None,
     )
-    .map_err(|e| anyhow!("open W_next(X) failed: {}", e))?;
+    .map_err(|e| PosolError::Opening(format!("open W_next(X) failed: {}", e)))?;
 
     let proof = Proof {
         b_commit: labeled_bsh_commits[0].commitment().clone(),
@@ -309,7 +357,7 @@ where
         w_opening,
         sw_opening,
         evaluations,
-        _p: std::marker::PhantomData,
+        _p: ark_std::marker::PhantomData,
     };
 
     end_timer!(timer);
@@ -317,31 +365,141 @@ where
     Ok((m, proof, labeled_b_poly))
 }
 
+/// Commit to B(X) directly from the balances with the Lagrange basis of the
+/// domain, without interpolating B(X). Padding is not committed to, so the
+/// cost only depends on the number of balances. The commitment is the same as
+/// `b_commit` of [`prove`], unless B(X) is randomised by the `blinding` feature.
+pub fn commit_evals<F, PC>(
+    basis: &LagrangeBasis<PC::Commitment>,
+    balances: &[u64],
+) -> Result<PC::Commitment, PosolError>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    if cfg!(feature = "blinding") {
+        return Err(PosolError::Commitment("blinded B(X) cannot be committed from its evaluations".into()));
+    }
+    check_balances(basis.size(), basis.size(), balances)?;
+
+    let timer = start_timer!(|| "Balance Sum: Committing Evaluations");
+
+    let b_evals = balances.iter().map(|&b| F::from(b)).collect_vec();
+    let b_commit = basis.commit::<F, PC>(&b_evals)?;
+
+    end_timer!(timer);
+
+    Ok(b_commit)
+}
+
+/// Update the commitment to B(X) of a previous snapshot by the changed
+/// balances, given as `(index, old balance, new balance)`, in time linear in
+/// the number of changes. The balance sum proof itself must be generated again.
+pub fn update_commit<F, PC>(
+    basis: &LagrangeBasis<PC::Commitment>,
+    b_commit: &PC::Commitment,
+    updates: &[(usize, u64, u64)],
+) -> Result<PC::Commitment, PosolError>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    let timer = start_timer!(|| "Balance Sum: Updating Commitment");
+
+    let deltas = balance_deltas(basis.size(), updates)?;
+    let b_commit = basis.update::<F, PC>(b_commit, &deltas)?;
+
+    end_timer!(timer);
+
+    Ok(b_commit)
+}
+
+/// Update B(X) by the changed balances, so that the updated commitment can be opened.
+pub fn update_poly<F, D>(
+    n: usize,
+    labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    updates: &[(usize, u64, u64)],
+) -> Result<LabeledPolynomial<F, DensePolynomial<F>>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Balance Sum: Updating Polynomial");
+
+    let deltas = balance_deltas(n, updates)?;
+    if let Some(&(i, _)) = deltas.iter().find(|(i, _)| *i >= n) {
+        return Err(PosolError::InputRange(format!("index {} out of domain of size {}", i, n)));
+    }
+    let updated_poly = add_lagrange_deltas(&domain, labeled_b_poly.polynomial(), &deltas);
+
+    end_timer!(timer);
+
+    Ok(LabeledPolynomial::new(labeled_b_poly.label().clone(), updated_poly, None, None))
+}
+
+fn balance_deltas<F: Field>(
+    n: usize,
+    updates: &[(usize, u64, u64)],
+) -> Result<Vec<(usize, F)>, PosolError> {
+    updates
+        .iter()
+        .map(|&(i, old, new)| {
+            if new >= n as u64 {
+                return Err(PosolError::InputRange(format!("balance {} not below {}", new, n)));
+            }
+            Ok((i, F::from(new) - F::from(old)))
+        })
+        .collect()
+}
+
+/// Check that there are at most `n` balances, each below `table_size`.
+pub(crate) fn check_balances(n: usize, table_size: usize, balances: &[u64]) -> Result<(), PosolError> {
+    if balances.len() > n {
+        return Err(PosolError::InputRange(format!("{} balances out of domain of size {}", balances.len(), n)));
+    }
+    if let Some(balance) = balances.iter().find(|&&b| b >= table_size as u64) {
+        return Err(PosolError::InputRange(format!("balance {} not below {}", balance, table_size)));
+    }
+
+    Ok(())
+}
+
 pub fn individual_open<F, D, PC>(
     ck: &PC::CommitterKey,
     n: usize,
     i: usize,
     labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
     b_commit: &PC::Commitment,
-) -> Result<PC::Proof>
+) -> Result<PC::Proof, PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
+    let domain = domain::<F, D>(n)?;
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    individual_open_in::<F, D, PC>(ck, &domain, i, labeled_b_poly, b_commit)
+}
 
+fn individual_open_in<F, D, PC>(
+    ck: &PC::CommitterKey,
+    domain: &D,
+    i: usize,
+    labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    b_commit: &PC::Commitment,
+) -> Result<PC::Proof, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+{
     let timer = start_timer!(|| "Balance: Individual Opening");
 
     let point = domain.element(i);
-    let labeled_b_commit = label_commitment!(b_commit);
+    let labeled_b_commit = label_commitment!(b_commit, labeled_b_poly.label());
     let randomness = <PC::Randomness as PCRandomness>::empty();
     let proof = PC::open(
         ck,
@@ -352,7 +510,7 @@ where
         vec![&randomness],
         None,
     )
-    .map_err(|e| anyhow!("open B(X) failed: {}", e))?;
+    .map_err(|e| PosolError::Opening(format!("open B(X) failed: {}", e)))?;
 
     end_timer!(timer);
 
@@ -363,28 +521,37 @@ pub fn individual_verify<F, D, PC>(
     vk: &PC::VerifierKey,
     n: usize,
     i: usize,
-    balance: u64,
+    balance: u128,
     b_commit: &PC::Commitment,
     proof: &PC::Proof,
-) -> Result<()>
+) -> Result<(), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
+    let domain = domain::<F, D>(n)?;
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    individual_verify_in::<F, D, PC>(vk, &domain, i, F::from(balance), b_commit, proof)
+}
 
+/// Check the opening of a balance polynomial to `evaluation` at the `i`-th element of `domain`.
+fn individual_verify_in<F, D, PC>(
+    vk: &PC::VerifierKey,
+    domain: &D,
+    i: usize,
+    evaluation: F,
+    b_commit: &PC::Commitment,
+    proof: &PC::Proof,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+{
     let timer = start_timer!(|| "Balance: Individual Verifying");
 
     let point = domain.element(i);
-    let evaluation = F::from(balance);
     let labeled_b_commit = label_commitment!(b_commit);
     match PC::check(
         vk,
@@ -399,18 +566,145 @@ where
             end_timer!(timer);
             Ok(())
         }
-        Ok(false) => Err(anyhow!("individual balance verification failed")),
-        Err(e) => Err(anyhow!("check opening proof error: {}", e)),
+        Ok(false) => Err(PosolError::Verification("individual balance verification failed".into())),
+        Err(e) => Err(PosolError::Verification(format!("check opening proof error: {}", e))),
     }
 }
 
-/// Add blinding factors to polynomial.
-fn add_blinders_to_poly<F, R>(rng: &mut R, k: usize, poly: &mut DensePolynomial<F>)
+/// Open B(X) at the domain points of every index in `indices` with one proof.
+pub fn multi_open<F, D, PC, T>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    indices: &[usize],
+    labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    b_commit: &PC::Commitment,
+) -> Result<MultiOpening<F, PC>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    multi_open_in::<F, D, PC, T>(ck, &domain, indices, labeled_b_poly, b_commit)
+}
+
+fn multi_open_in<F, D, PC, T>(
+    ck: &PC::CommitterKey,
+    domain: &D,
+    indices: &[usize],
+    labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    b_commit: &PC::Commitment,
+) -> Result<MultiOpening<F, PC>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let timer = start_timer!(|| "Balance: Multiple Opening");
+
+    let proof = opening::multi_open::<F, D, PC, T>(ck, domain, indices, labeled_b_poly, b_commit)?;
+
+    end_timer!(timer);
+
+    Ok(proof)
+}
+
+/// Verify the balances of every index in `indices` against one multi-point opening.
+pub fn multi_verify<F, D, PC, T>(
+    vk: &PC::VerifierKey,
+    n: usize,
+    indices: &[usize],
+    balances: &[u128],
+    b_commit: &PC::Commitment,
+    proof: &MultiOpening<F, PC>,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    multi_verify_in::<F, D, PC, T>(vk, &domain, indices, balances, b_commit, proof)
+}
+
+fn multi_verify_in<F, D, PC, T>(
+    vk: &PC::VerifierKey,
+    domain: &D,
+    indices: &[usize],
+    balances: &[u128],
+    b_commit: &PC::Commitment,
+    proof: &MultiOpening<F, PC>,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let timer = start_timer!(|| "Balance: Multiple Verifying");
+
+    let evaluations = balances.iter().map(|&b| F::from(b)).collect_vec();
+    opening::multi_check::<F, D, PC, T>(vk, domain, indices, &evaluations, b_commit, proof)
+        .map_err(|e| PosolError::Verification(format!("multiple balance verification failed: {}", e)))?;
+
+    end_timer!(timer);
+
+    Ok(())
+}
+
+/// Polynomials of a single balance column.
+pub(crate) struct ColumnPolys<'a, F: Field> {
+    /// Balance sum of the column.
+    pub(crate) m: F,
+    pub(crate) b_poly: &'a DensePolynomial<F>,
+    pub(crate) s_poly: &'a DensePolynomial<F>,
+    pub(crate) h1_poly: &'a DensePolynomial<F>,
+    pub(crate) h2_poly: &'a DensePolynomial<F>,
+    pub(crate) z_poly: &'a DensePolynomial<F>,
+}
+
+/// Split quotient polynomial into q1(X) and q2(X).
+fn split_quotient_poly<F, R>(
+    n: usize,
+    q_poly: &DensePolynomial<F>,
+    rng: &mut R,
+) -> (DensePolynomial<F>, DensePolynomial<F>)
+where
+    F: Field,
+    R: RngCore + CryptoRng,
+{
+    let split = if cfg!(feature = "blinding") { n + 3 } else { n };
+    let mut q1_poly = DensePolynomial::from_coefficients_slice(&q_poly[..split]);
+    let mut q2_poly = DensePolynomial::from_coefficients_slice(&q_poly[split..]);
+    if cfg!(feature = "blinding") {
+        // Add blinding factors for quotient polynomials.
+        let e0 = F::rand(rng);
+        q1_poly.coeffs.push(e0);
+        q2_poly.coeffs[0] -= e0;
+    }
+
+    (q1_poly, q2_poly)
+}
+
+/// Add blinding factors to polynomial, i.e. `poly(X) + (b_0 + ... + b_{k-1} X^{k-1}) * (X^n - 1)`.
+fn add_blinders_to_poly<F, R>(
+    rng: &mut R,
+    n: usize,
+    k: usize,
+    poly: &mut DensePolynomial<F>,
+)
 where
     F: Field,
     R: RngCore + CryptoRng,
 {
-    let blinders = (0..k).into_iter().map(|_| F::rand(rng)).collect_vec();
+    let blinders = (0..k).map(|_| F::rand(rng)).collect_vec();
+    // Leading zero coefficients were trimmed, e.g. for a zero column.
+    poly.coeffs.resize(n, F::zero());
     poly.coeffs.extend_from_slice(&blinders);
     
     ark_std::cfg_iter_mut!(poly.coeffs)
@@ -433,7 +727,9 @@ fn generate_s_evals<F: Field>(b_evals: &[F]) -> Vec<F> {
     s_evals
 }
 
-fn generate_h_evals<F: Field>(b_evals: &[F]) -> (Vec<F>, Vec<F>) {
+/// Sort the balances together with the range table `{0, ..., table_size - 1}`
+/// padded to the domain size with its largest value, and split the result in two.
+fn generate_h_evals<F: Field>(b_evals: &[F], table_size: usize) -> (Vec<F>, Vec<F>) {
     let n = b_evals.len();
 
     let mut counter = HashMap::with_capacity(n);
@@ -447,21 +743,18 @@ fn generate_h_evals<F: Field>(b_evals: &[F]) -> (Vec<F>, Vec<F>) {
 
     let mut current = F::zero();
     let mut h = Vec::with_capacity(2 * n);
-    loop {
+    for _ in 0..table_size {
         if let Some(&num) = counter.get(&current) {
             h.extend(vec![current; num + 1]);
         } else {
             h.push(current);
         }
         current += F::one();
-
-        if h.len() >= 2 * n {
-            break;
-        }
     }
 
     // Sanity check
-    assert_eq!(current, F::from(n as u64));
+    assert_eq!(h.len(), n + table_size);
+    h.resize(2 * n, current - F::one());
 
     let (h1, h2) = h.split_at(n);
     (h1.to_vec(), h2.to_vec())
@@ -469,10 +762,11 @@ fn generate_h_evals<F: Field>(b_evals: &[F]) -> (Vec<F>, Vec<F>) {
 
 fn generate_z_evals<F: Field>(
     gamma: F,
+    table_size: usize,
     b_evals: &[F],
     h1_evals: &[F],
     h2_evals: &[F],
-) -> Vec<F> {
+) -> Result<Vec<F>, PosolError> {
     let n = b_evals.len();
     assert_eq!(h1_evals.len(), n);
     assert_eq!(h2_evals.len(), n);
@@ -481,58 +775,70 @@ fn generate_z_evals<F: Field>(
     let mut z_evals = Vec::with_capacity(b_evals.len());
     z_evals.push(product);
     for i in 0..(n - 1) {
-        let numerator = (gamma + b_evals[i]) * (gamma + F::from(i as u64));
+        let numerator = (gamma + b_evals[i]) * (gamma + F::from(i.min(table_size - 1) as u64));
         let denominator = (gamma + h1_evals[i]) * (gamma + h2_evals[i]);
 
-        product *= numerator * denominator.inverse().unwrap();
+        let denominator_inv = denominator
+            .inverse()
+            .ok_or(PosolError::Commitment("challenge gamma zeroes Z(X) denominator".into()))?;
+        product *= numerator * denominator_inv;
         z_evals.push(product);
     }
 
-    z_evals
+    Ok(z_evals)
 }
 
 #[cfg(test)]
 mod test {
-    use ark_ff::UniformRand;
+    use ark_ff::{PrimeField, UniformRand};
     use ark_poly::{GeneralEvaluationDomain, EvaluationDomain, Polynomial};
-    use ark_poly_commit::PolynomialCommitment;
-    use ark_std::{test_rng, rand::Rng};
+    use ark_std::{test_rng, rand::{Rng, rngs::StdRng}};
     use ark_bn254::{Bn254, Fr};
+    use ark_pallas::Affine as PallasAffine;
+    use blake2::Blake2s;
     use itertools::Itertools;
     use num_traits::{Zero, One};
     
-    use crate::{commitment::KZG10, util::poly_from_evals_ref};
+    use crate::{commitment::{KZG10, IPA}, util::poly_from_evals_ref};
     use super::{*, transcript::MerlinTranscript};
 
     #[test]
     fn test_add_blinders_to_poly() {
         let rng = &mut test_rng();
         // 8 degree poly
-        let domain = GeneralEvaluationDomain::new(8).unwrap();
+        let n = 8;
+        let domain = GeneralEvaluationDomain::new(n).unwrap();
         let evals = (0..8).into_iter().map(|_| Fr::rand(rng)).collect_vec();
         let poly = poly_from_evals_ref(&domain, &evals);
 
         // add 1 blinder
         let mut poly_1 = poly.clone();
-        add_blinders_to_poly(rng, 1, &mut poly_1);
+        add_blinders_to_poly(rng, n, 1, &mut poly_1);
         for (ele, expect) in domain.elements().zip(evals.iter()) {
             let res = poly_1.evaluate(&ele);
             assert_eq!(&res, expect);
         }
         // add 2 blinders
         let mut poly_2 = poly.clone();
-        add_blinders_to_poly(rng, 2, &mut poly_2);
+        add_blinders_to_poly(rng, n, 2, &mut poly_2);
         for (ele, expect) in domain.elements().zip(evals.iter()) {
             let res = poly_2.evaluate(&ele);
             assert_eq!(&res, expect);
         }
         // add 3 blinders
         let mut poly_3 = poly.clone();
-        add_blinders_to_poly(rng, 3, &mut poly_3);
+        add_blinders_to_poly(rng, n, 3, &mut poly_3);
         for (ele, expect) in domain.elements().zip(evals.iter()) {
             let res = poly_3.evaluate(&ele);
             assert_eq!(&res, expect);
         }
+        // add 2 blinders to zero poly
+        let mut poly_0 = DensePolynomial::<Fr>::zero();
+        add_blinders_to_poly(rng, n, 2, &mut poly_0);
+        assert_eq!(poly_0.degree(), n + 1);
+        for ele in domain.elements() {
+            assert!(poly_0.evaluate(&ele).is_zero());
+        }
     }
 
     #[test]
@@ -560,7 +866,7 @@ mod test {
             Fr::from(b)
         }).collect_vec();
 
-        let (h1_evals, h2_evals) = generate_h_evals(&b_evals);
+        let (h1_evals, h2_evals) = generate_h_evals(&b_evals, 16);
         // Check that h1_evals are in increasing order
         h1_evals
             .iter()
@@ -590,9 +896,9 @@ mod test {
             Fr::from(b)
         }).collect_vec();
 
-        let (h1_evals, h2_evals) = generate_h_evals(&b_evals);
+        let (h1_evals, h2_evals) = generate_h_evals(&b_evals, 16);
         let gamma = Fr::rand(rng);
-        let z_evals = generate_z_evals(gamma, &b_evals, &h1_evals, &h2_evals);
+        let z_evals = generate_z_evals(gamma, 16, &b_evals, &h1_evals, &h2_evals).unwrap();
         let shifted_z = [&z_evals[1..], &z_evals[..1]].concat();
         itertools::izip!(b_evals, z_evals, shifted_z, h1_evals, h2_evals)
             .enumerate()
@@ -605,11 +911,23 @@ mod test {
 
     #[test]
     fn test_full() {
+        check_full::<_, KZG10<Bn254>>(&mut test_rng());
+    }
+
+    #[test]
+    fn test_full_ipa() {
+        check_full::<_, IPA<PallasAffine, Blake2s>>(&mut test_rng());
+    }
+
+    #[test]
+    fn test_errors() {
+        use ark_poly_commit::PolynomialCommitment;
+
         let rng = &mut test_rng();
 
         let n = 16;
         // setup
-        let max_degree = if cfg!(blinding) { n + 3 } else { n };
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
         let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
         let (ck, cvk) = KZG10::<Bn254>::trim(
             &pp,
@@ -617,21 +935,68 @@ mod test {
             0,
             None,
         ).unwrap();
+        let (labeled_t_poly, labeled_t_commit) =
+            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+        let mut prove = |n: usize, balances: &[u64]| {
+            prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &labeled_t_commit,
+                balances,
+                rng,
+            )
+        };
+
+        // domain size not a power of two
+        let res = prove(12, &[1, 2, 3]);
+        assert!(matches!(res, Err(PosolError::Domain { size: 12, .. })));
+        // balance not below the domain size
+        let res = prove(n, &[1, n as u64, 3]);
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+        // more balances than the domain size
+        let res = prove(n, &[0; 17]);
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+
+        // wrong sum
+        let (m, proof, _) = prove(n, &[1, 2, 3]).unwrap();
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &labeled_t_commit, m + Fr::from(1u64));
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+        let res = proof.verify::<MerlinTranscript>(&cvk, 12, &labeled_t_commit, m);
+        assert!(matches!(res, Err(PosolError::Domain { .. })));
+
+        assert!(matches!(num_limbs(1, 5), Err(PosolError::InputRange(_))));
+    }
+
+    fn check_full<F, PC>(rng: &mut StdRng)
+    where
+        F: PrimeField,
+        PC: HomomorphicCommitment<F>,
+    {
+        let n = 16;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = PC::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = PC::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
 
         // precompute
         let (labeled_t_poly, labeled_t_commit) =
-            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+            precompute::<F, GeneralEvaluationDomain<_>, PC>(&ck, n).unwrap();
 
         // generate random balances
         let balances = (0..n)
-            .into_iter()
             .map(|_| rng.gen_range(0..8u64))
             .collect_vec();
 
         // Proof of Balance Sum
         // prove
         let (m, proof, labeled_b_poly) =
-            prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+            prove::<F, GeneralEvaluationDomain<_>, PC, MerlinTranscript, _>(
                 &ck,
                 n,
                 &labeled_t_poly,
@@ -639,6 +1004,7 @@ mod test {
                 &balances,
                 rng,
             ).unwrap();
+        assert_eq!(m, F::from(balances.iter().sum::<u64>()));
         // verify
         let res = proof.verify::<MerlinTranscript>(&cvk, n, &labeled_t_commit, m);
         assert!(res.is_ok());
@@ -647,7 +1013,7 @@ mod test {
         let i = rng.gen_range(0..n);
         let balance = balances[i];
         // open
-        let opening_proof = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+        let opening_proof = individual_open::<F, GeneralEvaluationDomain<_>, PC>(
             &ck,
             n,
             i,
@@ -655,14 +1021,215 @@ mod test {
             &proof.b_commit,
         ).unwrap();
         // verify
-        let res = individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+        let res = individual_verify::<F, GeneralEvaluationDomain<_>, PC>(
             &cvk,
             n,
             i,
-            balance,
+            balance as u128,
+            &proof.b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+
+        // Multiple Checking
+        let indices = vec![0, 3, 7, 8, 12];
+        let opened_balances = indices.iter().map(|&i| balances[i] as u128).collect_vec();
+        // open
+        let opening_proof = multi_open::<F, GeneralEvaluationDomain<_>, PC, MerlinTranscript>(
+            &ck,
+            n,
+            &indices,
+            &labeled_b_poly,
             &proof.b_commit,
+        ).unwrap();
+        // verify
+        let res = multi_verify::<F, GeneralEvaluationDomain<_>, PC, MerlinTranscript>(
+            &cvk,
+            n,
+            &indices,
+            &opened_balances,
+            &proof.b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_update() {
+        use ark_poly_commit::PolynomialCommitment;
+        use crate::srs::lagrange::LagrangeBasis;
+
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+        let basis = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, n).unwrap();
+
+        // precompute
+        let (labeled_t_poly, labeled_t_commit) =
+            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // prove the previous snapshot
+        let mut balances = (0..n)
+            .map(|_| rng.gen_range(0..8u64))
+            .collect_vec();
+        let (_, proof, labeled_b_poly) =
+            prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &labeled_t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+
+        // change some of the balances
+        let indices = vec![1, 6, 11];
+        let updates = indices
+            .iter()
+            .map(|&i| {
+                let old = balances[i];
+                balances[i] = rng.gen_range(0..n as u64);
+                (i, old, balances[i])
+            })
+            .collect_vec();
+        let b_commit = update_commit::<_, KZG10<Bn254>>(&basis, &proof.b_commit, &updates).unwrap();
+        let labeled_b_poly =
+            update_poly::<_, GeneralEvaluationDomain<_>>(n, &labeled_b_poly, &updates).unwrap();
+
+        // the updated B(X) opens to the new balances against the updated commitment
+        let i = indices[1];
+        let opening_proof = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &ck,
+            n,
+            i,
+            &labeled_b_poly,
+            &b_commit,
+        ).unwrap();
+        let res = individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &cvk,
+            n,
+            i,
+            balances[i] as u128,
+            &b_commit,
             &opening_proof,
         );
         assert!(res.is_ok());
+
+        // without blinders, it is the commitment of the new snapshot
+        if !cfg!(feature = "blinding") {
+            let (_, proof, _) =
+                prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                    &ck,
+                    n,
+                    &labeled_t_poly,
+                    &labeled_t_commit,
+                    &balances,
+                    rng,
+                ).unwrap();
+            assert_eq!(b_commit, proof.b_commit);
+            let commit = commit_evals::<_, KZG10<Bn254>>(&basis, &balances).unwrap();
+            assert_eq!(commit, proof.b_commit);
+        } else {
+            assert!(commit_evals::<_, KZG10<Bn254>>(&basis, &balances).is_err());
+        }
+
+        // index out of domain
+        assert!(update_commit::<_, KZG10<Bn254>>(&basis, &b_commit, &[(n, 0, 1)]).is_err());
+        assert!(update_poly::<_, GeneralEvaluationDomain<_>>(n, &labeled_b_poly, &[(n, 0, 1)]).is_err());
+    }
+
+    /// Replay the verifier transcript up to the evaluation challenge `z`.
+    #[cfg(feature = "blinding")]
+    fn challenge_z<T>(
+        n: usize,
+        m: Fr,
+        proof: &Proof<Fr, GeneralEvaluationDomain<Fr>, KZG10<Bn254>>,
+    ) -> Fr
+    where
+        T: TranscriptProtocol<Fr, crate::commitment::KZG10Commitment<Bn254>>,
+    {
+        let transcript = &mut T::new("Proof of Balance Sum");
+        transcript.append_u64("n", n as u64);
+        transcript.append_scalar("m", &m);
+        transcript.append_commitment("b_commit", &proof.b_commit);
+        transcript.append_commitment("s_commit", &proof.s_commit);
+        transcript.append_commitment("h1_commit", &proof.h1_commit);
+        transcript.append_commitment("h2_commit", &proof.h2_commit);
+        transcript.challenge_scalar("gamma");
+        transcript.append_commitment("z_commit", &proof.z_commit);
+        transcript.challenge_scalar("delta");
+        transcript.append_commitment("q1_commit", &proof.q1_commit);
+        transcript.append_commitment("q2_commit", &proof.q2_commit);
+
+        transcript.challenge_scalar("z")
+    }
+
+    #[cfg(feature = "blinding")]
+    #[test]
+    fn test_blinding() {
+        use ark_poly_commit::PolynomialCommitment;
+
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let pp = KZG10::<Bn254>::setup(n + 3, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            n + 3,
+            0,
+            None,
+        ).unwrap();
+
+        // precompute
+        let (labeled_t_poly, labeled_t_commit) =
+            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // generate random balances
+        let balances = (0..n)
+            .map(|_| rng.gen_range(0..8u64))
+            .collect_vec();
+        let domain = GeneralEvaluationDomain::<Fr>::new(n).unwrap();
+        let b_evals = balances.iter().map(|&b| Fr::from(b)).collect_vec();
+        let unblinded_b_poly = poly_from_evals_ref(&domain, &b_evals);
+
+        // prove the same balances twice
+        let mut proofs = Vec::new();
+        for _ in 0..2 {
+            let (m, proof, labeled_b_poly) =
+                prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                    &ck,
+                    n,
+                    &labeled_t_poly,
+                    &labeled_t_commit,
+                    &balances,
+                    rng,
+                ).unwrap();
+            let res = proof.verify::<MerlinTranscript>(&cvk, n, &labeled_t_commit, m);
+            assert!(res.is_ok());
+
+            // B(X) still interpolates the balances over the domain
+            assert_eq!(labeled_b_poly.polynomial().degree(), n + 1);
+            for (point, b) in domain.elements().zip(&b_evals) {
+                assert_eq!(labeled_b_poly.evaluate(&point), *b);
+            }
+            // but its evaluation at z is no longer that of the balances' interpolation
+            let z = challenge_z::<MerlinTranscript>(n, m, &proof);
+            assert_ne!(proof.evaluations.b, unblinded_b_poly.evaluate(&z));
+
+            proofs.push(proof);
+        }
+        // commitments and evaluations are randomised across proofs
+        assert_ne!(proofs[0].b_commit, proofs[1].b_commit);
+        assert_ne!(proofs[0].evaluations.b, proofs[1].evaluations.b);
     }
 }
@@ -0,0 +1,442 @@
+mod proof;
+
+pub use proof::*;
+
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::{PCRandomness, LabeledPolynomial, LabeledCommitment};
+use itertools::Itertools;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::{domain, PosolError},
+    util::{EvaluationDomainExt, poly_from_evals_ref, poly_from_evals},
+    commitment::HomomorphicCommitment,
+    tag,
+    label_polynomial, label_commitment,
+};
+use super::{
+    ColumnPolys,
+    TranscriptProtocol,
+    add_blinders_to_poly,
+    generate_h_evals,
+    generate_s_evals,
+    check_balances,
+    generate_z_evals,
+    linear_poly,
+    quotient_poly,
+    split_quotient_poly,
+};
+
+/// Prove the balance sums of several assets over one user table.
+///
+/// `balances` holds one column per asset, each aligned with `tags`. The tag
+/// vector is committed once and bound to the transcript, so that all the
+/// assets share the same user ordering.
+///
+/// Returns the balance sum of each asset, the proof, the tag polynomial and
+/// the balance polynomial of each asset.
+#[allow(clippy::type_complexity)]
+pub fn prove<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    labeled_t_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: &PC::Commitment,
+    tags: &[&[u8]],
+    balances: &[Vec<u64>],
+    rng: &mut R,
+) -> Result<(
+    Vec<F>,
+    MultiAssetProof<F, D, PC>,
+    LabeledPolynomial<F, DensePolynomial<F>>,
+    Vec<LabeledPolynomial<F, DensePolynomial<F>>>,
+), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    if balances.is_empty() {
+        return Err(PosolError::InputRange("no balance column".into()));
+    }
+    for column in balances {
+        if column.len() != tags.len() {
+            return Err(PosolError::InputRange(format!(
+                "{} balances mismatch {} tags",
+                column.len(),
+                tags.len(),
+            )));
+        }
+        check_balances(n, n, column)?;
+    }
+
+    let domain = domain::<F, D>(n)?;
+
+    // Commit to tag(X) once for all the assets.
+    let (tag_commit, labeled_tag_poly) = tag::commit::<F, D, PC>(ck, n, tags)?;
+
+    let timer = start_timer!(|| "Multi-Asset Balance Sum: Proving");
+
+    let transcript = &mut T::new("Proof of Multi-Asset Balance Sum");
+    transcript.append_u64("n", n as u64);
+    transcript.append_u64("k", balances.len() as u64);
+    transcript.append_commitment("tag_commit", &tag_commit);
+
+    let mut sums = Vec::with_capacity(balances.len());
+    let mut b_evals_columns = Vec::with_capacity(balances.len());
+    let mut h_evals_columns = Vec::with_capacity(balances.len());
+    let mut labeled_b_polys = Vec::with_capacity(balances.len());
+    let mut labeled_s_polys = Vec::with_capacity(balances.len());
+    let mut labeled_h1_polys = Vec::with_capacity(balances.len());
+    let mut labeled_h2_polys = Vec::with_capacity(balances.len());
+    for (k, column) in balances.iter().enumerate() {
+        // Compute balances vector `B`.
+        let mut b_evals = column.iter().map(|&b| F::from(b)).collect_vec();
+        b_evals.resize(n, F::zero());
+
+        // Compute aux vector `S`.
+        let s_evals = generate_s_evals(&b_evals);
+        let m = s_evals[0];
+
+        // Add public input to transcript.
+        transcript.append_scalar("m", &m);
+
+        // Compute polynomials B(X).
+        let mut b_poly = poly_from_evals_ref(&domain, &b_evals);
+        if cfg!(feature = "blinding") {
+            add_blinders_to_poly(rng, n, 2, &mut b_poly);
+        }
+        labeled_b_polys.push(LabeledPolynomial::new(format!("b_poly_{}", k), b_poly, None, None));
+
+        // Compute aux polynomial S(X).
+        let mut s_poly = poly_from_evals(&domain, s_evals);
+        if cfg!(feature = "blinding") {
+            add_blinders_to_poly(rng, n, 3, &mut s_poly);
+        }
+        labeled_s_polys.push(LabeledPolynomial::new(format!("s_poly_{}", k), s_poly, None, None));
+
+        // Compute polynomials h1(X) and h2(X).
+        let (h1_evals, h2_evals) = generate_h_evals(&b_evals, n);
+        let mut h1_poly = poly_from_evals_ref(&domain, &h1_evals);
+        if cfg!(feature = "blinding") {
+            add_blinders_to_poly(rng, n, 3, &mut h1_poly);
+        }
+        labeled_h1_polys.push(LabeledPolynomial::new(format!("h1_poly_{}", k), h1_poly, None, None));
+
+        let mut h2_poly = poly_from_evals_ref(&domain, &h2_evals);
+        if cfg!(feature = "blinding") {
+            add_blinders_to_poly(rng, n, 3, &mut h2_poly);
+        }
+        labeled_h2_polys.push(LabeledPolynomial::new(format!("h2_poly_{}", k), h2_poly, None, None));
+
+        sums.push(m);
+        b_evals_columns.push(b_evals);
+        h_evals_columns.push((h1_evals, h2_evals));
+    }
+
+    // Commit to B(X), S(X), h1(X), h2(X) of all the assets.
+    let (labeled_b_commits, _) = PC::commit(ck, &labeled_b_polys, None)
+        .map_err(|e| PosolError::Commitment(format!("commit to B(X) failed: {}", e)))?;
+    let (labeled_s_commits, _) = PC::commit(ck, &labeled_s_polys, None)
+        .map_err(|e| PosolError::Commitment(format!("commit to S(X) failed: {}", e)))?;
+    let (labeled_h1_commits, _) = PC::commit(ck, &labeled_h1_polys, None)
+        .map_err(|e| PosolError::Commitment(format!("commit to h1(X) failed: {}", e)))?;
+    let (labeled_h2_commits, _) = PC::commit(ck, &labeled_h2_polys, None)
+        .map_err(|e| PosolError::Commitment(format!("commit to h2(X) failed: {}", e)))?;
+
+    // Add commitments to transcript.
+    for k in 0..balances.len() {
+        transcript.append_commitment("b_commit", labeled_b_commits[k].commitment());
+        transcript.append_commitment("s_commit", labeled_s_commits[k].commitment());
+        transcript.append_commitment("h1_commit", labeled_h1_commits[k].commitment());
+        transcript.append_commitment("h2_commit", labeled_h2_commits[k].commitment());
+    }
+
+    // Fiat-Shamir challenge
+    let gamma = transcript.challenge_scalar("gamma");
+
+    // Compute polynomials z(X) of all the assets.
+    let labeled_z_polys = b_evals_columns
+        .into_iter()
+        .zip(h_evals_columns)
+        .enumerate()
+        .map(|(k, (b_evals, (h1_evals, h2_evals)))| {
+            let z_evals = generate_z_evals(gamma, n, &b_evals, &h1_evals, &h2_evals)?;
+            let mut z_poly = poly_from_evals(&domain, z_evals);
+            if cfg!(feature = "blinding") {
+                add_blinders_to_poly(rng, n, 3, &mut z_poly);
+            }
+            Ok(LabeledPolynomial::new(format!("z_poly_{}", k), z_poly, None, None))
+        })
+        .collect::<Result<Vec<_>, PosolError>>()?;
+
+    // Commit to z(X).
+    let (labeled_z_commits, _) = PC::commit(ck, &labeled_z_polys, None)
+        .map_err(|e| PosolError::Commitment(format!("commit to z(X) failed: {}", e)))?;
+
+    // Add commitments to transcript.
+    for labeled_z_commit in labeled_z_commits.iter() {
+        transcript.append_commitment("z_commit", labeled_z_commit.commitment());
+    }
+
+    // Fiat-Shamir challenge
+    let delta = transcript.challenge_scalar("delta");
+
+    let columns = itertools::izip!(
+        sums.iter(),
+        labeled_b_polys.iter(),
+        labeled_s_polys.iter(),
+        labeled_h1_polys.iter(),
+        labeled_h2_polys.iter(),
+        labeled_z_polys.iter(),
+    )
+    .map(|(&m, b, s, h1, h2, z)| ColumnPolys {
+        m,
+        b_poly: b.polynomial(),
+        s_poly: s.polynomial(),
+        h1_poly: h1.polynomial(),
+        h2_poly: h2.polynomial(),
+        z_poly: z.polynomial(),
+    })
+    .collect_vec();
+
+    let extended_domain = quotient_poly::ExtendedDomain::new(&domain, labeled_t_poly.polynomial())?;
+    let q_poly = quotient_poly::compute_batched(
+        &domain,
+        &extended_domain,
+        n,
+        gamma,
+        delta,
+        labeled_t_poly.polynomial(),
+        &columns,
+    )?;
+
+    // Split quotient polynomials.
+    let (q1_poly, q2_poly) = split_quotient_poly(n, &q_poly, rng);
+    drop(q_poly);
+    let labeled_q1_poly = label_polynomial!(q1_poly);
+    let labeled_q2_poly = label_polynomial!(q2_poly);
+
+    // Commit to quotient polynomials.
+    let (labeled_q_commits, _) =
+        PC::commit(ck, vec![&labeled_q1_poly, &labeled_q2_poly], None)
+            .map_err(|e| PosolError::Commitment(format!("commit to q1(X), q2(X) failed: {}", e)))?;
+
+    // Add commitments to transcript.
+    transcript.append_commitment("q1_commit", labeled_q_commits[0].commitment());
+    transcript.append_commitment("q2_commit", labeled_q_commits[1].commitment());
+
+    // Compute evaluation point challenge `z`.
+    let z = transcript.challenge_scalar("z");
+
+    let (r_poly, t_eval, evaluations) = linear_poly::compute_batched(
+        &domain,
+        gamma,
+        delta,
+        z,
+        labeled_t_poly.polynomial(),
+        &columns,
+        labeled_q1_poly.polynomial(),
+        labeled_q2_poly.polynomial(),
+    )?;
+    drop(columns);
+    drop(labeled_q1_poly);
+    drop(labeled_q2_poly);
+    let labeled_r_poly = label_polynomial!(r_poly);
+
+    transcript.append_scalar("t_eval", &t_eval);
+    for evals in evaluations.iter() {
+        transcript.append_scalar("b_eval", &evals.b);
+        transcript.append_scalar("h1_eval", &evals.h1);
+        transcript.append_scalar("h2_eval", &evals.h2);
+        transcript.append_scalar("s_next_eval", &evals.s_next);
+        transcript.append_scalar("h1_next_eval", &evals.h1_next);
+        transcript.append_scalar("h2_next_eval", &evals.h2_next);
+        transcript.append_scalar("z_next_eval", &evals.z_next);
+    }
+
+    // Compute opening point challenge `eta`.
+    let eta = transcript.challenge_scalar("eta");
+
+    // Commit to linear polynomial.
+    let (labeled_r_commit, _) =
+        PC::commit(ck, vec![&labeled_r_poly], None)
+            .map_err(|e| PosolError::Commitment(format!("commit to r(X) failed: {}", e)))?;
+
+    let labeled_t_commit = label_commitment!(t_commit, labeled_t_poly.label());
+    let randomness = <PC::Randomness as PCRandomness>::empty();
+
+    // Compute opening proofs.
+    let w_polys = [&labeled_r_poly, labeled_t_poly]
+        .into_iter()
+        .chain(labeled_b_polys.iter())
+        .chain(labeled_h1_polys.iter())
+        .chain(labeled_h2_polys.iter())
+        .collect_vec();
+    let w_commits = [&labeled_r_commit[0], &labeled_t_commit]
+        .into_iter()
+        .chain(labeled_b_commits.iter())
+        .chain(labeled_h1_commits.iter())
+        .chain(labeled_h2_commits.iter())
+        .collect_vec();
+    let w_opening = PC::open(
+        ck,
+        w_polys,
+        w_commits,
+        &z,
+        eta,
+        vec![&randomness; 2 + 3 * balances.len()],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open W(X) failed: {}", e)))?;
+    drop(labeled_r_poly);
+
+    let sw_polys = labeled_s_polys
+        .iter()
+        .chain(labeled_h1_polys.iter())
+        .chain(labeled_h2_polys.iter())
+        .chain(labeled_z_polys.iter())
+        .collect_vec();
+    let sw_commits = labeled_s_commits
+        .iter()
+        .chain(labeled_h1_commits.iter())
+        .chain(labeled_h2_commits.iter())
+        .chain(labeled_z_commits.iter())
+        .collect_vec();
+    let sw_opening = PC::open(
+        ck,
+        sw_polys,
+        sw_commits,
+        &(z * domain.group_gen()),
+        eta,
+        vec![&randomness; 4 * balances.len()],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open W_next(X) failed: {}", e)))?;
+
+    let commitments = |labeled: &[LabeledCommitment<PC::Commitment>]| {
+        labeled.iter().map(|c| c.commitment().clone()).collect_vec()
+    };
+    let proof = MultiAssetProof {
+        tag_commit,
+        b_commits: commitments(&labeled_b_commits),
+        s_commits: commitments(&labeled_s_commits),
+        h1_commits: commitments(&labeled_h1_commits),
+        h2_commits: commitments(&labeled_h2_commits),
+        z_commits: commitments(&labeled_z_commits),
+        q1_commit: labeled_q_commits[0].commitment().clone(),
+        q2_commit: labeled_q_commits[1].commitment().clone(),
+        w_opening,
+        sw_opening,
+        t_eval,
+        evaluations,
+        _p: core::marker::PhantomData,
+    };
+
+    end_timer!(timer);
+
+    Ok((sums, proof, labeled_tag_poly, labeled_b_polys))
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Bn254, Fr};
+    use itertools::Itertools;
+
+    use crate::{
+        commitment::KZG10,
+        balance_sum::{precompute, individual_open, individual_verify, MerlinTranscript},
+    };
+    use super::*;
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let num_users = 13;
+        let num_assets = 3;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        // precompute
+        let (labeled_t_poly, t_commit) =
+            precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // generate random user table
+        let tags = (0..num_users)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let balances = (0..num_assets)
+            .map(|_| {
+                (0..num_users)
+                    .map(|_| rng.gen_range(0..n as u64))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        // prove
+        let (sums, proof, _, labeled_b_polys) =
+            prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &tags_ref,
+                &balances,
+                rng,
+            ).unwrap();
+        for (sum, column) in sums.iter().zip(balances.iter()) {
+            assert_eq!(sum, &Fr::from(column.iter().sum::<u64>()));
+        }
+
+        // verify
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit, &sums);
+        assert!(res.is_ok());
+
+        // verify with a wrong sum
+        let mut wrong_sums = sums.clone();
+        wrong_sums[1] += Fr::from(1u64);
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &t_commit, &wrong_sums);
+        assert!(res.is_err());
+
+        // Individual Checking
+        let i = rng.gen_range(0..num_users);
+        let k = rng.gen_range(0..num_assets);
+        let opening_proof = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &ck,
+            n,
+            i,
+            &labeled_b_polys[k],
+            &proof.b_commits[k],
+        ).unwrap();
+        let res = individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+            &cvk,
+            n,
+            i,
+            balances[k][i] as u128,
+            &proof.b_commits[k],
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+    }
+}
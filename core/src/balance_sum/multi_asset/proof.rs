@@ -0,0 +1,299 @@
+use core::marker::PhantomData;
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::EvaluationDomain;
+use ark_poly_commit::LabeledCommitment;
+use ark_serialize::*;
+use itertools::Itertools;
+
+use crate::{
+    error::{domain, PosolError},
+    util::{EvaluationDomainExt, compute_lagrange_evaluation},
+    commitment::HomomorphicCommitment,
+    label_commitment,
+};
+use super::super::{
+    TranscriptProtocol,
+    ColumnEvaluations,
+    column_linear_scalars,
+    column_r_eval,
+    delta_powers,
+    quotient_linear_scalars,
+};
+
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct MultiAssetProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    pub tag_commit: PC::Commitment,
+
+    pub b_commits: Vec<PC::Commitment>,
+    pub s_commits: Vec<PC::Commitment>,
+    pub h1_commits: Vec<PC::Commitment>,
+    pub h2_commits: Vec<PC::Commitment>,
+    pub z_commits: Vec<PC::Commitment>,
+    pub q1_commit: PC::Commitment,
+    pub q2_commit: PC::Commitment,
+
+    pub w_opening: PC::Proof,
+    pub sw_opening: PC::Proof,
+
+    pub t_eval: F,
+    pub evaluations: Vec<ColumnEvaluations<F>>,
+
+    pub(super) _p: PhantomData<D>,
+}
+
+impl<F, D, PC> MultiAssetProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Number of assets covered by the proof.
+    pub fn num_assets(&self) -> usize {
+        self.b_commits.len()
+    }
+
+    fn compute_r_eval(
+        &self,
+        n: u64,
+        gamma: F,
+        deltas: &[F],
+        l0_eval: F,
+        ln_eval: F,
+        sums: &[F],
+    ) -> F {
+        let delta_exp_8 = deltas[6] * deltas[0];
+        let mut factor = F::one();
+        let mut r_eval = F::zero();
+        for (evals, &m) in self.evaluations.iter().zip(sums) {
+            r_eval += factor * column_r_eval(n, gamma, deltas, l0_eval, ln_eval, m, evals);
+            factor *= delta_exp_8;
+        }
+
+        r_eval
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn linearisation_commitments(
+        &self,
+        gamma: F,
+        deltas: &[F],
+        z: F,
+        zh_eval: F,
+        l0_eval: F,
+        ln_eval: F,
+    ) -> PC::Commitment {
+        let num_assets = self.num_assets();
+        let mut scalars = Vec::with_capacity(5 * num_assets + 2);
+        let mut commitments = Vec::with_capacity(5 * num_assets + 2);
+
+        let delta_exp_8 = deltas[6] * deltas[0];
+        let mut factor = F::one();
+        for k in 0..num_assets {
+            let column_scalars = column_linear_scalars(
+                gamma,
+                deltas,
+                self.t_eval,
+                l0_eval,
+                ln_eval,
+                &self.evaluations[k],
+            );
+            scalars.extend(column_scalars.iter().map(|&scalar| scalar * factor));
+            commitments.push(self.s_commits[k].clone());
+            commitments.push(self.b_commits[k].clone());
+            commitments.push(self.z_commits[k].clone());
+            commitments.push(self.h1_commits[k].clone());
+            commitments.push(self.h2_commits[k].clone());
+
+            factor *= delta_exp_8;
+        }
+
+        scalars.extend(quotient_linear_scalars(z, zh_eval));
+        commitments.push(self.q1_commit.clone());
+        commitments.push(self.q2_commit.clone());
+
+        PC::multi_scalar_mul(&commitments, &scalars)
+    }
+
+    /// Verify the balance sum of every asset in one pass.
+    pub fn verify<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        n: usize,
+        t_commit: &PC::Commitment,
+        sums: &[F],
+    ) -> Result<(), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+
+        let num_assets = self.num_assets();
+        if num_assets == 0 {
+            return Err(PosolError::Verification("no asset provided".into()));
+        }
+        if sums.len() != num_assets
+            || self.s_commits.len() != num_assets
+            || self.h1_commits.len() != num_assets
+            || self.h2_commits.len() != num_assets
+            || self.z_commits.len() != num_assets
+            || self.evaluations.len() != num_assets
+        {
+            return Err(PosolError::Verification("number of assets mismatches".into()));
+        }
+
+        let domain = domain::<F, D>(n)?;
+
+        let timer = start_timer!(|| "Multi-Asset Balance Sum: Verifying");
+
+        let transcript = &mut T::new("Proof of Multi-Asset Balance Sum");
+        transcript.append_u64("n", n as u64);
+        transcript.append_u64("k", num_assets as u64);
+        transcript.append_commitment("tag_commit", &self.tag_commit);
+
+        // Append sums to the transcript.
+        for m in sums {
+            transcript.append_scalar("m", m);
+        }
+
+        for k in 0..num_assets {
+            transcript.append_commitment("b_commit", &self.b_commits[k]);
+            transcript.append_commitment("s_commit", &self.s_commits[k]);
+            transcript.append_commitment("h1_commit", &self.h1_commits[k]);
+            transcript.append_commitment("h2_commit", &self.h2_commits[k]);
+        }
+
+        let gamma = transcript.challenge_scalar("gamma");
+
+        for z_commit in self.z_commits.iter() {
+            transcript.append_commitment("z_commit", z_commit);
+        }
+
+        let delta = transcript.challenge_scalar("delta");
+
+        transcript.append_commitment("q1_commit", &self.q1_commit);
+        transcript.append_commitment("q2_commit", &self.q2_commit);
+
+        // Compute evaluation point challenge `z`.
+        let z = transcript.challenge_scalar("z");
+
+        // Compute zero polynomial evaluated at `z`
+        let zh_eval = domain.evaluate_vanishing_polynomial(z);
+        let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z)
+            .ok_or_else(|| PosolError::Verification("challenge z is in the domain".into()))?;
+        let ln_eval = compute_lagrange_evaluation(n, domain.element(n - 1), zh_eval, z)
+            .ok_or_else(|| PosolError::Verification("challenge z is in the domain".into()))?;
+
+        let deltas = delta_powers(delta);
+
+        let r_eval = self.compute_r_eval(
+            n as u64,
+            gamma,
+            &deltas,
+            l0_eval,
+            ln_eval,
+            sums,
+        );
+
+        let r_commit = self.linearisation_commitments(
+            gamma,
+            &deltas,
+            z,
+            zh_eval,
+            l0_eval,
+            ln_eval,
+        );
+
+        transcript.append_scalar("t_eval", &self.t_eval);
+        for evals in self.evaluations.iter() {
+            transcript.append_scalar("b_eval", &evals.b);
+            transcript.append_scalar("h1_eval", &evals.h1);
+            transcript.append_scalar("h2_eval", &evals.h2);
+            transcript.append_scalar("s_next_eval", &evals.s_next);
+            transcript.append_scalar("h1_next_eval", &evals.h1_next);
+            transcript.append_scalar("h2_next_eval", &evals.h2_next);
+            transcript.append_scalar("z_next_eval", &evals.z_next);
+        }
+
+        // Compute opening point challenge `eta`.
+        let eta = transcript.challenge_scalar("eta");
+
+        let labeled = |commits: &[PC::Commitment]| {
+            commits.iter().map(|c| label_commitment!(c)).collect_vec()
+        };
+        let labeled_r_commit = label_commitment!(r_commit);
+        let labeled_t_commit = label_commitment!(t_commit);
+        let labeled_b_commits = labeled(&self.b_commits);
+        let labeled_s_commits = labeled(&self.s_commits);
+        let labeled_h1_commits = labeled(&self.h1_commits);
+        let labeled_h2_commits = labeled(&self.h2_commits);
+        let labeled_z_commits = labeled(&self.z_commits);
+
+        let w_commits: Vec<&LabeledCommitment<PC::Commitment>> = [&labeled_r_commit, &labeled_t_commit]
+            .into_iter()
+            .chain(labeled_b_commits.iter())
+            .chain(labeled_h1_commits.iter())
+            .chain(labeled_h2_commits.iter())
+            .collect();
+        let w_evals = [r_eval, self.t_eval]
+            .into_iter()
+            .chain(self.evaluations.iter().map(|e| e.b))
+            .chain(self.evaluations.iter().map(|e| e.h1))
+            .chain(self.evaluations.iter().map(|e| e.h2))
+            .collect_vec();
+
+        match PC::check(
+            cvk,
+            w_commits,
+            &z,
+            w_evals,
+            &self.w_opening,
+            eta,
+            None,
+        ) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PosolError::Verification("verification of w opening failed".into())),
+            Err(e) => Err(PosolError::Verification(format!("check opening W(X) error: {}", e))),
+        }
+        .and_then(|_| {
+            let sw_commits = labeled_s_commits
+                .iter()
+                .chain(labeled_h1_commits.iter())
+                .chain(labeled_h2_commits.iter())
+                .chain(labeled_z_commits.iter())
+                .collect_vec();
+            let sw_evals = self.evaluations.iter().map(|e| e.s_next)
+                .chain(self.evaluations.iter().map(|e| e.h1_next))
+                .chain(self.evaluations.iter().map(|e| e.h2_next))
+                .chain(self.evaluations.iter().map(|e| e.z_next))
+                .collect_vec();
+
+            match PC::check(
+                cvk,
+                sw_commits,
+                &(z * domain.group_gen()),
+                sw_evals,
+                &self.sw_opening,
+                eta,
+                None,
+            ) {
+                Ok(true) => {
+                    end_timer!(timer);
+                    Ok(())
+                }
+                Ok(false) => Err(PosolError::Verification("verification of sw opening failed".into())),
+                Err(e) => Err(PosolError::Verification(format!("check opening W_next(X) error: {}", e))),
+            }
+        })
+    }
+}
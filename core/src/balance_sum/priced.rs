@@ -0,0 +1,494 @@
+//! Price-weighted sum of several committed balance columns.
+//!
+//! Given the public price `p_k` of each asset, e.g. in USD, and the
+//! commitments `[B_k]` to the balance columns of the assets, the verifier
+//! derives the commitment to the priced column
+//!
+//! ```text
+//! P(X) = sum_k p_k B_k(X)
+//! ```
+//!
+//! with [`HomomorphicCommitment::multi_scalar_mul`], and the prover shows that
+//! the evaluations of `P(X)` over the domain sum up to the public total with an
+//! accumulator `S(X)` satisfying
+//!
+//! ```text
+//! S(gX) - S(X) - P(X) + m L_0(X) = Z_H(X) Q(X)
+//! ```
+//!
+//! The per-asset sums are not revealed, only the priced total `m`. The range of
+//! the balances is proven by the balance sum proofs of the columns. The prices
+//! are bound into the transcript, so that the proof does not hold under any
+//! other prices.
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial, UVPolynomial};
+use ark_poly_commit::{PCRandomness, LabeledPolynomial};
+use ark_serialize::*;
+use itertools::Itertools;
+use num_traits::Zero;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::{domain, PosolError},
+    util::{EvaluationDomainExt, compute_lagrange_evaluation, poly_from_evals},
+    commitment::HomomorphicCommitment,
+    label_polynomial, label_commitment,
+};
+use super::{add_blinders_to_poly, generate_s_evals, TranscriptProtocol};
+
+#[derive(Debug, Clone, Eq, PartialEq, CanonicalDeserialize, CanonicalSerialize)]
+pub struct PricedEvaluations<F: FftField> {
+    pub p: F,
+    pub s: F,
+    pub q: F,
+
+    pub s_next: F,
+}
+
+/// Proof that the priced balance columns sum up to a public total.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct PricedProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    pub s_commit: PC::Commitment,
+    pub q_commit: PC::Commitment,
+
+    pub w_opening: PC::Proof,
+    pub sw_opening: PC::Proof,
+
+    pub evaluations: PricedEvaluations<F>,
+
+    pub(super) _p: core::marker::PhantomData<D>,
+}
+
+impl<F, D, PC> PricedProof<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Verify that the balance columns committed in `b_commits`, weighted by
+    /// `prices`, sum up to `total`.
+    pub fn verify<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        n: usize,
+        prices: &[u64],
+        b_commits: &[PC::Commitment],
+        total: F,
+    ) -> Result<(), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        if prices.is_empty() || prices.len() != b_commits.len() {
+            return Err(PosolError::Verification(format!(
+                "{} prices for {} balance commitments",
+                prices.len(),
+                b_commits.len(),
+            )));
+        }
+
+        let domain = domain::<F, D>(n)?;
+
+        let timer = start_timer!(|| "Priced Balance Sum: Verifying");
+
+        let transcript = &mut T::new("Proof of Priced Balance Sum");
+        append_public_inputs::<F, PC, T>(transcript, n, prices, b_commits, &total);
+
+        transcript.append_commitment("s_commit", &self.s_commit);
+        transcript.append_commitment("q_commit", &self.q_commit);
+
+        // Compute evaluation point challenge `z`.
+        let z = transcript.challenge_scalar("z");
+
+        // S(gz) - S(z) - P(z) + m L_0(z) = Z_H(z) Q(z)
+        let zh_eval = domain.evaluate_vanishing_polynomial(z);
+        let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z)
+            .ok_or_else(|| PosolError::Verification("challenge z is in the domain".into()))?;
+        let evals = &self.evaluations;
+        if evals.s_next - evals.s - evals.p + total * l0_eval != zh_eval * evals.q {
+            return Err(PosolError::Verification("priced sum constraint not satisfied".into()));
+        }
+
+        append_evaluations(transcript, evals);
+
+        // Compute opening point challenge `eta`.
+        let eta = transcript.challenge_scalar("eta");
+
+        let p_commit = priced_commit::<F, PC>(prices, b_commits);
+        let labeled_p_commit = label_commitment!(p_commit, "p_poly");
+        let labeled_s_commit = label_commitment!(self.s_commit, "s_poly");
+        let labeled_q_commit = label_commitment!(self.q_commit, "q_poly");
+
+        match PC::check(
+            cvk,
+            vec![&labeled_p_commit, &labeled_s_commit, &labeled_q_commit],
+            &z,
+            vec![evals.p, evals.s, evals.q],
+            &self.w_opening,
+            eta,
+            None,
+        ) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PosolError::Verification("verification of w opening failed".into())),
+            Err(e) => Err(PosolError::Verification(format!("check opening W(X) error: {}", e))),
+        }
+        .and_then(|_| {
+            match PC::check(
+                cvk,
+                vec![&labeled_s_commit],
+                &(z * domain.group_gen()),
+                vec![evals.s_next],
+                &self.sw_opening,
+                eta,
+                None,
+            ) {
+                Ok(true) => {
+                    end_timer!(timer);
+                    Ok(())
+                }
+                Ok(false) => Err(PosolError::Verification("verification of sw opening failed".into())),
+                Err(e) => Err(PosolError::Verification(format!("check opening W_next(X) error: {}", e))),
+            }
+        })
+    }
+}
+
+/// Commitment to the priced column `sum_k p_k B_k(X)`.
+pub fn priced_commit<F, PC>(prices: &[u64], b_commits: &[PC::Commitment]) -> PC::Commitment
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    let scalars = prices.iter().map(|&p| F::from(p)).collect_vec();
+
+    PC::multi_scalar_mul(b_commits, &scalars)
+}
+
+/// Prove that the balance columns `B_k(X)` committed in `b_commits`, weighted
+/// by `prices`, sum up to the returned total. The balance polynomials are
+/// those returned by the balance sum proofs of the columns over a domain of
+/// size `n`.
+///
+/// Returns the priced total and the proof.
+pub fn prove<F, D, PC, T, R>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    prices: &[u64],
+    labeled_b_polys: &[&LabeledPolynomial<F, DensePolynomial<F>>],
+    b_commits: &[PC::Commitment],
+    rng: &mut R,
+) -> Result<(F, PricedProof<F, D, PC>), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+    R: CryptoRng + RngCore,
+{
+    if prices.is_empty() || prices.len() != labeled_b_polys.len() || prices.len() != b_commits.len() {
+        return Err(PosolError::InputRange(format!(
+            "{} prices for {} balance polynomials and {} commitments",
+            prices.len(),
+            labeled_b_polys.len(),
+            b_commits.len(),
+        )));
+    }
+
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Priced Balance Sum: Proving");
+
+    // Compute the priced polynomial P(X).
+    let mut p_poly = DensePolynomial::zero();
+    for (&price, labeled_b_poly) in prices.iter().zip(labeled_b_polys) {
+        p_poly += (F::from(price), labeled_b_poly.polynomial());
+    }
+
+    // Compute aux vector `S` over the evaluations of P(X).
+    let p_evals = evals_over_domain(&domain, &p_poly);
+    let s_evals = generate_s_evals(&p_evals);
+    let m = s_evals[0];
+    drop(p_evals);
+
+    let transcript = &mut T::new("Proof of Priced Balance Sum");
+    append_public_inputs::<F, PC, T>(transcript, n, prices, b_commits, &m);
+
+    // Compute aux polynomial S(X).
+    let mut s_poly = poly_from_evals(&domain, s_evals);
+    if cfg!(feature = "blinding") {
+        add_blinders_to_poly(rng, n, 3, &mut s_poly);
+    }
+
+    // Compute quotient polynomial Q(X).
+    let q_poly = quotient_poly(&domain, m, &p_poly, &s_poly)?;
+
+    let labeled_p_poly = label_polynomial!(p_poly);
+    let labeled_s_poly = label_polynomial!(s_poly);
+    let labeled_q_poly = label_polynomial!(q_poly);
+
+    // Commit to S(X) and Q(X).
+    let (labeled_sq_commits, _) =
+        PC::commit(ck, vec![&labeled_s_poly, &labeled_q_poly], None)
+            .map_err(|e| PosolError::Commitment(format!("commit to S(X), Q(X) failed: {}", e)))?;
+
+    // Add commitments to transcript.
+    transcript.append_commitment("s_commit", labeled_sq_commits[0].commitment());
+    transcript.append_commitment("q_commit", labeled_sq_commits[1].commitment());
+
+    // Compute evaluation point challenge `z`.
+    let z = transcript.challenge_scalar("z");
+    let z_next = z * domain.group_gen();
+
+    let evaluations = PricedEvaluations {
+        p: labeled_p_poly.evaluate(&z),
+        s: labeled_s_poly.evaluate(&z),
+        q: labeled_q_poly.evaluate(&z),
+        s_next: labeled_s_poly.evaluate(&z_next),
+    };
+    append_evaluations(transcript, &evaluations);
+
+    // Compute opening point challenge `eta`.
+    let eta = transcript.challenge_scalar("eta");
+
+    let p_commit = priced_commit::<F, PC>(prices, b_commits);
+    let labeled_p_commit = label_commitment!(p_commit, labeled_p_poly.label());
+    let randomness = <PC::Randomness as PCRandomness>::empty();
+    // Compute opening proofs.
+    let w_opening = PC::open(
+        ck,
+        vec![&labeled_p_poly, &labeled_s_poly, &labeled_q_poly],
+        vec![&labeled_p_commit, &labeled_sq_commits[0], &labeled_sq_commits[1]],
+        &z,
+        eta,
+        vec![&randomness, &randomness, &randomness],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open W(X) failed: {}", e)))?;
+
+    let sw_opening = PC::open(
+        ck,
+        vec![&labeled_s_poly],
+        vec![&labeled_sq_commits[0]],
+        &z_next,
+        eta,
+        vec![&randomness],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open W_next(X) failed: {}", e)))?;
+
+    let proof = PricedProof {
+        s_commit: labeled_sq_commits[0].commitment().clone(),
+        q_commit: labeled_sq_commits[1].commitment().clone(),
+        w_opening,
+        sw_opening,
+        evaluations,
+        _p: core::marker::PhantomData,
+    };
+
+    end_timer!(timer);
+
+    Ok((m, proof))
+}
+
+/// Add the domain size, the prices, the commitments to the balance columns and
+/// the priced total to the transcript.
+fn append_public_inputs<F, PC, T>(
+    transcript: &mut T,
+    n: usize,
+    prices: &[u64],
+    b_commits: &[PC::Commitment],
+    total: &F,
+)
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    transcript.append_u64("n", n as u64);
+    transcript.append_u64("k", prices.len() as u64);
+    for (&price, b_commit) in prices.iter().zip(b_commits) {
+        transcript.append_u64("price", price);
+        transcript.append_commitment("b_commit", b_commit);
+    }
+    transcript.append_scalar("m", total);
+}
+
+fn append_evaluations<F, C, T>(transcript: &mut T, evaluations: &PricedEvaluations<F>)
+where
+    F: FftField,
+    C: ark_poly_commit::PCCommitment + 'static,
+    T: TranscriptProtocol<F, C>,
+{
+    transcript.append_scalar("p_eval", &evaluations.p);
+    transcript.append_scalar("s_eval", &evaluations.s);
+    transcript.append_scalar("q_eval", &evaluations.q);
+    transcript.append_scalar("s_next_eval", &evaluations.s_next);
+}
+
+/// Evaluations of `poly` over the domain, reducing it modulo `X^n - 1` first
+/// as blinded polynomials exceed the domain size.
+fn evals_over_domain<F, D>(domain: &D, poly: &DensePolynomial<F>) -> Vec<F>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let n = domain.size();
+    let mut coeffs = vec![F::zero(); n];
+    for (i, coeff) in poly.coeffs.iter().enumerate() {
+        coeffs[i % n] += coeff;
+    }
+
+    domain.fft(&coeffs)
+}
+
+/// Compute `Q(X) = (S(gX) - S(X) - P(X) + m L_0(X)) / Z_H(X)`.
+fn quotient_poly<F, D>(
+    domain: &D,
+    m: F,
+    p_poly: &DensePolynomial<F>,
+    s_poly: &DensePolynomial<F>,
+) -> Result<DensePolynomial<F>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+{
+    let n = domain.size();
+    let g = domain.group_gen();
+
+    // S(gX)
+    let mut power = F::one();
+    let s_next_coeffs = s_poly.coeffs
+        .iter()
+        .map(|&coeff| {
+            let shifted = coeff * power;
+            power *= g;
+            shifted
+        })
+        .collect_vec();
+    let mut numerator = DensePolynomial::from_coefficients_vec(s_next_coeffs);
+    numerator -= s_poly;
+    numerator -= p_poly;
+
+    // m L_0(X)
+    let mut l0_evals = vec![F::zero(); n];
+    l0_evals[0] = m;
+    numerator += &poly_from_evals(domain, l0_evals);
+
+    let (q_poly, r_poly) = numerator
+        .divide_by_vanishing_poly(*domain)
+        .ok_or_else(|| PosolError::Commitment("division by Z_H(X) failed".into()))?;
+    if !r_poly.is_zero() {
+        return Err(PosolError::Commitment("priced sum constraint does not vanish over the domain".into()));
+    }
+
+    Ok(q_poly)
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Bn254, Fr};
+
+    use crate::{
+        commitment::KZG10,
+        balance_sum::{precompute, multi_asset, MerlinTranscript},
+    };
+    use super::*;
+
+    type Domain = GeneralEvaluationDomain<Fr>;
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let num_users = 13;
+        let prices = [1u64, 3_000, 65_000];
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(&pp, max_degree, 0, None).unwrap();
+
+        // precompute
+        let (labeled_t_poly, t_commit) =
+            precompute::<_, Domain, KZG10<Bn254>>(&ck, n).unwrap();
+
+        // prove the balance columns of each asset
+        let tags = (0..num_users)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let columns = prices
+            .iter()
+            .map(|_| (0..num_users).map(|_| rng.gen_range(0..n as u64)).collect_vec())
+            .collect_vec();
+        let (sums, assets_proof, _, labeled_b_polys) =
+            multi_asset::prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &tags_ref,
+                &columns,
+                rng,
+            ).unwrap();
+
+        // prove the priced total
+        let labeled_b_polys = labeled_b_polys.iter().collect_vec();
+        let b_commits = &assets_proof.b_commits;
+        let (total, proof) = prove::<_, Domain, KZG10<Bn254>, MerlinTranscript, _>(
+            &ck,
+            n,
+            &prices,
+            &labeled_b_polys,
+            b_commits,
+            rng,
+        ).unwrap();
+        let expected = columns
+            .iter()
+            .zip(prices)
+            .map(|(column, p)| column.iter().map(|&b| b as u128 * p as u128).sum::<u128>())
+            .sum::<u128>();
+        assert_eq!(total, Fr::from(expected));
+        let priced_sums = sums.iter().zip(prices).map(|(&m, p)| m * Fr::from(p)).sum::<Fr>();
+        assert_eq!(total, priced_sums);
+
+        // verify
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &prices, b_commits, total);
+        assert!(res.is_ok());
+
+        // another total
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &prices, b_commits, total + Fr::from(1u64));
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // other prices
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &[1, 3_000, 65_001], b_commits, total);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // prices out of order
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &[3_000, 1, 65_000], b_commits, total);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+
+        // missing price
+        let res = proof.verify::<MerlinTranscript>(&cvk, n, &prices[..2], b_commits, total);
+        assert!(matches!(res, Err(PosolError::Verification(_))));
+    }
+}
@@ -3,9 +3,9 @@ use ark_std::{start_timer, end_timer};
 use ark_ff::FftField;
 use ark_poly::EvaluationDomain;
 use ark_serialize::*;
-use anyhow::{anyhow, Result};
 
 use crate::{
+    error::{domain, PosolError},
     util::{EvaluationDomainExt, compute_lagrange_evaluation},
     commitment::HomomorphicCommitment,
     label_commitment,
@@ -54,6 +54,105 @@ where
     pub(super) _p: PhantomData<D>,
 }
 
+impl<F: FftField> Evaluations<F> {
+    /// Split into the evaluation of t(X) and the evaluations of the balance column.
+    pub(crate) fn split(&self) -> (F, ColumnEvaluations<F>) {
+        (
+            self.t,
+            ColumnEvaluations {
+                b: self.b,
+                h1: self.h1,
+                h2: self.h2,
+                s_next: self.s_next,
+                z_next: self.z_next,
+                h1_next: self.h1_next,
+                h2_next: self.h2_next,
+            },
+        )
+    }
+}
+
+/// Evaluations of the polynomials of a single balance column.
+#[derive(Debug, Clone, Eq, PartialEq, CanonicalDeserialize, CanonicalSerialize)]
+pub struct ColumnEvaluations<F: FftField> {
+    pub b: F,
+    pub h1: F,
+    pub h2: F,
+
+    pub s_next: F,
+    pub z_next: F,
+    pub h1_next: F,
+    pub h2_next: F,
+}
+
+/// Compute `[delta, delta^2, ..., delta^7]`.
+pub(crate) fn delta_powers<F: FftField>(delta: F) -> [F; 7] {
+    let mut deltas = [delta; 7];
+    for i in 1..7 {
+        deltas[i] = deltas[i - 1] * delta;
+    }
+
+    deltas
+}
+
+/// Compute the evaluation of the linearisation polynomial of a balance column at `z`.
+pub(crate) fn column_r_eval<F: FftField>(
+    table_size: u64,
+    gamma: F,
+    deltas: &[F],
+    l0_eval: F,
+    ln_eval: F,
+    m: F,
+    evals: &ColumnEvaluations<F>,
+) -> F {
+    - evals.s_next - m * l0_eval
+    + evals.z_next * (gamma + evals.h1) * gamma * deltas[0]
+    + l0_eval * deltas[1]
+    - evals.h1_next
+        * (evals.h1_next - evals.h1 - F::one())
+        * (ln_eval - F::one())
+        * deltas[2]
+    - evals.h2_next
+        * (evals.h2_next - evals.h2 - F::one())
+        * (ln_eval - F::one())
+        * deltas[3]
+    - evals.h2_next
+        * (evals.h2_next - evals.h1 - F::one())
+        * ln_eval
+        * deltas[4]
+    + F::from(table_size - 1) * ln_eval * deltas[6]
+}
+
+/// Compute the scalars of S(X), B(X), z(X), h1(X), h2(X) of a balance column
+/// in the linearisation polynomial, in that order.
+pub(crate) fn column_linear_scalars<F: FftField>(
+    gamma: F,
+    deltas: &[F],
+    t_eval: F,
+    l0_eval: F,
+    ln_eval: F,
+    evals: &ColumnEvaluations<F>,
+) -> [F; 5] {
+    let z_scalar = (gamma + evals.b) * (gamma + t_eval) * deltas[0] + l0_eval * deltas[1];
+    let h1_scalar = l0_eval * deltas[5]
+        - (evals.h1_next - evals.h1 - F::one()) * (ln_eval - F::one()) * deltas[2]
+        - (evals.h2_next - evals.h1 - F::one()) * ln_eval * deltas[4];
+    let h2_scalar = ln_eval * deltas[6]
+        - evals.z_next * (gamma + evals.h1) * deltas[0]
+        - (evals.h2_next - evals.h2 - F::one()) * (ln_eval - F::one()) * deltas[3];
+
+    [-F::one(), -F::one(), z_scalar, h1_scalar, h2_scalar]
+}
+
+/// Compute the scalars of q1(X), q2(X) in the linearisation polynomial.
+pub(crate) fn quotient_linear_scalars<F: FftField>(z: F, zh_eval: F) -> [F; 2] {
+    if cfg!(feature = "blinding") {
+        [-zh_eval, -zh_eval * (zh_eval + F::one()) * z.square() * z]
+    } else {
+        [-zh_eval, -zh_eval * (zh_eval + F::one())]
+    }
+}
+
 impl<F, D, PC> Proof<F, D, PC>
 where
     F: FftField,
@@ -62,29 +161,15 @@ where
 {
     fn compute_r_eval(
         &self,
-        n: u64,
+        table_size: u64,
         gamma: F,
         deltas: &[F],
         l0_eval: F,
         ln_eval: F,
         m: F,
     ) -> F {
-        - self.evaluations.s_next - m * l0_eval
-        + self.evaluations.z_next * (gamma + self.evaluations.h1) * gamma * deltas[0]
-        + l0_eval * deltas[1]
-        - self.evaluations.h1_next
-            * (self.evaluations.h1_next - self.evaluations.h1 - F::one())
-            * (ln_eval - F::one())
-            * deltas[2]
-        - self.evaluations.h2_next
-            * (self.evaluations.h2_next - self.evaluations.h2 - F::one())
-            * (ln_eval - F::one())
-            * deltas[3]
-        - self.evaluations.h2_next
-            * (self.evaluations.h2_next - self.evaluations.h1 - F::one())
-            * ln_eval
-            * deltas[4]
-        + F::from(n - 1) * ln_eval * deltas[6]
+        let (_, column) = self.evaluations.split();
+        column_r_eval(table_size, gamma, deltas, l0_eval, ln_eval, m, &column)
     }
 
     fn linearisation_commitments(
@@ -96,43 +181,19 @@ where
         l0_eval: F,
         ln_eval: F,
     ) -> PC::Commitment {
-        let mut scalars = Vec::with_capacity(7);
-        let mut commitments = Vec::with_capacity(7);
-
-        scalars.push(-F::one());
-        commitments.push(self.s_commit.clone());
-        
-        scalars.push(-F::one());
-        commitments.push(self.b_commit.clone());
-        
-        scalars.push(
-            (gamma + self.evaluations.b) * (gamma + self.evaluations.t) * deltas[0] + l0_eval * deltas[1]
-        );
-        commitments.push(self.z_commit.clone());
-
-        scalars.push(
-            l0_eval * deltas[5]
-            - (self.evaluations.h1_next - self.evaluations.h1 - F::one()) * (ln_eval - F::one()) * deltas[2]
-            - (self.evaluations.h2_next - self.evaluations.h1 - F::one()) * ln_eval * deltas[4]
-        );
-        commitments.push(self.h1_commit.clone());
-
-        scalars.push(
-            ln_eval * deltas[6]
-            - self.evaluations.z_next * (gamma + self.evaluations.h1) * deltas[0]
-            - (self.evaluations.h2_next - self.evaluations.h2 - F::one()) * (ln_eval - F::one()) * deltas[3]
-        );
-        commitments.push(self.h2_commit.clone());
-
-        scalars.push(-zh_eval);
-        commitments.push(self.q1_commit.clone());
-
-        if cfg!(blinding) {
-            scalars.push(-zh_eval * (zh_eval + F::one()) * z.square() * z);
-        } else {
-            scalars.push(-zh_eval * (zh_eval + F::one()));
-        }
-        commitments.push(self.q2_commit.clone());
+        let (t_eval, column) = self.evaluations.split();
+        let mut scalars = column_linear_scalars(gamma, deltas, t_eval, l0_eval, ln_eval, &column).to_vec();
+        scalars.extend(quotient_linear_scalars(z, zh_eval));
+
+        let commitments = vec![
+            self.s_commit.clone(),
+            self.b_commit.clone(),
+            self.z_commit.clone(),
+            self.h1_commit.clone(),
+            self.h2_commit.clone(),
+            self.q1_commit.clone(),
+            self.q2_commit.clone(),
+        ];
 
         PC::multi_scalar_mul(&commitments, &scalars)
     }
@@ -143,19 +204,29 @@ where
         n: usize,
         t_commit: &PC::Commitment,
         m: F,
-    ) -> Result<()>
+    ) -> Result<(), PosolError>
     where
         T: TranscriptProtocol<F, PC::Commitment>,
     {
-        assert!(n.is_power_of_two());
+        let domain = domain::<F, D>(n)?;
+
+        self.verify_in::<T>(cvk, &domain, n, t_commit, m)
+    }
 
-        let domain = D::new(n)
-            .ok_or(anyhow!(
-                "log size of group: {}, 2-adicity: {}",
-                n.trailing_zeros(),
-                <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-            ))?;
-        assert_eq!(n, domain.size());
+    /// Verify over a domain computed beforehand, with the range table
+    /// `{0, ..., table_size - 1}` committed to in `t_commit`.
+    pub(super) fn verify_in<T>(
+        &self,
+        cvk: &PC::VerifierKey,
+        domain: &D,
+        table_size: usize,
+        t_commit: &PC::Commitment,
+        m: F,
+    ) -> Result<(), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        let n = domain.size();
 
         let timer = start_timer!(|| "Balance Sum: Verifying");
 
@@ -184,19 +255,15 @@ where
 
         // Compute zero polynomial evaluated at `z`
         let zh_eval = domain.evaluate_vanishing_polynomial(z);
-        let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z);
-        let ln_eval = compute_lagrange_evaluation(n, domain.element(n - 1), zh_eval, z);
+        let l0_eval = compute_lagrange_evaluation(n, domain.element(0), zh_eval, z)
+            .ok_or_else(|| PosolError::Verification("challenge z is in the domain".into()))?;
+        let ln_eval = compute_lagrange_evaluation(n, domain.element(n - 1), zh_eval, z)
+            .ok_or_else(|| PosolError::Verification("challenge z is in the domain".into()))?;
 
-        let delta_exp_2 = delta.square();
-        let delta_exp_3 = delta_exp_2 * delta;
-        let delta_exp_4 = delta_exp_3 * delta;
-        let delta_exp_5 = delta_exp_4 * delta;
-        let delta_exp_6 = delta_exp_5 * delta;
-        let delta_exp_7 = delta_exp_6 * delta;
-        let deltas = [delta, delta_exp_2, delta_exp_3, delta_exp_4, delta_exp_5, delta_exp_6, delta_exp_7];
+        let deltas = delta_powers(delta);
 
         let r_eval = self.compute_r_eval(
-            n as u64,
+            table_size as u64,
             gamma,
             &deltas,
             l0_eval,
@@ -253,8 +320,8 @@ where
             None,
         ) {
             Ok(true) => Ok(()),
-            Ok(false) => Err(anyhow!("verification of w opening failed")),
-            Err(e) => Err(anyhow!("check opening W(X) error: {}", e)),
+            Ok(false) => Err(PosolError::Verification("verification of w opening failed".into())),
+            Err(e) => Err(PosolError::Verification(format!("check opening W(X) error: {}", e))),
         }
         .and_then(|_| {
             let labeled_s_commit = label_commitment!(self.s_commit);
@@ -283,8 +350,8 @@ where
                     end_timer!(timer);
                     Ok(())
                 }
-                Ok(false) => Err(anyhow!("verification of sw opening failed")),
-                Err(e) => Err(anyhow!("check opening W_next(X) error: {}", e)),
+                Ok(false) => Err(PosolError::Verification("verification of sw opening failed".into())),
+                Err(e) => Err(PosolError::Verification(format!("check opening W_next(X) error: {}", e))),
             }
         })
     }
@@ -1,20 +1,99 @@
-use ark_ff::FftField;
+use ark_ff::{FftField, Field};
 use ark_std::{start_timer, end_timer};
-use ark_poly::{univariate::DensePolynomial, EvaluationDomain, Polynomial};
-use anyhow::{anyhow, Result};
+use ark_poly::{univariate::DensePolynomial, EvaluationDomain, Polynomial, UVPolynomial};
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+use crate::error::{self, PosolError};
 use crate::util::{
     coset_evals_from_poly,
     coset_evals_from_poly_ref,
     poly_from_coset_evals,
     poly_from_evals,
 };
+use super::{ColumnPolys, proof::delta_powers};
+
+/// The extended domain of the quotient polynomial, with the evaluations of
+/// t(X), `1 / (X^n - 1)`, L0(X) and L{n-1}(X) over its coset, computed once
+/// per domain.
+///
+/// With the `low-memory` feature only the extended domain is kept: the coset
+/// evaluations are computed chunk by chunk instead.
+pub(crate) struct ExtendedDomain<F: FftField, D: EvaluationDomain<F>> {
+    pub(crate) domain: D,
+    t_coset: Vec<F>,
+    vh_inv_coset: Vec<F>,
+    l0_coset: Vec<F>,
+    ln_coset: Vec<F>,
+}
+
+impl<F, D> ExtendedDomain<F, D>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    pub(crate) fn new(domain: &D, t_poly: &DensePolynomial<F>) -> Result<Self, PosolError> {
+        let n = domain.size();
+        // Size of quotient poly is 2n+6 <= 4n => n >= 3
+        if cfg!(feature = "blinding") && n < 3 {
+            return Err(PosolError::InputRange(format!("domain size {} below 3 with blinding", n)));
+        }
+
+        let multiplier = if cfg!(feature = "blinding") { 4 } else { 2 };
+        let extended_domain = error::domain::<F, D>(multiplier * n)?;
+        if cfg!(feature = "low-memory") {
+            return Ok(Self {
+                domain: extended_domain,
+                t_coset: Vec::new(),
+                vh_inv_coset: Vec::new(),
+                l0_coset: Vec::new(),
+                ln_coset: Vec::new(),
+            });
+        }
+
+        Ok(Self::with_cosets(domain, extended_domain, t_poly))
+    }
+
+    fn with_cosets(domain: &D, extended_domain: D, t_poly: &DensePolynomial<F>) -> Self {
+        let timer = start_timer!(|| "Balance Sum: Precomputing Extended Domain");
+
+        let n = domain.size();
+        let t_coset = coset_evals_from_poly_ref(&extended_domain, t_poly);
+
+        // Compute extended evaluations for x^n - 1
+        let vh_poly: DensePolynomial<_> = domain.vanishing_polynomial().into();
+        let mut vh_inv_coset = coset_evals_from_poly(&extended_domain, vh_poly);
+        ark_ff::batch_inversion(&mut vh_inv_coset);
+
+        // compute extended evaluations for L0(x)
+        let mut l0_evals = vec![F::zero(); n];
+        l0_evals[0] = F::one();
+        let l0_poly = poly_from_evals(domain, l0_evals);
+        let l0_coset = coset_evals_from_poly(&extended_domain, l0_poly);
+
+        // compute extended evaluations for L{n-1}(x)
+        let mut ln_evals = vec![F::zero(); n];
+        ln_evals[n - 1] = F::one();
+        let ln_poly = poly_from_evals(domain, ln_evals);
+        let ln_coset = coset_evals_from_poly(&extended_domain, ln_poly);
+
+        end_timer!(timer);
+
+        Self {
+            domain: extended_domain,
+            t_coset,
+            vh_inv_coset,
+            l0_coset,
+            ln_coset,
+        }
+    }
+}
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn compute<F, D>(
     domain: &D,
+    extended_domain: &ExtendedDomain<F, D>,
+    table_size: usize,
     m: F,
     gamma: F,
     delta: F,
@@ -24,7 +103,37 @@ pub(crate) fn compute<F, D>(
     h1_poly: &DensePolynomial<F>,
     h2_poly: &DensePolynomial<F>,
     z_poly: &DensePolynomial<F>,
-) -> Result<DensePolynomial<F>>
+) -> Result<DensePolynomial<F>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let column = ColumnPolys {
+        m,
+        b_poly,
+        s_poly,
+        h1_poly,
+        h2_poly,
+        z_poly,
+    };
+
+    compute_batched(domain, extended_domain, table_size, gamma, delta, t_poly, &[column])
+}
+
+/// Compute the quotient polynomial of several balance columns sharing t(X),
+/// where the constraints of column `k` are batched with `delta^(8k)`.
+///
+/// With the `low-memory` feature the extended domain is never materialised:
+/// the quotient is evaluated coset by coset of the base domain instead.
+pub(crate) fn compute_batched<F, D>(
+    domain: &D,
+    extended_domain: &ExtendedDomain<F, D>,
+    table_size: usize,
+    gamma: F,
+    delta: F,
+    t_poly: &DensePolynomial<F>,
+    columns: &[ColumnPolys<F>],
+) -> Result<DensePolynomial<F>, PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
@@ -32,134 +141,336 @@ where
     let timer = start_timer!(|| "Balance Sum: Computing Quotient Polynomial");
 
     let n = domain.size();
-    if cfg!(blinding) {
-        // Size of quotient poly is 2n+6 <= 4n => n >= 3
-        assert!(n >= 3);
+    let q_poly = if cfg!(feature = "low-memory") {
+        compute_by_cosets(domain, &extended_domain.domain, table_size, gamma, delta, t_poly, columns)?
+    } else {
+        compute_on_extended_domain(domain, extended_domain, table_size, gamma, delta, columns)
+    };
+    // Sanity check
+    let max_degree = if cfg!(feature = "blinding") { 2 * n + 6 } else { 2 * n };
+    if q_poly.degree() > max_degree {
+        return Err(PosolError::Commitment(format!(
+            "quotient polynomial of degree {} above {}",
+            q_poly.degree(),
+            max_degree,
+        )));
     }
 
-    let multiplier = if cfg!(blinding) { 4 } else { 2 };
-    let extended_domain = D::new(multiplier * n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            (multiplier * n).trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
-
-    let t_coset = coset_evals_from_poly_ref(&extended_domain, t_poly);
-    let b_coset = coset_evals_from_poly_ref(&extended_domain, b_poly);
-
-    let mut s_coset = coset_evals_from_poly_ref(&extended_domain, s_poly);
-    s_coset.push(s_coset[0]);
-    s_coset.push(s_coset[1]);
-    if cfg!(blinding) {
-        s_coset.push(s_coset[2]);
-        s_coset.push(s_coset[3]);
-    }
+    end_timer!(timer);
 
-    let mut h1_coset = coset_evals_from_poly_ref(&extended_domain, h1_poly);
-    h1_coset.push(h1_coset[0]);
-    h1_coset.push(h1_coset[1]);
-    if cfg!(blinding) {
-        h1_coset.push(h1_coset[2]);
-        h1_coset.push(h1_coset[3]);
-    }
+    Ok(q_poly)
+}
+
+/// Evaluations of a column's polynomials at a point `x` and at `g x`.
+struct PointEvals<F> {
+    t: F,
+    b: F,
+    s: F,
+    s_next: F,
+    h1: F,
+    h1_next: F,
+    h2: F,
+    h2_next: F,
+    z: F,
+    z_next: F,
+    l0: F,
+    ln: F,
+}
+
+/// Combine the constraints of a column at a point, before division by `X^n - 1`.
+#[inline]
+fn constraints<F: Field>(m: F, gamma: F, deltas: &[F], table_max: F, e: &PointEvals<F>) -> F {
+    e.s_next - e.s + m * e.l0 - e.b
+        + e.z * (gamma + e.b) * (gamma + e.t) * deltas[0]
+        - e.z_next * (gamma + e.h1) * (gamma + e.h2) * deltas[0]
+        + (e.z - F::one()) * e.l0 * deltas[1]
+        + (e.h1_next - e.h1) * (e.h1_next - e.h1 - F::one()) * (e.ln - F::one()) * deltas[2]
+        + (e.h2_next - e.h2) * (e.h2_next - e.h2 - F::one()) * (e.ln - F::one()) * deltas[3]
+        + (e.h2_next - e.h1) * (e.h2_next - e.h1 - F::one()) * e.ln * deltas[4]
+        + e.h1 * e.l0 * deltas[5]
+        + (e.h2 - table_max) * e.ln * deltas[6]
+}
+
+/// Evaluate the quotient over a coset of the whole extended domain at once.
+fn compute_on_extended_domain<F, D>(
+    domain: &D,
+    extended: &ExtendedDomain<F, D>,
+    table_size: usize,
+    gamma: F,
+    delta: F,
+    columns: &[ColumnPolys<F>],
+) -> DensePolynomial<F>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let n = domain.size();
+    let extended_domain = &extended.domain;
+    let multiplier = extended_domain.size() / n;
+    let ExtendedDomain { t_coset, vh_inv_coset, l0_coset, ln_coset, .. } = extended;
 
-    let mut h2_coset = coset_evals_from_poly_ref(&extended_domain, h2_poly);
-    h2_coset.push(h2_coset[0]);
-    h2_coset.push(h2_coset[1]);
-    if cfg!(blinding) {
-        h2_coset.push(h2_coset[2]);
-        h2_coset.push(h2_coset[3]);
+    let deltas = delta_powers(delta);
+    let delta_exp_8 = deltas[6] * delta;
+    let table_max = F::from(table_size as u64 - 1);
+
+    let mut q_evals = vec![F::zero(); multiplier * n];
+    let mut factor = F::one();
+    for column in columns {
+        let b_coset = coset_evals_from_poly_ref(extended_domain, column.b_poly);
+        let s_coset = shiftable_coset_evals(extended_domain, column.s_poly, multiplier);
+        let h1_coset = shiftable_coset_evals(extended_domain, column.h1_poly, multiplier);
+        let h2_coset = shiftable_coset_evals(extended_domain, column.h2_poly, multiplier);
+        let z_coset = shiftable_coset_evals(extended_domain, column.z_poly, multiplier);
+
+        #[cfg(not(feature = "parallel"))]
+        let quotient_iter = itertools::izip!(
+            q_evals.iter_mut(),
+            t_coset.iter(),
+            b_coset,
+            s_coset.iter(),
+            s_coset.iter().skip(multiplier),
+            h1_coset.iter(),
+            h1_coset.iter().skip(multiplier),
+            h2_coset.iter(),
+            h2_coset.iter().skip(multiplier),
+            z_coset.iter(),
+            z_coset.iter().skip(multiplier),
+            l0_coset.iter(),
+            ln_coset.iter(),
+        );
+        #[cfg(feature = "parallel")]
+        let quotient_iter = crate::par_izip!(
+            q_evals.par_iter_mut(),
+            t_coset.par_iter(),
+            b_coset,
+            s_coset.par_iter(),
+            s_coset.par_iter().skip(multiplier),
+            h1_coset.par_iter(),
+            h1_coset.par_iter().skip(multiplier),
+            h2_coset.par_iter(),
+            h2_coset.par_iter().skip(multiplier),
+            z_coset.par_iter(),
+            z_coset.par_iter().skip(multiplier),
+            l0_coset.par_iter(),
+            ln_coset.par_iter(),
+        );
+
+        let m = column.m;
+        quotient_iter
+            .for_each(|(q, &t, b, &s, &s_next, &h1, &h1_next, &h2, &h2_next, &z, &z_next, &l0, &ln)| {
+                let evals = PointEvals { t, b, s, s_next, h1, h1_next, h2, h2_next, z, z_next, l0, ln };
+                *q += constraints(m, gamma, &deltas, table_max, &evals) * factor;
+            });
+
+        factor *= delta_exp_8;
     }
 
-    let mut z_coset = coset_evals_from_poly_ref(&extended_domain, z_poly);
-    z_coset.push(z_coset[0]);
-    z_coset.push(z_coset[1]);
-    if cfg!(blinding) {
-        z_coset.push(z_coset[2]);
-        z_coset.push(z_coset[3]);
+    ark_std::cfg_iter_mut!(q_evals)
+        .zip(ark_std::cfg_iter!(vh_inv_coset))
+        .for_each(|(q, vh_inv)| *q *= vh_inv);
+
+    poly_from_coset_evals(extended_domain, q_evals)
+}
+
+/// Evaluate the quotient over the cosets `c_j H` of the base domain `H` making
+/// up the extended coset, so that only vectors of size `n` are alive besides
+/// the quotient itself.
+///
+/// On `c_j H` the vanishing polynomial is the constant `c_j^n - 1`, shifting by
+/// the generator rotates the evaluations, and interpolating gives
+/// `q(X) mod (X^n - c_j^n) = sum_k c_j^(kn) q_k(X)` where `q = sum_k X^(kn) q_k`.
+/// Since `c_j^n = g^n zeta^j` for a primitive root of unity `zeta` of order
+/// `multiplier`, the blocks `q_k` are recovered with a small inverse DFT.
+#[allow(clippy::too_many_arguments)]
+fn compute_by_cosets<F, D>(
+    domain: &D,
+    extended_domain: &D,
+    table_size: usize,
+    gamma: F,
+    delta: F,
+    t_poly: &DensePolynomial<F>,
+    columns: &[ColumnPolys<F>],
+) -> Result<DensePolynomial<F>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let n = domain.size();
+    let multiplier = extended_domain.size() / n;
+    let inverse = |x: F| {
+        x.inverse()
+            .ok_or(PosolError::Commitment("zero inverse on the extended domain".into()))
+    };
+
+    let deltas = delta_powers(delta);
+    let delta_exp_8 = deltas[6] * delta;
+    let table_max = F::from(table_size as u64 - 1);
+    let n_inv = inverse(F::from(n as u64))?;
+    let g = domain.element(1);
+    let g_inv = domain.element(n - 1);
+
+    let offset = F::multiplicative_generator();
+    let offset_n_inv = inverse(offset.pow([n as u64]))?;
+    let zeta_inv = inverse(extended_domain.element(n))?;
+    let multiplier_inv = inverse(F::from(multiplier as u64))?;
+
+    let mut q_coeffs = vec![F::zero(); multiplier * n];
+    let mut c = offset;
+    for j in 0..multiplier {
+        let c_n = c.pow([n as u64]);
+
+        let t_evals = coset_chunk_evals(domain, t_poly, c, c_n);
+
+        // L0(x) = (x^n - 1) / (n (x - 1)) and L{n-1}(x) = (x^n - 1) / (n (g x - 1))
+        let mut l0_evals = Vec::with_capacity(n);
+        let mut ln_evals = Vec::with_capacity(n);
+        let mut x = c;
+        for _ in 0..n {
+            l0_evals.push(x - F::one());
+            ln_evals.push(x - g_inv);
+            x *= g;
+        }
+        ark_ff::batch_inversion(&mut l0_evals);
+        ark_ff::batch_inversion(&mut ln_evals);
+        let l_factor = (c_n - F::one()) * n_inv;
+        ark_std::cfg_iter_mut!(l0_evals).for_each(|l0| *l0 *= l_factor);
+        let ln_factor = l_factor * g_inv;
+        ark_std::cfg_iter_mut!(ln_evals).for_each(|ln| *ln *= ln_factor);
+
+        let mut q_evals = vec![F::zero(); n];
+        let mut factor = F::one();
+        for column in columns {
+            let b_evals = coset_chunk_evals(domain, column.b_poly, c, c_n);
+            let s_evals = coset_chunk_evals(domain, column.s_poly, c, c_n);
+            let h1_evals = coset_chunk_evals(domain, column.h1_poly, c, c_n);
+            let h2_evals = coset_chunk_evals(domain, column.h2_poly, c, c_n);
+            let z_evals = coset_chunk_evals(domain, column.z_poly, c, c_n);
+
+            let m = column.m;
+            ark_std::cfg_iter_mut!(q_evals)
+                .enumerate()
+                .for_each(|(i, q)| {
+                    let next = (i + 1) % n;
+                    let evals = PointEvals {
+                        t: t_evals[i],
+                        b: b_evals[i],
+                        s: s_evals[i],
+                        s_next: s_evals[next],
+                        h1: h1_evals[i],
+                        h1_next: h1_evals[next],
+                        h2: h2_evals[i],
+                        h2_next: h2_evals[next],
+                        z: z_evals[i],
+                        z_next: z_evals[next],
+                        l0: l0_evals[i],
+                        ln: ln_evals[i],
+                    };
+                    *q += constraints(m, gamma, &deltas, table_max, &evals) * factor;
+                });
+
+            factor *= delta_exp_8;
+        }
+
+        // Divide by the vanishing polynomial and interpolate q(X) mod (X^n - c^n)
+        let vh_inv = inverse(c_n - F::one())?;
+        domain.ifft_in_place(&mut q_evals);
+        D::distribute_powers_and_mul_by_const(&mut q_evals, inverse(c)?, vh_inv);
+
+        // q_k += (g^n)^(-k) zeta^(-jk) / multiplier * r_j
+        let zeta_inv_j = zeta_inv.pow([j as u64]);
+        let mut weight = multiplier_inv;
+        for q_k in q_coeffs.chunks_mut(n) {
+            ark_std::cfg_iter_mut!(q_k)
+                .zip(ark_std::cfg_iter!(q_evals))
+                .for_each(|(q, &r)| *q += r * weight);
+            weight *= offset_n_inv * zeta_inv_j;
+        }
+
+        c *= extended_domain.element(1);
     }
 
-    // Compute extended evaluations for x^n - 1
-    let vh_poly: DensePolynomial<_> = domain.vanishing_polynomial().into();
-    let vh_coset = coset_evals_from_poly(&extended_domain, vh_poly);
-
-    // compute extended evaluations for L0(x)
-    let mut l0_evals = vec![F::zero(); n];
-    l0_evals[0] = F::one();
-    let l0_poly = poly_from_evals(domain, l0_evals);
-    let l0_coset = coset_evals_from_poly(&extended_domain, l0_poly);
-
-    // compute extended evaluations for L{n-1}(x)
-    let mut ln_evals = vec![F::zero(); n];
-    ln_evals[n - 1] = F::one();
-    let ln_poly = poly_from_evals(domain, ln_evals);
-    let ln_coset = coset_evals_from_poly(&extended_domain, ln_poly);
-
-    #[cfg(not(feature = "parallel"))]
-    let quotient_iter = itertools::izip!(
-        t_coset,
-        b_coset,
-        s_coset.iter(),
-        s_coset.iter().skip(multiplier),
-        h1_coset.iter(),
-        h1_coset.iter().skip(multiplier),
-        h2_coset.iter(),
-        h2_coset.iter().skip(multiplier),
-        z_coset.iter(),
-        z_coset.iter().skip(multiplier),
-        vh_coset,
-        l0_coset,
-        ln_coset,
-    );
-    #[cfg(feature = "parallel")]
-    let quotient_iter = crate::par_izip!(
-        t_coset,
-        b_coset,
-        s_coset.par_iter(),
-        s_coset.par_iter().skip(multiplier),
-        h1_coset.par_iter(),
-        h1_coset.par_iter().skip(multiplier),
-        h2_coset.par_iter(),
-        h2_coset.par_iter().skip(multiplier),
-        z_coset.par_iter(),
-        z_coset.par_iter().skip(multiplier),
-        vh_coset,
-        l0_coset,
-        ln_coset,
-    );
-
-    let delta_exp_2 = delta.square();
-    let delta_exp_3 = delta_exp_2 * delta;
-    let delta_exp_4 = delta_exp_3 * delta;
-    let delta_exp_5 = delta_exp_4 * delta;
-    let delta_exp_6 = delta_exp_5 * delta;
-    let delta_exp_7 = delta_exp_6 * delta;
-    let q_evals = quotient_iter
-        .map(|(t, b, &s, &s_next, &h1, &h1_next, &h2, &h2_next, &z, &z_next, vh, l0, ln)| {
-            let q_eval = s_next - s + m * l0 - b
-                + z * (gamma + b) * (gamma + t) * delta
-                - z_next * (gamma + h1) * (gamma + h2) * delta
-                + (z - F::one()) * l0 * delta_exp_2
-                + (h1_next - h1) * (h1_next - h1 - F::one()) * (ln - F::one()) * delta_exp_3
-                + (h2_next - h2) * (h2_next - h2 - F::one()) * (ln - F::one()) * delta_exp_4
-                + (h2_next - h1) * (h2_next - h1 - F::one()) * ln * delta_exp_5
-                + h1 * l0 * delta_exp_6
-                + (h2 - F::from(n as u64 - 1)) * ln * delta_exp_7;
-
-            q_eval * vh.inverse().unwrap()
-        })
-        .collect();
-
-    let q_poly = poly_from_coset_evals(&extended_domain, q_evals);
-    // Sanity check
-    if cfg!(blinding) {
-        assert!(q_poly.degree() <= 2 * n + 6);
-    } else {
-        assert!(q_poly.degree() <= 2 * n);
+    Ok(DensePolynomial::from_coefficients_vec(q_coeffs))
+}
+
+/// Compute the evaluations of a polynomial over the coset `c H` of `domain`, given `c^n`.
+fn coset_chunk_evals<F, D>(domain: &D, poly: &DensePolynomial<F>, c: F, c_n: F) -> Vec<F>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let n = domain.size();
+    // Reduce modulo X^n - c^n, which vanishes on the coset
+    let mut evals = vec![F::zero(); n];
+    let mut factor = F::one();
+    for chunk in poly.coeffs.chunks(n) {
+        ark_std::cfg_iter_mut!(evals)
+            .zip(ark_std::cfg_iter!(chunk))
+            .for_each(|(e, &coeff)| *e += coeff * factor);
+        factor *= c_n;
     }
+    D::distribute_powers(&mut evals, c);
+    domain.fft_in_place(&mut evals);
 
-    end_timer!(timer);
+    evals
+}
 
-    Ok(q_poly)
+/// Compute the extended coset evaluations of a polynomial, appending the first
+/// `multiplier` evaluations so that they can be shifted by the generator.
+fn shiftable_coset_evals<F, D>(
+    extended_domain: &D,
+    poly: &DensePolynomial<F>,
+    multiplier: usize,
+) -> Vec<F>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let mut coset = coset_evals_from_poly_ref(extended_domain, poly);
+    coset.extend_from_within(..multiplier);
+
+    coset
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::Fr;
+    use ark_ff::UniformRand;
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_std::test_rng;
+
+    use super::*;
+
+    #[test]
+    fn test_compute_by_cosets() {
+        let rng = &mut test_rng();
+        let n = 16;
+        let domain = GeneralEvaluationDomain::<Fr>::new(n).unwrap();
+
+        for multiplier in [2, 4] {
+            let extended_domain = GeneralEvaluationDomain::<Fr>::new(multiplier * n).unwrap();
+            // Degrees of blinded polynomials exceed n
+            let polys = (0..11)
+                .map(|_| DensePolynomial::rand(n + 2, rng))
+                .collect::<Vec<_>>();
+            let columns = polys[1..]
+                .chunks(5)
+                .map(|p| ColumnPolys {
+                    m: Fr::rand(rng),
+                    b_poly: &p[0],
+                    s_poly: &p[1],
+                    h1_poly: &p[2],
+                    h2_poly: &p[3],
+                    z_poly: &p[4],
+                })
+                .collect::<Vec<_>>();
+            let gamma = Fr::rand(rng);
+            let delta = Fr::rand(rng);
+
+            let extended = ExtendedDomain::with_cosets(&domain, extended_domain, &polys[0]);
+            assert_eq!(
+                compute_by_cosets(&domain, &extended.domain, n, gamma, delta, &polys[0], &columns).unwrap(),
+                compute_on_extended_domain(&domain, &extended, n, gamma, delta, &columns),
+            );
+        }
+    }
 }
@@ -0,0 +1,443 @@
+//! Prover and verifier of the balance sum over a fixed domain.
+//!
+//! The free functions of [`super`] rebuild the domain on every call and leave
+//! t(X) and its commitment to the caller. A [`Prover`] or a [`Verifier`] is
+//! built once per domain size instead, and keeps the domain, t(X) with its
+//! commitment and, for the prover, the evaluations of t(X), L0(X), L{n-1}(X)
+//! and `1 / (X^n - 1)` over the extended coset of the quotient polynomial.
+//!
+//! They also decouple the range table `{0, ..., table_size - 1}` from the
+//! users domain of size `n`: the proof runs over a domain of size
+//! `max(n, table_size)` rounded up to a power of two. A smaller table is padded
+//! with its largest value. With a larger table, the balance of user `i` is
+//! placed at the `(i * stride)`-th element of the proof domain, which is the
+//! `i`-th element of the users domain, so that balances are still opened over
+//! the users domain, consistently with the tags.
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::LabeledPolynomial;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    error::{domain, PosolError},
+    util::EvaluationDomainExt,
+    commitment::HomomorphicCommitment,
+    opening::MultiOpening,
+};
+use super::{
+    check_balances,
+    individual_open_in,
+    individual_verify_in,
+    multi_open_in,
+    multi_verify_in,
+    precompute_in,
+    prove_in,
+    prove_limbs_with,
+    LimbsProof,
+    quotient_poly::ExtendedDomain,
+    Proof,
+    TranscriptProtocol,
+};
+
+/// Prover of the balance sum over a domain of size `n`.
+pub struct Prover<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    ck: PC::CommitterKey,
+    users_domain: D,
+    domain: D,
+    table_size: usize,
+    extended_domain: ExtendedDomain<F, D>,
+    labeled_t_poly: LabeledPolynomial<F, DensePolynomial<F>>,
+    t_commit: PC::Commitment,
+}
+
+impl<F, D, PC> Prover<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Build the domain of size `n` and precompute t(X) with its commitment,
+    /// for balances below `n`.
+    pub fn new(ck: PC::CommitterKey, n: usize) -> Result<Self, PosolError> {
+        Self::with_table_size(ck, n, n)
+    }
+
+    /// Build the domains for `n` users and balances below `table_size`, and
+    /// precompute t(X) with its commitment.
+    pub fn with_table_size(ck: PC::CommitterKey, n: usize, table_size: usize) -> Result<Self, PosolError> {
+        let (users_domain, domain) = domains::<F, D>(n, table_size)?;
+        let (labeled_t_poly, t_commit) = precompute_in::<F, D, PC>(&ck, &domain, table_size)?;
+        let extended_domain = ExtendedDomain::new(&domain, labeled_t_poly.polynomial())?;
+
+        Ok(Self {
+            ck,
+            users_domain,
+            domain,
+            table_size,
+            extended_domain,
+            labeled_t_poly,
+            t_commit,
+        })
+    }
+
+    /// Size of the users domain.
+    pub fn size(&self) -> usize {
+        self.users_domain.size()
+    }
+
+    /// Size of the range table, every balance is below it.
+    pub fn table_size(&self) -> usize {
+        self.table_size
+    }
+
+    /// Size of the domain of the proof, which the committer key must support.
+    pub fn proof_size(&self) -> usize {
+        self.domain.size()
+    }
+
+    pub fn committer_key(&self) -> &PC::CommitterKey {
+        &self.ck
+    }
+
+    pub fn labeled_t_poly(&self) -> &LabeledPolynomial<F, DensePolynomial<F>> {
+        &self.labeled_t_poly
+    }
+
+    pub fn t_commit(&self) -> &PC::Commitment {
+        &self.t_commit
+    }
+
+    /// Prove the sum of `balances`, see [`super::prove`].
+    #[allow(clippy::type_complexity)]
+    pub fn prove<T, R>(
+        &self,
+        balances: &[u64],
+        rng: &mut R,
+    ) -> Result<(F, Proof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+        R: CryptoRng + RngCore,
+    {
+        check_balances(self.size(), self.table_size, balances)?;
+
+        prove_in::<F, D, PC, T, R>(
+            &self.ck,
+            &self.domain,
+            &self.extended_domain,
+            self.table_size,
+            self.proof_size() / self.size(),
+            &self.labeled_t_poly,
+            &self.t_commit,
+            balances,
+            rng,
+        )
+    }
+
+    /// Prove the sum of full-width balances by decomposing them into limbs
+    /// below the table size, see [`super::prove_limbs`].
+    #[allow(clippy::type_complexity)]
+    pub fn prove_limbs<T, R>(
+        &self,
+        balances: &[u128],
+        rng: &mut R,
+    ) -> Result<(F, LimbsProof<F, D, PC>, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+        R: CryptoRng + RngCore,
+    {
+        if balances.len() > self.size() {
+            return Err(PosolError::InputRange(format!(
+                "{} balances out of domain of size {}",
+                balances.len(),
+                self.size(),
+            )));
+        }
+
+        prove_limbs_with(self.table_size, balances, |limb| self.prove::<T, R>(limb, rng))
+    }
+
+    /// Open B(X) at the `i`-th element of the users domain, see [`super::individual_open`].
+    pub fn individual_open(
+        &self,
+        i: usize,
+        labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+        b_commit: &PC::Commitment,
+    ) -> Result<PC::Proof, PosolError> {
+        individual_open_in::<F, D, PC>(&self.ck, &self.users_domain, i, labeled_b_poly, b_commit)
+    }
+
+    /// Open B(X) at every index of `indices` with one proof, see [`super::multi_open`].
+    pub fn multi_open<T>(
+        &self,
+        indices: &[usize],
+        labeled_b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+        b_commit: &PC::Commitment,
+    ) -> Result<MultiOpening<F, PC>, PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        multi_open_in::<F, D, PC, T>(&self.ck, &self.users_domain, indices, labeled_b_poly, b_commit)
+    }
+
+    /// Verifier of the proofs of this prover.
+    pub fn verifier(&self, cvk: PC::VerifierKey) -> Verifier<F, D, PC> {
+        Verifier {
+            cvk,
+            users_domain: self.users_domain,
+            domain: self.domain,
+            table_size: self.table_size,
+            t_commit: self.t_commit.clone(),
+        }
+    }
+}
+
+/// Verifier of the balance sum over a domain of size `n`.
+pub struct Verifier<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    cvk: PC::VerifierKey,
+    users_domain: D,
+    domain: D,
+    table_size: usize,
+    t_commit: PC::Commitment,
+}
+
+impl<F, D, PC> Verifier<F, D, PC>
+where
+    F: FftField,
+    D: EvaluationDomain<F> + EvaluationDomainExt<F>,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Build the domain of size `n`, given the published commitment to t(X).
+    pub fn new(cvk: PC::VerifierKey, n: usize, t_commit: PC::Commitment) -> Result<Self, PosolError> {
+        Self::with_table_size(cvk, n, n, t_commit)
+    }
+
+    /// Build the domains for `n` users and balances below `table_size`, given
+    /// the published commitment to t(X).
+    pub fn with_table_size(
+        cvk: PC::VerifierKey,
+        n: usize,
+        table_size: usize,
+        t_commit: PC::Commitment,
+    ) -> Result<Self, PosolError> {
+        let (users_domain, domain) = domains::<F, D>(n, table_size)?;
+
+        Ok(Self { cvk, users_domain, domain, table_size, t_commit })
+    }
+
+    /// Size of the users domain.
+    pub fn size(&self) -> usize {
+        self.users_domain.size()
+    }
+
+    /// Size of the range table, every balance is below it.
+    pub fn table_size(&self) -> usize {
+        self.table_size
+    }
+
+    pub fn verifier_key(&self) -> &PC::VerifierKey {
+        &self.cvk
+    }
+
+    pub fn t_commit(&self) -> &PC::Commitment {
+        &self.t_commit
+    }
+
+    /// Verify a proof that the balances sum to `m`, see [`Proof::verify`].
+    pub fn verify<T>(&self, proof: &Proof<F, D, PC>, m: F) -> Result<(), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        proof.verify_in::<T>(&self.cvk, &self.domain, self.table_size, &self.t_commit, m)
+    }
+
+    /// Verify every limb proof, returning the aggregated balance sum, see [`LimbsProof::verify`].
+    pub fn verify_limbs<T>(&self, proof: &LimbsProof<F, D, PC>) -> Result<F, PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        proof.verify_with(self.table_size, |proof, sum| self.verify::<T>(proof, sum))
+    }
+
+    /// Verify the balance of the `i`-th user, see [`super::individual_verify`].
+    pub fn individual_verify(
+        &self,
+        i: usize,
+        balance: u128,
+        b_commit: &PC::Commitment,
+        proof: &PC::Proof,
+    ) -> Result<(), PosolError> {
+        individual_verify_in::<F, D, PC>(&self.cvk, &self.users_domain, i, F::from(balance), b_commit, proof)
+    }
+
+    /// Verify the balances of every index of `indices`, see [`super::multi_verify`].
+    pub fn multi_verify<T>(
+        &self,
+        indices: &[usize],
+        balances: &[u128],
+        b_commit: &PC::Commitment,
+        proof: &MultiOpening<F, PC>,
+    ) -> Result<(), PosolError>
+    where
+        T: TranscriptProtocol<F, PC::Commitment>,
+    {
+        multi_verify_in::<F, D, PC, T>(&self.cvk, &self.users_domain, indices, balances, b_commit, proof)
+    }
+}
+
+/// The users domain of size `n`, and the domain of the proof holding both the
+/// users and the range table.
+fn domains<F, D>(n: usize, table_size: usize) -> Result<(D, D), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    if table_size == 0 {
+        return Err(PosolError::InputRange("empty range table".into()));
+    }
+    let users_domain = domain::<F, D>(n)?;
+    let domain = domain::<F, D>(n.max(table_size.next_power_of_two()))?;
+
+    Ok((users_domain, domain))
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use itertools::Itertools;
+
+    use crate::commitment::KZG10;
+    use super::super::{individual_open, MerlinTranscript};
+    use super::*;
+
+    type KZGProver = Prover<Fr, GeneralEvaluationDomain<Fr>, KZG10<Bn254>>;
+
+    #[test]
+    fn test_session() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        assert!(matches!(KZGProver::new(ck.clone(), 12), Err(PosolError::Domain { .. })));
+        let prover = KZGProver::new(ck.clone(), n).unwrap();
+        let verifier = Verifier::new(cvk.clone(), n, prover.t_commit().clone()).unwrap();
+
+        // prove several snapshots with the same prover
+        for _ in 0..2 {
+            let balances = (0..n).map(|_| rng.gen_range(0..n as u64)).collect_vec();
+            let (m, proof, labeled_b_poly) = prover.prove::<MerlinTranscript, _>(&balances, rng).unwrap();
+            assert_eq!(m, Fr::from(balances.iter().sum::<u64>()));
+            assert!(verifier.verify::<MerlinTranscript>(&proof, m).is_ok());
+            assert!(matches!(
+                verifier.verify::<MerlinTranscript>(&proof, m + Fr::from(1u64)),
+                Err(PosolError::Verification(_)),
+            ));
+
+            // individual openings are those of the free functions
+            for (i, &balance) in balances.iter().enumerate().take(4) {
+                let opening = prover.individual_open(i, &labeled_b_poly, &proof.b_commit).unwrap();
+                let expected = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                    &ck,
+                    n,
+                    i,
+                    &labeled_b_poly,
+                    &proof.b_commit,
+                ).unwrap();
+                assert_eq!(opening, expected);
+                let res = verifier.individual_verify(i, balance as u128, &proof.b_commit, &opening);
+                assert!(res.is_ok());
+            }
+
+            let indices = [1, 5, 7];
+            let multi_opening = prover
+                .multi_open::<MerlinTranscript>(&indices, &labeled_b_poly, &proof.b_commit)
+                .unwrap();
+            let opened = indices.iter().map(|&i| balances[i] as u128).collect_vec();
+            let res = prover
+                .verifier(cvk.clone())
+                .multi_verify::<MerlinTranscript>(&indices, &opened, &proof.b_commit, &multi_opening);
+            assert!(res.is_ok());
+        }
+
+        // out of range balances are rejected before proving
+        let res = prover.prove::<MerlinTranscript, _>(&[n as u64], rng);
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+    }
+
+    #[test]
+    fn test_table_size() {
+        let rng = &mut test_rng();
+
+        // setup for the largest proof domain
+        let max_degree = if cfg!(feature = "blinding") { 32 + 3 } else { 32 };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        // a table smaller than the users domain, and a larger one
+        for (n, table_size, proof_size) in [(16, 4, 16), (8, 20, 32)] {
+            let prover = KZGProver::with_table_size(ck.clone(), n, table_size).unwrap();
+            assert_eq!(prover.proof_size(), proof_size);
+            let verifier = prover.verifier(cvk.clone());
+
+            let balances = (0..n - 1).map(|_| rng.gen_range(0..table_size as u64)).collect_vec();
+            let (m, proof, labeled_b_poly) = prover.prove::<MerlinTranscript, _>(&balances, rng).unwrap();
+            assert_eq!(m, Fr::from(balances.iter().sum::<u64>()));
+            assert!(verifier.verify::<MerlinTranscript>(&proof, m).is_ok());
+
+            // balances are opened over the users domain
+            for (i, &balance) in balances.iter().enumerate() {
+                let opening = prover.individual_open(i, &labeled_b_poly, &proof.b_commit).unwrap();
+                let res = verifier.individual_verify(i, balance as u128, &proof.b_commit, &opening);
+                assert!(res.is_ok());
+            }
+
+            // the proof does not verify against another table
+            let other = Verifier::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>::with_table_size(
+                cvk.clone(),
+                n,
+                table_size - 1,
+                prover.t_commit().clone(),
+            ).unwrap();
+            assert!(other.verify::<MerlinTranscript>(&proof, m).is_err());
+
+            let res = prover.prove::<MerlinTranscript, _>(&[table_size as u64], rng);
+            assert!(matches!(res, Err(PosolError::InputRange(_))));
+
+            // full-width balances are split into limbs below the table size
+            let wide_balances = (0..n).map(|_| rng.gen_range(0..1u128 << 40)).collect_vec();
+            let (m, limbs_proof, labeled_b_poly) =
+                prover.prove_limbs::<MerlinTranscript, _>(&wide_balances, rng).unwrap();
+            assert_eq!(m, Fr::from(wide_balances.iter().sum::<u128>()));
+            assert_eq!(verifier.verify_limbs::<MerlinTranscript>(&limbs_proof).unwrap(), m);
+            let b_commit = limbs_proof.aggregate_b_commit(table_size);
+            let opening = prover.individual_open(3, &labeled_b_poly, &b_commit).unwrap();
+            assert!(verifier.individual_verify(3, wide_balances[3], &b_commit, &opening).is_ok());
+        }
+    }
+}
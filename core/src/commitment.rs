@@ -106,6 +106,12 @@ where
             .collect_vec();
 
         let points_repr = commitments.iter().map(|c| c.comm).collect_vec();
+        // Shifted commitments only combine when every commitment is degree-bounded.
+        let shifted_points_repr = commitments
+            .iter()
+            .map(|c| c.shifted_comm)
+            .collect::<Option<Vec<_>>>()
+            .filter(|points| !points.is_empty());
 
         IPACommitment::<G, D> {
             comm: VariableBaseMSM::multi_scalar_mul(
@@ -113,7 +119,13 @@ where
                 &scalars_repr,
             )
             .into(),
-            shifted_comm: None, // TODO: support degree bounds?
+            shifted_comm: shifted_points_repr.map(|points| {
+                VariableBaseMSM::multi_scalar_mul(
+                    &points,
+                    &scalars_repr,
+                )
+                .into()
+            }),
         }
     }
 }
@@ -0,0 +1,54 @@
+//! Errors of the tag and balance sum protocols.
+use core::fmt;
+use ark_ff::FftField;
+use ark_poly::EvaluationDomain;
+
+/// Error of the tag and balance sum protocols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PosolError {
+    /// No evaluation domain of this size: it must be a power of two, and at
+    /// most `2^two_adicity`.
+    Domain { size: usize, two_adicity: u32 },
+    /// An input is out of its valid range, e.g. a balance not below the domain size.
+    InputRange(String),
+    /// Computing or committing to a polynomial failed.
+    Commitment(String),
+    /// Opening a polynomial failed.
+    Opening(String),
+    /// A proof or an opening does not verify.
+    Verification(String),
+}
+
+impl fmt::Display for PosolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Domain { size, two_adicity } => write!(
+                f,
+                "no domain of size {}, must be a power of two with 2-adicity: {}",
+                size,
+                two_adicity,
+            ),
+            Self::InputRange(msg) => write!(f, "input out of range: {}", msg),
+            Self::Commitment(msg) => write!(f, "commitment error: {}", msg),
+            Self::Opening(msg) => write!(f, "opening error: {}", msg),
+            Self::Verification(msg) => write!(f, "verification error: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PosolError {}
+
+/// Evaluation domain of size exactly `n`.
+pub(crate) fn domain<F, D>(n: usize) -> Result<D, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    D::new(n)
+        .filter(|domain| n.is_power_of_two() && domain.size() == n)
+        .ok_or(PosolError::Domain {
+            size: n,
+            two_adicity: <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
+        })
+}
@@ -0,0 +1,215 @@
+//! Openings of a KZG committed polynomial at every point of the domain at once.
+//!
+//! Following Feist and Khovratovich, the opening proof of `f(X)` of degree `d`
+//! at `z` is
+//!
+//! ```text
+//! [(f(X) - f(z)) / (X - z)] = sum_m z^m h_m,  h_m = sum_k f_(k+m+1) [tau^k]
+//! ```
+//!
+//! The vector `h` is a Toeplitz matrix product, computed with FFTs of size `2d`
+//! over G1, so the proofs at every `omega^i` are a single FFT of `h` folded to
+//! the domain size. All `n` openings cost `O(n log n)` group operations instead
+//! of the `O(n)` of each individual opening.
+use ark_std::{cfg_iter, start_timer, end_timer, vec, vec::Vec};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use ark_poly::{EvaluationDomain, Polynomial, UVPolynomial, univariate::DensePolynomial};
+use ark_poly_commit::{kzg10, LabeledPolynomial};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{
+    commitment::{KZG10CommitterKey, KZG10Proof},
+    error::{self, domain, PosolError},
+};
+
+/// Open every polynomial of `polys` at every element of the domain of size `n`.
+///
+/// The `i`-th proof of a polynomial is the same as the one of its individual
+/// opening at `omega^i`, e.g. with [`crate::tag::individual_open`].
+pub fn open_all<E, D>(
+    ck: &KZG10CommitterKey<E>,
+    n: usize,
+    polys: &[&LabeledPolynomial<E::Fr, DensePolynomial<E::Fr>>],
+) -> Result<Vec<Vec<KZG10Proof<E>>>, PosolError>
+where
+    E: PairingEngine,
+    D: EvaluationDomain<E::Fr>,
+{
+    let domain = domain::<E::Fr, D>(n)?;
+
+    // Number of terms of h, the degree of the largest polynomial.
+    let l = polys
+        .iter()
+        .map(|p| p.polynomial().degree())
+        .max()
+        .unwrap_or(0);
+    if l == 0 {
+        return Ok(vec![vec![KZG10Proof::<E>::default(); n]; polys.len()]);
+    }
+    if ck.powers_of_g.len() < l {
+        return Err(PosolError::InputRange(format!(
+            "committer key supports degree {}, {} required",
+            ck.powers_of_g.len() - 1,
+            l,
+        )));
+    }
+    let conv_domain = error::domain::<E::Fr, D>((2 * l).next_power_of_two())?;
+
+    let timer = start_timer!(|| "FK20: Opening All");
+
+    // The powers of tau in reverse order, shared by every polynomial.
+    let fft_timer = start_timer!(|| "FK20: Powers FFT");
+    let mut powers_fft = ck.powers_of_g[..l]
+        .iter()
+        .rev()
+        .map(|p| p.into_projective())
+        .collect::<Vec<_>>();
+    conv_domain.fft_in_place(&mut powers_fft);
+    end_timer!(fft_timer);
+
+    let proofs = polys
+        .iter()
+        .map(|p| {
+            let coeffs = p.polynomial().coeffs();
+            let mut coeffs_fft = coeffs.get(1..).unwrap_or(&[]).to_vec();
+            conv_domain.fft_in_place(&mut coeffs_fft);
+
+            // h_m is the (m + l - 1)-th term of the convolution of the
+            // coefficients with the reversed powers of tau.
+            let mut h = cfg_iter!(powers_fft)
+                .zip(coeffs_fft)
+                .map(|(s, c)| s.mul(c.into_repr()))
+                .collect::<Vec<_>>();
+            conv_domain.ifft_in_place(&mut h);
+
+            // omega^(i n) = 1, so the terms beyond the domain size fold back.
+            let mut folded = vec![E::G1Projective::zero(); n];
+            h[l - 1..2 * l - 1]
+                .iter()
+                .enumerate()
+                .for_each(|(m, h_m)| folded[m % n] += h_m);
+            domain.fft_in_place(&mut folded);
+            E::G1Projective::batch_normalization(&mut folded);
+
+            folded
+                .into_iter()
+                .map(|w| kzg10::Proof { w: w.into(), random_v: None })
+                .collect()
+        })
+        .collect();
+
+    end_timer!(timer);
+
+    Ok(proofs)
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use itertools::Itertools;
+
+    use crate::{
+        balance_sum::{self, MerlinTranscript},
+        commitment::KZG10,
+        label_polynomial,
+        tag,
+    };
+    use super::*;
+
+    #[test]
+    fn test_open_all() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let max_degree = n + 3;
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        // commit to tags and prove for balances
+        let tags = (0..n)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let (tag_commit, labeled_tag_poly) =
+            tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+        let balances = (0..n - 3).map(|_| rng.gen_range(0..n as u64)).collect_vec();
+        let (labeled_t_poly, t_commit) =
+            balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+        let (_, proof, labeled_b_poly) =
+            balance_sum::prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+        // a polynomial of degree above the domain size
+        let long_poly = label_polynomial!(DensePolynomial::<Fr>::rand(n + 2, rng));
+
+        let proofs = open_all::<_, GeneralEvaluationDomain<_>>(
+            &ck,
+            n,
+            &[&labeled_tag_poly, &labeled_b_poly, &long_poly],
+        ).unwrap();
+        for i in 0..n {
+            let tag_opening = tag::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                &ck,
+                n,
+                i,
+                &labeled_tag_poly,
+                &tag_commit,
+            ).unwrap();
+            assert_eq!(proofs[0][i], tag_opening);
+            let b_opening = balance_sum::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                &ck,
+                n,
+                i,
+                &labeled_b_poly,
+                &proof.b_commit,
+            ).unwrap();
+            assert_eq!(proofs[1][i], b_opening);
+            let balance = balances.get(i).copied().unwrap_or(0);
+            let res = balance_sum::individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                &cvk,
+                n,
+                i,
+                balance as u128,
+                &proof.b_commit,
+                &proofs[1][i],
+            );
+            assert!(res.is_ok());
+        }
+
+        let (long_commit, _) = KZG10::<Bn254>::commit(&ck, vec![&long_poly], None).unwrap();
+        let domain = GeneralEvaluationDomain::<Fr>::new(n).unwrap();
+        for (i, point) in domain.elements().enumerate() {
+            let res = KZG10::<Bn254>::check(
+                &cvk,
+                vec![&long_commit[0]],
+                &point,
+                vec![long_poly.evaluate(&point)],
+                &proofs[2][i],
+                Fr::from(1u64),
+                None,
+            ).unwrap();
+            assert!(res);
+        }
+    }
+}
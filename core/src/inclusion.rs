@@ -0,0 +1,241 @@
+//! Joint opening of the tag and balance polynomials at a single user index.
+//!
+//! A user always checks `T(omega^i)` and `B(omega^i)` together, so both are
+//! opened with one proof of the combination `T(X) + nu * B(X)`, where `nu` is
+//! drawn from a transcript bound to the commitments and the opened values.
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::{LabeledPolynomial, PCRandomness};
+
+use crate::{
+    error::{domain, PosolError},
+    balance_sum::TranscriptProtocol,
+    commitment::HomomorphicCommitment,
+    label_commitment,
+};
+
+/// Open tag(X) and B(X) at the domain point of user `i` with one proof.
+pub fn joint_open<F, D, PC, T>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    i: usize,
+    tag_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    tag_commit: &PC::Commitment,
+    b_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    b_commit: &PC::Commitment,
+) -> Result<PC::Proof, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Joint Opening");
+
+    let point = domain.element(i);
+    let tag = tag_poly.evaluate(&point);
+    let balance = b_poly.evaluate(&point);
+    let nu = challenge::<F, PC, T>(n, i, tag_commit, b_commit, &tag, &balance);
+
+    let labeled_tag_commit = label_commitment!(tag_commit, tag_poly.label());
+    let labeled_b_commit = label_commitment!(b_commit, b_poly.label());
+    let randomness = <PC::Randomness as PCRandomness>::empty();
+    let proof = PC::open(
+        ck,
+        vec![tag_poly, b_poly],
+        vec![&labeled_tag_commit, &labeled_b_commit],
+        &point,
+        nu,
+        vec![&randomness, &randomness],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open tag(X) and B(X) failed: {}", e)))?;
+
+    end_timer!(timer);
+
+    Ok(proof)
+}
+
+/// Verify the tag and balance of user `i` against one joint opening.
+#[allow(clippy::too_many_arguments)]
+pub fn joint_verify<F, D, PC, T>(
+    vk: &PC::VerifierKey,
+    n: usize,
+    i: usize,
+    tag: &[u8],
+    balance: u128,
+    tag_commit: &PC::Commitment,
+    b_commit: &PC::Commitment,
+    proof: &PC::Proof,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Joint Verifying");
+
+    let point = domain.element(i);
+    let tag = F::read(tag)
+        .map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))?;
+    let balance = F::from(balance);
+    let nu = challenge::<F, PC, T>(n, i, tag_commit, b_commit, &tag, &balance);
+
+    let labeled_tag_commit = label_commitment!(tag_commit);
+    let labeled_b_commit = label_commitment!(b_commit);
+    match PC::check(
+        vk,
+        vec![&labeled_tag_commit, &labeled_b_commit],
+        &point,
+        vec![tag, balance],
+        proof,
+        nu,
+        None,
+    ) {
+        Ok(true) => {
+            end_timer!(timer);
+            Ok(())
+        }
+        Ok(false) => Err(PosolError::Verification("joint tag and balance verification failed".into())),
+        Err(e) => Err(PosolError::Verification(format!("check opening proof error: {}", e))),
+    }
+}
+
+/// Fiat-Shamir challenge `nu` combining tag(X) and B(X).
+fn challenge<F, PC, T>(
+    n: usize,
+    i: usize,
+    tag_commit: &PC::Commitment,
+    b_commit: &PC::Commitment,
+    tag: &F,
+    balance: &F,
+) -> F
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let transcript = &mut T::new("Joint Opening");
+    transcript.append_u64("n", n as u64);
+    transcript.append_u64("index", i as u64);
+    transcript.append_commitment("tag_commit", tag_commit);
+    transcript.append_commitment("b_commit", b_commit);
+    transcript.append_scalar("tag", tag);
+    transcript.append_scalar("balance", balance);
+
+    transcript.challenge_scalar("nu")
+}
+
+#[cfg(test)]
+mod test {
+    use ark_ff::{ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use ark_bn254::{Fr, Bn254};
+    use itertools::Itertools;
+
+    use crate::{commitment::KZG10, balance_sum::{self, MerlinTranscript}, tag};
+    use super::*;
+
+    #[test]
+    fn test_full() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let max_degree = if cfg!(feature = "blinding") { n + 3 } else { n };
+        let pp = KZG10::<Bn254>::setup(max_degree, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            max_degree,
+            0,
+            None,
+        ).unwrap();
+
+        // generate random tags and balances
+        let tags = (0..n - 3)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let balances = (0..n - 3)
+            .map(|_| rng.gen_range(0..n as u64))
+            .collect_vec();
+
+        // commit and prove
+        let (tag_commit, labeled_tag_poly) =
+            tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+        let (labeled_t_poly, t_commit) =
+            balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+        let (_, proof, labeled_b_poly) =
+            balance_sum::prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+
+        // open
+        let i = rng.gen_range(0..n - 3);
+        let opening_proof =
+            joint_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+                &ck,
+                n,
+                i,
+                &labeled_tag_poly,
+                &tag_commit,
+                &labeled_b_poly,
+                &proof.b_commit,
+            ).unwrap();
+        // verify
+        let res = joint_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+            &cvk,
+            n,
+            i,
+            tags_ref[i],
+            balances[i] as u128,
+            &tag_commit,
+            &proof.b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+
+        // verify with a wrong balance
+        let res = joint_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+            &cvk,
+            n,
+            i,
+            tags_ref[i],
+            balances[i] as u128 + 1,
+            &tag_commit,
+            &proof.b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_err());
+
+        // verify with the tag of another user
+        let res = joint_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+            &cvk,
+            n,
+            i,
+            tags_ref[(i + 1) % (n - 3)],
+            balances[i] as u128,
+            &tag_commit,
+            &proof.b_commit,
+            &opening_proof,
+        );
+        assert!(res.is_err());
+    }
+}
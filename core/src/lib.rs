@@ -3,5 +3,10 @@
 
 pub mod balance_sum;
 pub mod commitment;
+pub mod error;
+pub mod fk20;
+pub mod inclusion;
+pub mod opening;
+pub mod srs;
 pub mod util;
 pub mod tag;
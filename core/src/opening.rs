@@ -0,0 +1,237 @@
+//! Opening a polynomial at a set of domain points with a constant-size proof.
+//!
+//! For an index set `S`, the prover divides `P(X)` by the vanishing polynomial
+//! `Z_S(X) = ∏ (X - omega^i)`, obtaining `P(X) = f(X) * Z_S(X) + I(X)` where
+//! `I(X)` interpolates the opened values. After committing to `f(X)` and
+//! drawing a challenge `rho`, the prover opens
+//!
+//! ```text
+//! L(X) = P(X) - Z_S(rho) * f(X)
+//! ```
+//!
+//! at `rho`, whose evaluation `I(rho)` the verifier computes on its own.
+//! The proof consists of the commitment to `f(X)` and a single opening proof.
+use ark_std::{start_timer, end_timer};
+use ark_ff::FftField;
+use ark_poly::{
+    EvaluationDomain,
+    Polynomial,
+    UVPolynomial,
+    univariate::{DensePolynomial, DenseOrSparsePolynomial},
+};
+use ark_poly_commit::{LabeledPolynomial, PCRandomness};
+use ark_serialize::*;
+use itertools::Itertools;
+
+use crate::{
+    error::PosolError,
+    balance_sum::TranscriptProtocol,
+    commitment::HomomorphicCommitment,
+    label_polynomial, label_commitment,
+};
+
+/// Opening proof of a polynomial at a set of domain points.
+#[derive(CanonicalDeserialize, CanonicalSerialize, derivative::Derivative)]
+#[derivative(
+    Clone(bound = "PC::Commitment: Clone, PC::Proof: Clone"),
+    Debug(bound = "PC::Commitment: core::fmt::Debug, PC::Proof: core::fmt::Debug"),
+    Eq(bound = "PC::Commitment: Eq, PC::Proof: Eq"),
+    PartialEq(bound = "PC::Commitment: PartialEq, PC::Proof: PartialEq")
+)]
+pub struct MultiOpening<F, PC>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    /// Commitment to `f(X) = (P(X) - I(X)) / Z_S(X)`.
+    pub f_commit: PC::Commitment,
+    /// Opening proof of `P(X) - Z_S(rho) * f(X)` at `rho`.
+    pub opening: PC::Proof,
+}
+
+/// Open `P(X)` at `omega^i` for every `i` in `indices`.
+pub(crate) fn multi_open<F, D, PC, T>(
+    ck: &PC::CommitterKey,
+    domain: &D,
+    indices: &[usize],
+    labeled_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    commit: &PC::Commitment,
+) -> Result<MultiOpening<F, PC>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let points = opening_points(domain, indices)?;
+
+    let timer = start_timer!(|| "Multi-Point Opening");
+
+    // Divide P(X) by Z_S(X), the remainder is the interpolation I(X).
+    let zs_poly = vanishing_poly(&points);
+    let (f_poly, i_poly) = DenseOrSparsePolynomial::from(labeled_poly.polynomial())
+        .divide_with_q_and_r(&DenseOrSparsePolynomial::from(&zs_poly))
+        .ok_or(PosolError::Opening("divide P(X) by Z_S(X) failed".into()))?;
+    let evaluations = points.iter().map(|p| i_poly.evaluate(p)).collect_vec();
+    let labeled_f_poly = label_polynomial!(f_poly);
+
+    // Commit to f(X).
+    let (labeled_f_commit, _) =
+        PC::commit(ck, vec![&labeled_f_poly], None)
+            .map_err(|e| PosolError::Commitment(format!("commit to f(X) failed: {}", e)))?;
+    let f_commit = labeled_f_commit[0].commitment().clone();
+
+    let rho = challenge::<F, PC, T>(domain.size(), indices, &evaluations, commit, &f_commit);
+    let zs_eval = zs_poly.evaluate(&rho);
+
+    // Open L(X) = P(X) - Z_S(rho) * f(X) at rho.
+    let mut l_poly = labeled_poly.polynomial().clone();
+    l_poly -= &(labeled_f_poly.polynomial() * zs_eval);
+    let labeled_l_poly = label_polynomial!(l_poly);
+    let l_commit = PC::multi_scalar_mul(&[commit.clone(), f_commit.clone()], &[F::one(), -zs_eval]);
+    let labeled_l_commit = label_commitment!(l_commit, labeled_l_poly.label());
+    let randomness = <PC::Randomness as PCRandomness>::empty();
+    let opening = PC::open(
+        ck,
+        vec![&labeled_l_poly],
+        vec![&labeled_l_commit],
+        &rho,
+        F::one(),
+        vec![&randomness],
+        None,
+    )
+    .map_err(|e| PosolError::Opening(format!("open L(X) failed: {}", e)))?;
+
+    end_timer!(timer);
+
+    Ok(MultiOpening { f_commit, opening })
+}
+
+/// Check that `P(omega^i)` equals `evaluations[j]` for the `j`-th index `i` in `indices`.
+pub(crate) fn multi_check<F, D, PC, T>(
+    vk: &PC::VerifierKey,
+    domain: &D,
+    indices: &[usize],
+    evaluations: &[F],
+    commit: &PC::Commitment,
+    proof: &MultiOpening<F, PC>,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    if indices.len() != evaluations.len() {
+        return Err(PosolError::InputRange(format!(
+            "number of indices {} mismatches number of evaluations {}",
+            indices.len(),
+            evaluations.len(),
+        )));
+    }
+    let points = opening_points(domain, indices)?;
+
+    let timer = start_timer!(|| "Multi-Point Checking");
+
+    let rho = challenge::<F, PC, T>(domain.size(), indices, evaluations, commit, &proof.f_commit);
+    let zs_eval = vanishing_poly(&points).evaluate(&rho);
+    let i_eval = interpolation_eval(&points, evaluations, rho, zs_eval)?;
+
+    let l_commit = PC::multi_scalar_mul(&[commit.clone(), proof.f_commit.clone()], &[F::one(), -zs_eval]);
+    let labeled_l_commit = label_commitment!(l_commit);
+    match PC::check(
+        vk,
+        vec![&labeled_l_commit],
+        &rho,
+        vec![i_eval],
+        &proof.opening,
+        F::one(),
+        None,
+    ) {
+        Ok(true) => {
+            end_timer!(timer);
+            Ok(())
+        }
+        Ok(false) => Err(PosolError::Verification("multi-point opening verification failed".into())),
+        Err(e) => Err(PosolError::Verification(format!("check opening proof error: {}", e))),
+    }
+}
+
+/// Map distinct indices to their domain points.
+fn opening_points<F, D>(domain: &D, indices: &[usize]) -> Result<Vec<F>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    if indices.is_empty() {
+        return Err(PosolError::InputRange("no index to open".into()));
+    }
+    if let Some(&i) = indices.iter().find(|&&i| i >= domain.size()) {
+        return Err(PosolError::InputRange(format!("index {} out of domain size {}", i, domain.size())));
+    }
+    if !indices.iter().all_unique() {
+        return Err(PosolError::InputRange("duplicate indices to open".into()));
+    }
+
+    Ok(indices.iter().map(|&i| domain.element(i)).collect())
+}
+
+/// Compute `Z_S(X) = ∏ (X - x)` for `x` in `points`.
+fn vanishing_poly<F: FftField>(points: &[F]) -> DensePolynomial<F> {
+    points
+        .iter()
+        .fold(DensePolynomial::from_coefficients_vec(vec![F::one()]), |acc, &x| {
+            acc.naive_mul(&DensePolynomial::from_coefficients_vec(vec![-x, F::one()]))
+        })
+}
+
+/// Evaluate at `rho` the polynomial interpolating `evaluations` over `points`,
+/// with the barycentric formula `I(rho) = Z_S(rho) * Σ y_j / ((rho - x_j) * Z_S'(x_j))`.
+fn interpolation_eval<F: FftField>(
+    points: &[F],
+    evaluations: &[F],
+    rho: F,
+    zs_eval: F,
+) -> Result<F, PosolError> {
+    let mut sum = F::zero();
+    for (j, (&x, &y)) in points.iter().zip(evaluations).enumerate() {
+        let mut denominator = rho - x;
+        for (l, &x_l) in points.iter().enumerate() {
+            if l != j {
+                denominator *= x - x_l;
+            }
+        }
+        let denominator_inv = denominator
+            .inverse()
+            .ok_or(PosolError::Verification("challenge hits an opening point".into()))?;
+        sum += y * denominator_inv;
+    }
+
+    Ok(sum * zs_eval)
+}
+
+/// Fiat-Shamir challenge `rho` bound to the opening statement.
+fn challenge<F, PC, T>(
+    n: usize,
+    indices: &[usize],
+    evaluations: &[F],
+    commit: &PC::Commitment,
+    f_commit: &PC::Commitment,
+) -> F
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+    let transcript = &mut T::new("Multi-Point Opening");
+    transcript.append_u64("n", n as u64);
+    transcript.append_u64("size", indices.len() as u64);
+    for (&i, evaluation) in indices.iter().zip(evaluations) {
+        transcript.append_u64("index", i as u64);
+        transcript.append_scalar("evaluation", evaluation);
+    }
+    transcript.append_commitment("commit", commit);
+    transcript.append_commitment("f_commit", f_commit);
+
+    transcript.challenge_scalar("rho")
+}
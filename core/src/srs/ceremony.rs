@@ -0,0 +1,274 @@
+//! Multi-party powers of tau ceremony.
+//!
+//! Starting from `tau = 1`, every participant multiplies the current tau by a
+//! secret `s` of their own and publishes `s G`, `s H` and a Schnorr proof of
+//! knowledge of `s` bound to the powers they started from. As long as one
+//! participant discards their secret, nobody knows the final tau.
+use ark_std::{cfg_iter, start_timer, end_timer, vec, vec::Vec};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{One, UniformRand, Zero};
+use ark_poly_commit::kzg10::Commitment;
+use ark_serialize::*;
+use anyhow::{anyhow, Result};
+use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{
+    balance_sum::TranscriptProtocol,
+    commitment::{KZG10CommitterKey, KZG10VerifierKey},
+};
+use super::PowersOfTau;
+
+/// Public part of a single contribution.
+#[derive(Debug, Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Contribution<E: PairingEngine> {
+    /// `s G` of the contributed secret `s`
+    pub s_g: E::G1Affine,
+    /// `s H` of the contributed secret `s`
+    pub s_h: E::G2Affine,
+    /// Schnorr proof of knowledge of `s`
+    pub pok_r: E::G1Affine,
+    pub pok_z: E::Fr,
+    /// `tau G` of the powers after the contribution
+    pub tau_g: E::G1Affine,
+}
+
+/// Current powers of tau along with every contribution made to them.
+#[derive(Debug, Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Ceremony<E: PairingEngine> {
+    pub powers: PowersOfTau<E>,
+    pub contributions: Vec<Contribution<E>>,
+}
+
+impl<E: PairingEngine> Ceremony<E> {
+    /// Start a ceremony supporting polynomials up to `max_degree`, with `tau = 1`.
+    pub fn new(max_degree: usize) -> Self {
+        let g = E::G1Affine::prime_subgroup_generator();
+        let h = E::G2Affine::prime_subgroup_generator();
+
+        Self {
+            powers: PowersOfTau {
+                powers_of_g: vec![g; max_degree + 1],
+                h,
+                beta_h: h,
+            },
+            contributions: Vec::new(),
+        }
+    }
+
+    /// Multiply tau by a fresh secret sampled from `rng`, which is dropped afterwards.
+    pub fn contribute<T, R>(&mut self, rng: &mut R) -> Result<()>
+    where
+        T: TranscriptProtocol<E::Fr, Commitment<E>>,
+        R: RngCore + CryptoRng,
+    {
+        let timer = start_timer!(|| "Ceremony: Contributing");
+
+        let s = E::Fr::rand(rng);
+        if s.is_zero() {
+            return Err(anyhow!("zero secret sampled"));
+        }
+        let g = E::G1Affine::prime_subgroup_generator();
+        let s_g = g.mul(s).into_affine();
+        let s_h = E::G2Affine::prime_subgroup_generator().mul(s).into_affine();
+
+        // Schnorr proof of knowledge of s
+        let k = E::Fr::rand(rng);
+        let pok_r = g.mul(k).into_affine();
+        let c = challenge::<E, T>(self.contributions.len(), &self.powers.powers_of_g[1], &s_g, &pok_r);
+        let pok_z = k + c * s;
+
+        let mut s_powers = Vec::with_capacity(self.powers.powers_of_g.len());
+        let mut s_power = E::Fr::one();
+        for _ in 0..self.powers.powers_of_g.len() {
+            s_powers.push(s_power);
+            s_power *= s;
+        }
+        let mut powers_of_g = cfg_iter!(self.powers.powers_of_g)
+            .zip(s_powers)
+            .map(|(p, s_power)| p.mul(s_power))
+            .collect::<Vec<_>>();
+        E::G1Projective::batch_normalization(&mut powers_of_g);
+
+        self.powers.powers_of_g = powers_of_g.into_iter().map(|p| p.into()).collect();
+        self.powers.beta_h = self.powers.beta_h.mul(s).into_affine();
+        self.contributions.push(Contribution {
+            s_g,
+            s_h,
+            pok_r,
+            pok_z,
+            tau_g: self.powers.powers_of_g[1],
+        });
+
+        end_timer!(timer);
+
+        Ok(())
+    }
+
+    /// Check that `self` extends `prev` by exactly one valid contribution.
+    pub fn verify_contribution<T, R>(&self, prev: &Self, rng: &mut R) -> Result<()>
+    where
+        T: TranscriptProtocol<E::Fr, Commitment<E>>,
+        R: RngCore + CryptoRng,
+    {
+        let index = prev.contributions.len();
+        if self.contributions.len() != index + 1 || self.contributions[..index] != prev.contributions[..] {
+            return Err(anyhow!("ceremony does not extend the previous one by a single contribution"));
+        }
+        if self.powers.powers_of_g.len() != prev.powers.powers_of_g.len() {
+            return Err(anyhow!("ceremony degree changed"));
+        }
+
+        verify_link::<E, T>(index, &prev.powers.powers_of_g[1], &self.contributions[index])?;
+        self.verify_powers(rng)
+    }
+
+    /// Check every contribution and the resulting powers.
+    pub fn verify<T, R>(&self, rng: &mut R) -> Result<()>
+    where
+        T: TranscriptProtocol<E::Fr, Commitment<E>>,
+        R: RngCore + CryptoRng,
+    {
+        let timer = start_timer!(|| "Ceremony: Verifying");
+
+        if self.contributions.is_empty() {
+            return Err(anyhow!("ceremony has no contribution"));
+        }
+        let mut prev_tau_g = E::G1Affine::prime_subgroup_generator();
+        for (i, contribution) in self.contributions.iter().enumerate() {
+            verify_link::<E, T>(i, &prev_tau_g, contribution)?;
+            prev_tau_g = contribution.tau_g;
+        }
+        self.verify_powers(rng)?;
+
+        end_timer!(timer);
+
+        Ok(())
+    }
+
+    /// Verify the whole ceremony and build the KZG keys supporting polynomials
+    /// up to `supported_degree` from its final powers.
+    pub fn finalize<T, R>(
+        &self,
+        supported_degree: usize,
+        rng: &mut R,
+    ) -> Result<(KZG10CommitterKey<E>, KZG10VerifierKey<E>)>
+    where
+        T: TranscriptProtocol<E::Fr, Commitment<E>>,
+        R: RngCore + CryptoRng,
+    {
+        self.verify::<T, _>(rng)?;
+        self.powers.trim(supported_degree)
+    }
+
+    /// The powers must be consistent and end with the last contribution.
+    fn verify_powers<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<()> {
+        let last = self.contributions.last().expect("at least one contribution");
+        if self.powers.powers_of_g[1] != last.tau_g {
+            return Err(anyhow!("powers do not match the last contribution"));
+        }
+
+        self.powers.verify(rng)
+    }
+}
+
+/// Check the proof of knowledge of contribution `i` and that it multiplies
+/// `prev_tau_g` by the contributed secret.
+fn verify_link<E, T>(i: usize, prev_tau_g: &E::G1Affine, contribution: &Contribution<E>) -> Result<()>
+where
+    E: PairingEngine,
+    T: TranscriptProtocol<E::Fr, Commitment<E>>,
+{
+    let g = E::G1Affine::prime_subgroup_generator();
+    let h = E::G2Affine::prime_subgroup_generator();
+
+    let c = challenge::<E, T>(i, prev_tau_g, &contribution.s_g, &contribution.pok_r);
+    if g.mul(contribution.pok_z) != contribution.s_g.mul(c) + contribution.pok_r.into_projective() {
+        return Err(anyhow!("invalid proof of knowledge in contribution {}", i));
+    }
+    if contribution.s_g.is_zero() || E::pairing(contribution.s_g, h) != E::pairing(g, contribution.s_h) {
+        return Err(anyhow!("inconsistent secret in contribution {}", i));
+    }
+    if E::pairing(contribution.tau_g, h) != E::pairing(*prev_tau_g, contribution.s_h) {
+        return Err(anyhow!("contribution {} does not extend the previous powers", i));
+    }
+
+    Ok(())
+}
+
+/// Fiat-Shamir challenge of the Schnorr proof, bound to the previous powers.
+fn challenge<E, T>(i: usize, prev_tau_g: &E::G1Affine, s_g: &E::G1Affine, pok_r: &E::G1Affine) -> E::Fr
+where
+    E: PairingEngine,
+    T: TranscriptProtocol<E::Fr, Commitment<E>>,
+{
+    let transcript = &mut T::new("Powers of Tau Ceremony");
+    transcript.append_u64("index", i as u64);
+    transcript.append_commitment("prev_tau_g", &Commitment(*prev_tau_g));
+    transcript.append_commitment("s_g", &Commitment(*s_g));
+    transcript.append_commitment("pok_r", &Commitment(*pok_r));
+
+    transcript.challenge_scalar("c")
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_std::{test_rng, rand::Rng};
+
+    use crate::{balance_sum::{self, MerlinTranscript}, commitment::KZG10};
+    use super::*;
+
+    #[test]
+    fn test_ceremony() {
+        let rng = &mut test_rng();
+        let n = 16;
+        let mut ceremony = Ceremony::<Bn254>::new(n + 3);
+        assert!(ceremony.verify::<MerlinTranscript, _>(rng).is_err());
+
+        let mut prev = ceremony.clone();
+        for _ in 0..3 {
+            ceremony.contribute::<MerlinTranscript, _>(rng).unwrap();
+            ceremony.verify_contribution::<MerlinTranscript, _>(&prev, rng).unwrap();
+            prev = ceremony.clone();
+        }
+
+        let mut bytes = Vec::new();
+        ceremony.serialize(&mut bytes).unwrap();
+        let ceremony = Ceremony::<Bn254>::deserialize(&bytes[..]).unwrap();
+
+        let (ck, cvk) = ceremony.finalize::<MerlinTranscript, _>(n + 3, rng).unwrap();
+        let balances = (0..n).map(|_| rng.gen_range(0..n as u64)).collect::<Vec<_>>();
+        let (labeled_t_poly, t_commit) =
+            balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+        let (m, proof, _) =
+            balance_sum::prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+        proof.verify::<MerlinTranscript>(&cvk, n, &t_commit, m).unwrap();
+
+        // A contribution copied from the previous ceremony state is rejected
+        let mut replayed = ceremony.clone();
+        let copied = replayed.contributions[1].clone();
+        replayed.contributions[2].s_g = copied.s_g;
+        replayed.contributions[2].pok_r = copied.pok_r;
+        replayed.contributions[2].pok_z = copied.pok_z;
+        assert!(replayed.verify::<MerlinTranscript, _>(rng).is_err());
+
+        // Powers not produced by the recorded contributions are rejected
+        let mut forged = ceremony.clone();
+        let s = Fr::rand(rng);
+        forged.powers.powers_of_g[1] = forged.powers.powers_of_g[1].mul(s).into_affine();
+        forged.contributions[2].tau_g = forged.powers.powers_of_g[1];
+        assert!(forged.verify::<MerlinTranscript, _>(rng).is_err());
+
+        assert!(ceremony.verify_contribution::<MerlinTranscript, _>(&ceremony, rng).is_err());
+    }
+}
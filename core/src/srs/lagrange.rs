@@ -0,0 +1,205 @@
+//! Commitments to the Lagrange basis of an evaluation domain.
+use ark_std::{start_timer, end_timer, vec::Vec};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::Field;
+use ark_poly::EvaluationDomain;
+use ark_poly_commit::{ipa_pc, kzg10::Commitment};
+use ark_serialize::*;
+
+use crate::{
+    commitment::{HomomorphicCommitment, KZG10CommitterKey},
+    error::{domain, PosolError},
+};
+
+/// Commitments to the Lagrange basis polynomials `L_i(X)` of a domain of size `n`,
+/// so that the commitment to a polynomial changes by `delta * [L_i]` when its
+/// evaluation at the `i`-th element of the domain changes by `delta`.
+#[derive(Debug, Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct LagrangeBasis<C: CanonicalSerialize + CanonicalDeserialize> {
+    pub commitments: Vec<C>,
+}
+
+impl<C: CanonicalSerialize + CanonicalDeserialize> LagrangeBasis<C> {
+    /// Size of the domain.
+    pub fn size(&self) -> usize {
+        self.commitments.len()
+    }
+
+    /// Commit to the polynomial of evaluations `evals` over the domain, padded
+    /// with zeros, without interpolating it. Only `evals.len()` elements are used.
+    pub fn commit<F, PC>(&self, evals: &[F]) -> Result<C, PosolError>
+    where
+        F: Field,
+        PC: HomomorphicCommitment<F, Commitment = C>,
+    {
+        if evals.len() > self.size() {
+            return Err(PosolError::InputRange(format!(
+                "{} evaluations out of domain of size {}",
+                evals.len(),
+                self.size(),
+            )));
+        }
+
+        let timer = start_timer!(|| "Lagrange Basis: Committing");
+        let commit = PC::multi_scalar_mul(&self.commitments[..evals.len()], evals);
+        end_timer!(timer);
+
+        Ok(commit)
+    }
+
+    /// Update `commit` by the evaluation `deltas`, given as `(index, new - old)`.
+    pub fn update<F, PC>(&self, commit: &C, deltas: &[(usize, F)]) -> Result<C, PosolError>
+    where
+        F: Field,
+        C: Clone,
+        PC: HomomorphicCommitment<F, Commitment = C>,
+    {
+        let mut commitments = Vec::with_capacity(deltas.len() + 1);
+        let mut scalars = Vec::with_capacity(deltas.len() + 1);
+        commitments.push(commit.clone());
+        scalars.push(F::one());
+        for &(i, delta) in deltas {
+            let basis = self.commitments
+                .get(i)
+                .ok_or_else(|| PosolError::InputRange(format!("index {} out of domain of size {}", i, self.size())))?;
+            commitments.push(basis.clone());
+            scalars.push(delta);
+        }
+
+        Ok(PC::multi_scalar_mul(&commitments, &scalars))
+    }
+}
+
+impl<E: PairingEngine> LagrangeBasis<Commitment<E>> {
+    /// Derive the Lagrange basis from the monomial KZG key.
+    pub fn from_kzg_key<D>(ck: &KZG10CommitterKey<E>, n: usize) -> Result<Self, PosolError>
+    where
+        D: EvaluationDomain<E::Fr>,
+    {
+        let points = ifft_points::<_, D>(&ck.powers_of_g, n)?;
+
+        Ok(Self {
+            commitments: points.into_iter().map(Commitment).collect(),
+        })
+    }
+}
+
+impl<G: AffineCurve> LagrangeBasis<ipa_pc::Commitment<G>> {
+    /// Derive the Lagrange basis from the monomial IPA key.
+    pub fn from_ipa_key<D>(ck: &ipa_pc::CommitterKey<G>, n: usize) -> Result<Self, PosolError>
+    where
+        D: EvaluationDomain<G::ScalarField>,
+    {
+        let points = ifft_points::<_, D>(&ck.comm_key, n)?;
+
+        Ok(Self {
+            commitments: points
+                .into_iter()
+                .map(|comm| ipa_pc::Commitment { comm, shifted_comm: None })
+                .collect(),
+        })
+    }
+}
+
+/// Inverse FFT over the group, `[L_i] = 1/n sum_j w^(-ij) [X^j]` for the
+/// commitments `[X^j]` to the monomials.
+fn ifft_points<G, D>(powers: &[G], n: usize) -> Result<Vec<G>, PosolError>
+where
+    G: AffineCurve,
+    D: EvaluationDomain<G::ScalarField>,
+{
+    let domain = domain::<G::ScalarField, D>(n)?;
+    if powers.len() < n {
+        return Err(PosolError::Commitment(format!(
+            "committer key supports degree {}, {} required",
+            powers.len() - 1,
+            n - 1,
+        )));
+    }
+
+    let timer = start_timer!(|| "Lagrange Basis: Group IFFT");
+
+    let mut points = powers[..n]
+        .iter()
+        .map(|p| p.into_projective())
+        .collect::<Vec<_>>();
+    domain.ifft_in_place(&mut points);
+    G::Projective::batch_normalization(&mut points);
+
+    end_timer!(timer);
+
+    Ok(points.into_iter().map(|p| p.into()).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_ff::UniformRand;
+    use ark_pallas::{Affine as PallasAffine, Fr as PallasFr};
+    use ark_poly::{GeneralEvaluationDomain, univariate::DensePolynomial};
+    use ark_poly_commit::{PCCommitment, PolynomialCommitment};
+    use ark_std::test_rng;
+    use blake2::Blake2s;
+
+    use crate::{commitment::{KZG10, IPA}, label_polynomial, util::poly_from_evals};
+    use super::*;
+
+    #[test]
+    fn test_lagrange_basis() {
+        let rng = &mut test_rng();
+        let n = 16;
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, _) = KZG10::<Bn254>::trim(&pp, n, 0, None).unwrap();
+        let basis = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, n).unwrap();
+        assert_eq!(basis.size(), n);
+
+        let domain = GeneralEvaluationDomain::<Fr>::new(n).unwrap();
+        let mut evals = vec![Fr::from(0u64); n];
+        for i in [0, 5, n - 1] {
+            evals[i] = Fr::from(1u64);
+            let poly: DensePolynomial<_> = poly_from_evals(&domain, evals.clone());
+            let labeled_poly = label_polynomial!(poly);
+            let (commits, _) = KZG10::<Bn254>::commit(&ck, vec![&labeled_poly], None).unwrap();
+            assert_eq!(commits[0].commitment(), &basis.commitments[i]);
+            evals[i] = Fr::from(0u64);
+        }
+
+        let zero = Commitment::<Bn254>::empty();
+        let delta = Fr::rand(rng);
+        let updated = basis.update::<_, KZG10<Bn254>>(&zero, &[(3, delta)]).unwrap();
+        assert_eq!(updated.0, basis.commitments[3].0.mul(delta).into_affine());
+        let res = basis.update::<_, KZG10<Bn254>>(&zero, &[(n, delta)]);
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+
+        // a key too small for the domain, and a domain that is not a power of two
+        let res = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, 2 * n);
+        assert!(matches!(res, Err(PosolError::Commitment(_))));
+        let res = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, n - 1);
+        assert!(matches!(res, Err(PosolError::Domain { .. })));
+    }
+
+    #[test]
+    fn test_commit_ipa() {
+        let rng = &mut test_rng();
+        let n = 16;
+        let pp = IPA::<PallasAffine, Blake2s>::setup(n, None, rng).unwrap();
+        let (ck, _) = IPA::<PallasAffine, Blake2s>::trim(&pp, n, 0, None).unwrap();
+        let basis = LagrangeBasis::from_ipa_key::<GeneralEvaluationDomain<_>>(&ck, n).unwrap();
+
+        // evaluations over part of the domain, padded with zeros
+        let domain = GeneralEvaluationDomain::<PallasFr>::new(n).unwrap();
+        let mut evals = (0..n / 2).map(|_| PallasFr::rand(rng)).collect::<Vec<_>>();
+        let commit = basis.commit::<_, IPA<PallasAffine, Blake2s>>(&evals).unwrap();
+
+        evals.resize(n, PallasFr::from(0u64));
+        let poly: DensePolynomial<_> = poly_from_evals(&domain, evals.clone());
+        let labeled_poly = label_polynomial!(poly);
+        let (commits, _) =
+            IPA::<PallasAffine, Blake2s>::commit(&ck, vec![&labeled_poly], None).unwrap();
+        assert_eq!(commits[0].commitment(), &commit);
+
+        evals.push(PallasFr::from(0u64));
+        let res = basis.commit::<_, IPA<PallasAffine, Blake2s>>(&evals);
+        assert!(matches!(res, Err(PosolError::InputRange(_))));
+    }
+}
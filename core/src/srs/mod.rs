@@ -0,0 +1,154 @@
+//! Structured reference strings: powers of tau from public or own ceremonies,
+//! and keys derived from them
+pub mod ceremony;
+pub mod lagrange;
+pub mod ptau;
+
+use ark_std::{collections::BTreeMap, start_timer, end_timer};
+use ark_ec::{msm::VariableBaseMSM, AffineCurve, PairingEngine};
+use ark_ff::{PrimeField, UniformRand, Zero};
+use ark_poly_commit::{kzg10::UniversalParams, PolynomialCommitment};
+use ark_serialize::*;
+use anyhow::{anyhow, Result};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::commitment::{KZG10, KZG10CommitterKey, KZG10VerifierKey};
+
+/// Powers of a secret tau, `{ tau^i G }` in G1 together with `H` and `tau H` in G2.
+#[derive(Debug, Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PowersOfTau<E: PairingEngine> {
+    pub powers_of_g: Vec<E::G1Affine>,
+    pub h: E::G2Affine,
+    pub beta_h: E::G2Affine,
+}
+
+impl<E: PairingEngine> PowersOfTau<E> {
+    /// Maximum degree of polynomials that can be committed with these powers.
+    pub fn max_degree(&self) -> usize {
+        self.powers_of_g.len() - 1
+    }
+
+    /// Check that the powers start from the standard generators and are consistent,
+    /// i.e. `e(tau^(i+1) G, H) = e(tau^i G, tau H)` for every `i`. All the checks
+    /// are batched with random scalars into a single pairing equation.
+    pub fn verify<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<()> {
+        let timer = start_timer!(|| "SRS: Verifying powers of tau");
+
+        if self.powers_of_g.len() < 2 {
+            return Err(anyhow!("at least two powers of tau are required"));
+        }
+        if self.powers_of_g[0] != E::G1Affine::prime_subgroup_generator() {
+            return Err(anyhow!("first power of tau in G1 is not the generator"));
+        }
+        if self.h != E::G2Affine::prime_subgroup_generator() {
+            return Err(anyhow!("first power of tau in G2 is not the generator"));
+        }
+        if self.beta_h.is_zero() || self.beta_h == self.h {
+            return Err(anyhow!("degenerate tau in G2"));
+        }
+
+        let scalars = (1..self.powers_of_g.len())
+            .map(|_| E::Fr::rand(rng).into_repr())
+            .collect::<Vec<_>>();
+        let shifted = VariableBaseMSM::multi_scalar_mul(&self.powers_of_g[1..], &scalars);
+        let unshifted = VariableBaseMSM::multi_scalar_mul(&self.powers_of_g[..self.max_degree()], &scalars);
+
+        let valid = E::pairing(shifted, self.h) == E::pairing(unshifted, self.beta_h);
+
+        end_timer!(timer);
+
+        if valid {
+            Ok(())
+        } else {
+            Err(anyhow!("powers of tau are inconsistent"))
+        }
+    }
+
+    /// Build the KZG keys supporting polynomials up to `supported_degree`.
+    ///
+    /// Ceremonies publish no powers of a second secret, so hiding commitments are
+    /// not supported: `gamma G` is set to zero, which keeps commitments binding.
+    pub fn trim(&self, supported_degree: usize) -> Result<(KZG10CommitterKey<E>, KZG10VerifierKey<E>)> {
+        let mut powers_of_gamma_g = BTreeMap::new();
+        powers_of_gamma_g.insert(0, E::G1Affine::zero());
+        powers_of_gamma_g.insert(1, E::G1Affine::zero());
+
+        let pp = UniversalParams::<E> {
+            powers_of_g: self.powers_of_g.clone(),
+            powers_of_gamma_g,
+            h: self.h,
+            beta_h: self.beta_h,
+            neg_powers_of_h: BTreeMap::new(),
+            prepared_h: self.h.into(),
+            prepared_beta_h: self.beta_h.into(),
+        };
+
+        KZG10::<E>::trim(&pp, supported_degree, 0, None)
+            .map_err(|e| anyhow!("trim powers of tau failed: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{One, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_std::{test_rng, rand::Rng};
+
+    use crate::{balance_sum, commitment::KZG10};
+    use super::*;
+
+    pub(super) fn powers_of_tau(tau: Fr, max_degree: usize) -> PowersOfTau<Bn254> {
+        let g = <Bn254 as PairingEngine>::G1Affine::prime_subgroup_generator();
+        let h = <Bn254 as PairingEngine>::G2Affine::prime_subgroup_generator();
+
+        let mut power = Fr::one();
+        let powers_of_g = (0..=max_degree)
+            .map(|_| {
+                let p = g.mul(power).into_affine();
+                power *= tau;
+                p
+            })
+            .collect();
+
+        PowersOfTau {
+            powers_of_g,
+            h,
+            beta_h: h.mul(tau).into_affine(),
+        }
+    }
+
+    #[test]
+    fn test_verify_and_trim() {
+        let rng = &mut test_rng();
+        let n = 16;
+        // Leaves room for the blinding feature
+        let powers = powers_of_tau(Fr::rand(rng), n + 3);
+        powers.verify(rng).unwrap();
+
+        let (ck, cvk) = powers.trim(n + 3).unwrap();
+        let balances = (0..n).map(|_| rng.gen_range(0..n as u64)).collect::<Vec<_>>();
+        let (labeled_t_poly, t_commit) =
+            balance_sum::precompute::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n).unwrap();
+        let (m, proof, _) =
+            balance_sum::prove::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, balance_sum::MerlinTranscript, _>(
+                &ck,
+                n,
+                &labeled_t_poly,
+                &t_commit,
+                &balances,
+                rng,
+            ).unwrap();
+        proof.verify::<balance_sum::MerlinTranscript>(&cvk, n, &t_commit, m).unwrap();
+
+        // A single tampered power is caught by the batched check
+        let mut tampered = powers.clone();
+        tampered.powers_of_g[n / 2] = tampered.powers_of_g[n / 2 + 1];
+        assert!(tampered.verify(rng).is_err());
+
+        let mut tampered = powers;
+        tampered.beta_h = tampered.h.mul(Fr::rand(rng)).into_affine();
+        assert!(tampered.verify(rng).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Reader for the snarkjs `.ptau` format used by the perpetual powers of tau ceremony.
+//!
+//! The file starts with the magic `ptau`, a version and the number of sections,
+//! each section being a `u32` type and a `u64` size followed by its data. The
+//! header section holds the byte size `n8` and modulus of the base field and the
+//! `power` of the ceremony, section 2 holds `2^(power+1) - 1` powers of tau in G1
+//! and section 3 holds `2^power` powers of tau in G2. Points are uncompressed,
+//! with every base field element little-endian in Montgomery form.
+use ark_std::{io::Read, vec, vec::Vec};
+use ark_ec::{AffineCurve, PairingEngine};
+use ark_ff::{BigInteger, FpParameters, Field, PrimeField};
+use anyhow::{anyhow, Result};
+
+use super::PowersOfTau;
+
+const MAGIC: &[u8; 4] = b"ptau";
+const HEADER_SECTION: u32 = 1;
+const TAU_G1_SECTION: u32 = 2;
+const TAU_G2_SECTION: u32 = 3;
+
+/// Read the powers of tau supporting polynomials up to `max_degree` from a `.ptau` file.
+///
+/// Every point is checked to be on the curve and in the prime order subgroup,
+/// but the consistency of the powers must be checked with [`PowersOfTau::verify`].
+pub fn read<E, R>(mut reader: R, max_degree: usize) -> Result<PowersOfTau<E>>
+where
+    E: PairingEngine,
+    R: Read,
+{
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(anyhow!("not a ptau file"));
+    }
+    let _version = read_u32(&mut reader)?;
+    let num_sections = read_u32(&mut reader)?;
+
+    let modulus = <<E::Fq as PrimeField>::Params as FpParameters>::MODULUS.to_bytes_le();
+    let n8 = modulus.len();
+    // Montgomery form of x is x * R
+    let r_inv = E::Fq::from_repr(<<E::Fq as PrimeField>::Params as FpParameters>::R)
+        .and_then(|r| r.inverse())
+        .expect("R is invertible");
+
+    let mut power = None;
+    let mut powers_of_g = None;
+    let mut powers_of_h = None;
+    for _ in 0..num_sections {
+        let section = read_u32(&mut reader)?;
+        let size = read_u64(&mut reader)? as usize;
+        match section {
+            HEADER_SECTION => {
+                if read_u32(&mut reader)? as usize != n8 {
+                    return Err(anyhow!("ptau file is for another curve"));
+                }
+                let mut q = vec![0u8; n8];
+                reader.read_exact(&mut q)?;
+                if q != modulus {
+                    return Err(anyhow!("ptau file is for another curve"));
+                }
+                power = Some(read_u32(&mut reader)?);
+                skip_rest(&mut reader, size, 4 + n8 + 4)?;
+            }
+            TAU_G1_SECTION => {
+                let power = power.ok_or(anyhow!("ptau header must precede the powers"))?;
+                let count = (1usize << (power + 1)) - 1;
+                if max_degree >= count {
+                    return Err(anyhow!(
+                        "ptau file of power {} supports degree up to {}, {} required",
+                        power,
+                        count - 1,
+                        max_degree,
+                    ));
+                }
+                let points = (0..=max_degree)
+                    .map(|_| read_point::<E::G1Affine, E::Fq, _>(&mut reader, n8, r_inv, 2))
+                    .collect::<Result<Vec<_>>>()?;
+                skip_rest(&mut reader, size, (max_degree + 1) * 2 * n8)?;
+                powers_of_g = Some(points);
+            }
+            TAU_G2_SECTION => {
+                let points = (0..2)
+                    .map(|_| read_point::<E::G2Affine, E::Fq, _>(&mut reader, n8, r_inv, 4))
+                    .collect::<Result<Vec<_>>>()?;
+                skip_rest(&mut reader, size, 2 * 4 * n8)?;
+                powers_of_h = Some(points);
+            }
+            _ => skip(&mut reader, size)?,
+        }
+
+        if powers_of_g.is_some() && powers_of_h.is_some() {
+            break;
+        }
+    }
+
+    let powers_of_g = powers_of_g.ok_or(anyhow!("ptau file has no powers of tau in G1"))?;
+    let powers_of_h = powers_of_h.ok_or(anyhow!("ptau file has no powers of tau in G2"))?;
+
+    Ok(PowersOfTau {
+        powers_of_g,
+        h: powers_of_h[0],
+        beta_h: powers_of_h[1],
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn skip<R: Read>(reader: &mut R, mut len: usize) -> Result<()> {
+    let mut buf = [0u8; 4096];
+    while len > 0 {
+        let chunk = len.min(buf.len());
+        reader.read_exact(&mut buf[..chunk])?;
+        len -= chunk;
+    }
+
+    Ok(())
+}
+
+/// Skip the rest of a section of `size` bytes, `read` of which have been read.
+fn skip_rest<R: Read>(reader: &mut R, size: usize, read: usize) -> Result<()> {
+    let len = size
+        .checked_sub(read)
+        .ok_or(anyhow!("ptau section is too short"))?;
+    skip(reader, len)
+}
+
+/// Read an affine point of `components` base prime field elements, as
+/// `G::deserialize_unchecked` expects them in canonical form.
+fn read_point<G, F, R>(reader: &mut R, n8: usize, r_inv: F, components: usize) -> Result<G>
+where
+    G: AffineCurve,
+    F: PrimeField,
+    R: Read,
+{
+    let mut canonical = Vec::with_capacity(components * n8);
+    let mut bytes = vec![0u8; n8];
+    for _ in 0..components {
+        reader.read_exact(&mut bytes)?;
+        let mut repr = F::BigInt::default();
+        repr.as_mut()
+            .iter_mut()
+            .zip(bytes.chunks(8))
+            .for_each(|(limb, chunk)| {
+                let mut limb_bytes = [0u8; 8];
+                limb_bytes[..chunk.len()].copy_from_slice(chunk);
+                *limb = u64::from_le_bytes(limb_bytes);
+            });
+        let element = F::from_repr(repr)
+            .ok_or(anyhow!("ptau field element is not reduced"))?;
+        (element * r_inv).serialize(&mut canonical)?;
+    }
+
+    let point = G::deserialize_unchecked(&canonical[..])?;
+    // Compressed deserialization recomputes y from x and checks the subgroup
+    let mut compressed = Vec::new();
+    point.serialize(&mut compressed)?;
+    let checked = G::deserialize(&compressed[..])
+        .map_err(|_| anyhow!("ptau point is not in the prime order subgroup"))?;
+    if checked != point {
+        return Err(anyhow!("ptau point is not on the curve"));
+    }
+
+    Ok(point)
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fq, Fr, G2Affine};
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::{One, UniformRand};
+    use ark_std::test_rng;
+
+    use super::*;
+    use super::super::test::powers_of_tau;
+
+    fn write_fq(bytes: &mut Vec<u8>, fq: Fq) {
+        let r = Fq::from_repr(<<Fq as PrimeField>::Params as FpParameters>::R).unwrap();
+        bytes.extend((fq * r).into_repr().to_bytes_le());
+    }
+
+    fn write_section(bytes: &mut Vec<u8>, section: u32, data: &[u8]) {
+        bytes.extend(section.to_le_bytes());
+        bytes.extend((data.len() as u64).to_le_bytes());
+        bytes.extend(data);
+    }
+
+    fn write_ptau(tau: Fr, power: u32) -> Vec<u8> {
+        let powers = powers_of_tau(tau, (1 << (power + 1)) - 2);
+        let h = G2Affine::prime_subgroup_generator();
+
+        let mut header = vec![];
+        header.extend(32u32.to_le_bytes());
+        header.extend(<<Fq as PrimeField>::Params as FpParameters>::MODULUS.to_bytes_le());
+        header.extend(power.to_le_bytes());
+        header.extend(power.to_le_bytes());
+
+        let mut tau_g1 = vec![];
+        for p in powers.powers_of_g.iter() {
+            write_fq(&mut tau_g1, p.x);
+            write_fq(&mut tau_g1, p.y);
+        }
+
+        let mut tau_g2 = vec![];
+        let mut tau_power = Fr::one();
+        for _ in 0..(1 << power) {
+            let p = h.mul(tau_power).into_affine();
+            write_fq(&mut tau_g2, p.x.c0);
+            write_fq(&mut tau_g2, p.x.c1);
+            write_fq(&mut tau_g2, p.y.c0);
+            write_fq(&mut tau_g2, p.y.c1);
+            tau_power *= tau;
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(4u32.to_le_bytes());
+        write_section(&mut bytes, HEADER_SECTION, &header);
+        write_section(&mut bytes, TAU_G1_SECTION, &tau_g1);
+        write_section(&mut bytes, TAU_G2_SECTION, &tau_g2);
+        write_section(&mut bytes, 4, &[0u8; 64]);
+
+        bytes
+    }
+
+    #[test]
+    fn test_read() {
+        let rng = &mut test_rng();
+        let tau = Fr::rand(rng);
+        let bytes = write_ptau(tau, 3);
+
+        let powers = read::<Bn254, _>(&bytes[..], 10).unwrap();
+        assert_eq!(powers, powers_of_tau(tau, 10));
+        powers.verify(rng).unwrap();
+
+        // 2^4 - 1 powers support degree up to 14
+        assert!(read::<Bn254, _>(&bytes[..], 15).is_err());
+
+        // A point off the curve is rejected
+        let mut tampered = bytes.clone();
+        // Magic, version, section count, header section, G1 section header, x
+        let first_y = 12 + (12 + 44) + 12 + 32;
+        tampered[first_y] ^= 1;
+        assert!(read::<Bn254, _>(&tampered[..], 10).is_err());
+
+        assert!(read::<Bn254, _>(&bytes[1..], 10).is_err());
+    }
+}
@@ -2,33 +2,35 @@ use ark_std::{start_timer, end_timer};
 use ark_ff::FftField;
 use ark_poly::{EvaluationDomain, univariate::DensePolynomial};
 use ark_poly_commit::{LabeledPolynomial, PCRandomness};
-use anyhow::{anyhow, Result};
 
 use crate::{
+    error::{domain, PosolError},
+    balance_sum::TranscriptProtocol,
     commitment::HomomorphicCommitment,
-    util::poly_from_evals,
+    opening::{self, MultiOpening},
+    srs::lagrange::LagrangeBasis,
+    util::{add_lagrange_deltas, poly_from_evals},
     label_polynomial, label_commitment,
 };
 
+pub use posol_verifier::tag::derive;
+
+#[allow(clippy::type_complexity)]
 pub fn commit<F, D, PC>(
     ck: &PC::CommitterKey,
     n: usize,
     tags: &[&[u8]],
-) -> Result<(PC::Commitment, LabeledPolynomial<F, DensePolynomial<F>>)>
+) -> Result<(PC::Commitment, LabeledPolynomial<F, DensePolynomial<F>>), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
-    assert!(tags.len() <= n);
+    if tags.len() > n {
+        return Err(PosolError::InputRange(format!("{} tags out of domain of size {}", tags.len(), n)));
+    }
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    let domain = domain::<F, D>(n)?;
 
     let timer = start_timer!(|| "Tag: Committing");
 
@@ -36,9 +38,9 @@ where
         .iter()
         .map(|&reader| {
             F::read(reader)
-                .map_err(|e| anyhow!("failed to read tag: {}", e))
+                .map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))
         })
-        .collect::<Result<Vec<_>>>()?;
+        .collect::<Result<Vec<_>, PosolError>>()?;
     tag_evals.resize(n, F::zero());
     
     let tag_poly = poly_from_evals(&domain, tag_evals);
@@ -48,7 +50,7 @@ where
     let (labeled_tag_commit, _) =
         PC::commit(ck, vec![&labeled_tag_poly], None)
         .map_err(|e| {
-            anyhow!("commit to tag(x) failed: {}", e)
+            PosolError::Commitment(format!("commit to tag(x) failed: {}", e))
         })?;
 
     end_timer!(timer);
@@ -59,31 +61,111 @@ where
     ))
 }
 
+/// Commit to tag(X) directly from the tags with the Lagrange basis of the
+/// domain, without interpolating tag(X). Padding is not committed to, so the
+/// cost only depends on the number of tags. The commitment is the same as that
+/// of [`commit`], which is still needed to open it.
+pub fn commit_evals<F, PC>(
+    basis: &LagrangeBasis<PC::Commitment>,
+    tags: &[&[u8]],
+) -> Result<PC::Commitment, PosolError>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    let timer = start_timer!(|| "Tag: Committing Evaluations");
+
+    let tag_evals = tags
+        .iter()
+        .map(|&reader| {
+            F::read(reader)
+                .map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))
+        })
+        .collect::<Result<Vec<_>, PosolError>>()?;
+    let tag_commit = basis.commit::<F, PC>(&tag_evals)?;
+
+    end_timer!(timer);
+
+    Ok(tag_commit)
+}
+
+/// Update the tag commitment of a previous snapshot by the changed tags, given
+/// as `(index, old tag, new tag)`, in time linear in the number of changes.
+pub fn update_commit<F, PC>(
+    basis: &LagrangeBasis<PC::Commitment>,
+    tag_commit: &PC::Commitment,
+    updates: &[(usize, &[u8], &[u8])],
+) -> Result<PC::Commitment, PosolError>
+where
+    F: FftField,
+    PC: HomomorphicCommitment<F>,
+{
+    let timer = start_timer!(|| "Tag: Updating Commitment");
+
+    let deltas = tag_deltas(updates)?;
+    let tag_commit = basis.update::<F, PC>(tag_commit, &deltas)?;
+
+    end_timer!(timer);
+
+    Ok(tag_commit)
+}
+
+/// Update tag(X) by the changed tags, so that the updated commitment can be opened.
+pub fn update_poly<F, D>(
+    n: usize,
+    tag_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    updates: &[(usize, &[u8], &[u8])],
+) -> Result<LabeledPolynomial<F, DensePolynomial<F>>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Tag: Updating Polynomial");
+
+    let deltas = tag_deltas(updates)?;
+    if let Some(&(i, _)) = deltas.iter().find(|(i, _)| *i >= n) {
+        return Err(PosolError::InputRange(format!("index {} out of domain of size {}", i, n)));
+    }
+    let updated_poly = add_lagrange_deltas(&domain, tag_poly.polynomial(), &deltas);
+
+    end_timer!(timer);
+
+    Ok(LabeledPolynomial::new(tag_poly.label().clone(), updated_poly, None, None))
+}
+
+fn tag_deltas<F: FftField>(updates: &[(usize, &[u8], &[u8])]) -> Result<Vec<(usize, F)>, PosolError> {
+    updates
+        .iter()
+        .map(|&(i, old, new)| {
+            let old = F::read(old).map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))?;
+            let new = F::read(new).map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))?;
+            Ok((i, new - old))
+        })
+        .collect()
+}
+
 pub fn individual_open<F, D, PC>(
     ck: &PC::CommitterKey,
     n: usize,
     i: usize,
     tag_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
     tag_commit: &PC::Commitment,
-) -> Result<PC::Proof>
+) -> Result<PC::Proof, PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    let domain = domain::<F, D>(n)?;
 
     let timer = start_timer!(|| "Tag: Individual Opening");
     
     let point = domain.element(i);
-    let labeled_tag_commit = label_commitment!(tag_commit);
+    let labeled_tag_commit = label_commitment!(tag_commit, tag_poly.label());
     let randomness = <PC::Randomness as PCRandomness>::empty();
     let proof = PC::open(
         ck,
@@ -94,7 +176,7 @@ where
         vec![&randomness],
         None,
     )
-    .map_err(|e| anyhow!("open tag(X) failed: {}", e))?;
+    .map_err(|e| PosolError::Opening(format!("open tag(X) failed: {}", e)))?;
 
     end_timer!(timer);
 
@@ -108,26 +190,20 @@ pub fn individual_verify<F, D, PC>(
     tag: &[u8],
     tag_commit: &PC::Commitment,
     proof: &PC::Proof,
-) -> Result<()>
+) -> Result<(), PosolError>
 where
     F: FftField,
     D: EvaluationDomain<F>,
     PC: HomomorphicCommitment<F>,
 {
-    assert!(n.is_power_of_two());
 
-    let domain = D::new(n)
-        .ok_or(anyhow!(
-            "log size of group: {}, 2-adicity: {}",
-            n.trailing_zeros(),
-            <F::FftParams as ark_ff::FftParameters>::TWO_ADICITY,
-        ))?;
+    let domain = domain::<F, D>(n)?;
 
     let timer = start_timer!(|| "Tag: Individual Verifying");
 
     let point = domain.element(i);
     let evaluation = F::read(tag)
-        .map_err(|e| anyhow!("failed to read tag: {}", e))?;
+        .map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))?;
     let labeled_tag_commit = label_commitment!(tag_commit);
     match PC::check(
         vk,
@@ -142,43 +218,116 @@ where
             end_timer!(timer);
             Ok(())
         }
-        Ok(false) => Err(anyhow!("individual tag verification failed")),
-        Err(e) => Err(anyhow!("check opening proof error: {}", e)),
+        Ok(false) => Err(PosolError::Verification("individual tag verification failed".into())),
+        Err(e) => Err(PosolError::Verification(format!("check opening proof error: {}", e))),
     }
 }
 
+/// Open tag(X) at the domain points of every index in `indices` with one proof.
+pub fn multi_open<F, D, PC, T>(
+    ck: &PC::CommitterKey,
+    n: usize,
+    indices: &[usize],
+    tag_poly: &LabeledPolynomial<F, DensePolynomial<F>>,
+    tag_commit: &PC::Commitment,
+) -> Result<MultiOpening<F, PC>, PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Tag: Multiple Opening");
+
+    let proof = opening::multi_open::<F, D, PC, T>(ck, &domain, indices, tag_poly, tag_commit)?;
+
+    end_timer!(timer);
+
+    Ok(proof)
+}
+
+/// Verify the tags of every index in `indices` against one multi-point opening.
+pub fn multi_verify<F, D, PC, T>(
+    vk: &PC::VerifierKey,
+    n: usize,
+    indices: &[usize],
+    tags: &[&[u8]],
+    tag_commit: &PC::Commitment,
+    proof: &MultiOpening<F, PC>,
+) -> Result<(), PosolError>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+    PC: HomomorphicCommitment<F>,
+    T: TranscriptProtocol<F, PC::Commitment>,
+{
+
+    let domain = domain::<F, D>(n)?;
+
+    let timer = start_timer!(|| "Tag: Multiple Verifying");
+
+    let evaluations = tags
+        .iter()
+        .map(|&reader| {
+            F::read(reader)
+                .map_err(|e| PosolError::InputRange(format!("failed to read tag: {}", e)))
+        })
+        .collect::<Result<Vec<_>, PosolError>>()?;
+    opening::multi_check::<F, D, PC, T>(vk, &domain, indices, &evaluations, tag_commit, proof)
+        .map_err(|e| PosolError::Verification(format!("multiple tag verification failed: {}", e)))?;
+
+    end_timer!(timer);
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
-    use ark_ff::{ToBytes, UniformRand};
+    use ark_ff::{FromBytes, PrimeField, ToBytes, UniformRand};
     use ark_poly::GeneralEvaluationDomain;
     use ark_poly_commit::PolynomialCommitment;
-    use ark_std::{test_rng, rand::Rng};
+    use ark_std::{test_rng, rand::{Rng, rngs::StdRng}};
     use ark_bn254::{Fr, Bn254};
+    use ark_pallas::Affine as PallasAffine;
+    use blake2::Blake2s;
     use itertools::Itertools;
     
-    use crate::commitment::KZG10;
+    use crate::{commitment::{KZG10, IPA}, balance_sum::MerlinTranscript};
     use super::*;
 
     #[test]
     fn test_full() {
-        let rng = &mut test_rng();
+        check_full::<_, KZG10<Bn254>>(&mut test_rng());
+    }
 
+    #[test]
+    fn test_full_ipa() {
+        check_full::<_, IPA<PallasAffine, Blake2s>>(&mut test_rng());
+    }
+
+    fn check_full<F, PC>(rng: &mut StdRng)
+    where
+        F: PrimeField,
+        PC: HomomorphicCommitment<F>,
+    {
         let n = 16;
         // setup
-        let pp = KZG10::<Bn254>::setup(n + 3, None, rng).unwrap();
-        let (ck, cvk) = KZG10::<Bn254>::trim(
+        let pp = PC::setup(n + 3, None, rng).unwrap();
+        let (ck, cvk) = PC::trim(
             &pp,
             n + 3,
             0,
             None,
         ).unwrap();
 
-        // generate random balances
+        // generate random tags
         let tags = (0..n)
-            .into_iter()
             .map(|_| {
                 let mut bytes = vec![0u8; 32];
-                Fr::rand(rng).write(&mut bytes).unwrap();
+                F::rand(rng).write(&mut bytes[..]).unwrap();
                 bytes
             })
             .collect_vec();
@@ -186,13 +335,13 @@ mod test {
 
         // commit
         let (tag_commit, labeled_tag_poly) =
-            commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+            commit::<F, GeneralEvaluationDomain<_>, PC>(&ck, n, &tags_ref).unwrap();
 
         // Individual Checking
         let i = rng.gen_range(0..n);
         let tag = tags_ref[i];
         // open
-        let opening_proof = individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+        let opening_proof = individual_open::<F, GeneralEvaluationDomain<_>, PC>(
             &ck,
             n,
             i,
@@ -200,7 +349,7 @@ mod test {
             &tag_commit,
         ).unwrap();
         // verify
-        let res = individual_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+        let res = individual_verify::<F, GeneralEvaluationDomain<_>, PC>(
             &cvk,
             n,
             i,
@@ -210,5 +359,150 @@ mod test {
         );
         assert!(res.is_ok());
     }
-}
 
+    #[test]
+    fn test_derive() {
+        // big-endian uint256 tags of `computeUserTag`
+        let vectors = [
+            ("", "", "05d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"),
+            ("a", "bc", "0e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"),
+        ];
+        for (id, salt, expected) in vectors {
+            let mut expected = (0..64)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&expected[i..i + 2], 16).unwrap())
+                .collect_vec();
+            expected.reverse();
+            let tag = derive(id.as_bytes(), salt.as_bytes());
+            assert_eq!(&tag[..], &expected[..]);
+            assert!(Fr::read(&tag[..]).is_ok());
+        }
+        assert_ne!(derive(b"alice", b"salt"), derive(b"alice", b"pepper"));
+    }
+
+    #[test]
+    fn test_update() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, _) = KZG10::<Bn254>::trim(
+            &pp,
+            n,
+            0,
+            None,
+        ).unwrap();
+        let basis = LagrangeBasis::from_kzg_key::<GeneralEvaluationDomain<_>>(&ck, n).unwrap();
+
+        // generate random tags
+        let mut tags = (0..n)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let (tag_commit, labeled_tag_poly) =
+            commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+
+        // change some of the tags
+        let old_tags = tags.clone();
+        let indices = vec![2, 7, 15];
+        for &i in indices.iter() {
+            Fr::rand(rng).write(&mut tags[i][..]).unwrap();
+        }
+        let updates = indices.iter().map(|&i| (i, &old_tags[i][..], &tags[i][..])).collect_vec();
+        let updated_commit =
+            update_commit::<_, KZG10<Bn254>>(&basis, &tag_commit, &updates).unwrap();
+        let updated_poly =
+            update_poly::<_, GeneralEvaluationDomain<_>>(n, &labeled_tag_poly, &updates).unwrap();
+
+        // same as committing to the new tags from scratch
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+        let (expected_commit, expected_poly) =
+            commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+        assert_eq!(updated_commit, expected_commit);
+        assert_eq!(updated_poly.polynomial(), expected_poly.polynomial());
+        let evals_commit = commit_evals::<_, KZG10<Bn254>>(&basis, &tags_ref).unwrap();
+        assert_eq!(evals_commit, expected_commit);
+
+        // tags of part of the domain
+        let tags_ref = &tags_ref[..n / 2 + 1];
+        let (expected_commit, _) =
+            commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, tags_ref).unwrap();
+        let evals_commit = commit_evals::<_, KZG10<Bn254>>(&basis, tags_ref).unwrap();
+        assert_eq!(evals_commit, expected_commit);
+
+        // index out of domain
+        let updates = vec![(n, &old_tags[0][..], &tags[0][..])];
+        assert!(update_commit::<_, KZG10<Bn254>>(&basis, &tag_commit, &updates).is_err());
+        assert!(update_poly::<_, GeneralEvaluationDomain<_>>(n, &labeled_tag_poly, &updates).is_err());
+    }
+
+    #[test]
+    fn test_multi_open() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        // setup
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(
+            &pp,
+            n,
+            0,
+            None,
+        ).unwrap();
+
+        // generate random tags
+        let tags = (0..n)
+            .map(|_| {
+                let mut bytes = vec![0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect_vec();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect_vec();
+
+        // commit
+        let (tag_commit, labeled_tag_poly) =
+            commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+
+        // Multiple Checking
+        let indices = vec![1, 4, 5, 9, 15];
+        let opened_tags = indices.iter().map(|&i| tags_ref[i]).collect_vec();
+        // open
+        let opening_proof =
+            multi_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+                &ck,
+                n,
+                &indices,
+                &labeled_tag_poly,
+                &tag_commit,
+            ).unwrap();
+        // verify
+        let res = multi_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+            &cvk,
+            n,
+            &indices,
+            &opened_tags,
+            &tag_commit,
+            &opening_proof,
+        );
+        assert!(res.is_ok());
+
+        // verify with a swapped tag
+        let mut wrong_tags = opened_tags.clone();
+        wrong_tags.swap(0, 1);
+        let res = multi_verify::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>, MerlinTranscript>(
+            &cvk,
+            n,
+            &indices,
+            &wrong_tags,
+            &tag_commit,
+            &opening_proof,
+        );
+        assert!(res.is_err());
+    }
+}
@@ -22,15 +22,17 @@ use ark_poly::{
 /// ```text
 /// L_k(X) = (x^n - 1) * omega^k / n * (x - omega^k)
 /// ```
+///
+/// Returns `None` if `z` is `point`, where the expression is undefined.
 pub(crate) fn compute_lagrange_evaluation<F: Field>(
     n: usize,
     point: F,
     zh_eval: F,
     z: F,
-) -> F {
+) -> Option<F> {
     let numinator = zh_eval * point;
     let dominator = F::from(n as u64) * (z - point);
-    numinator * dominator.inverse().unwrap()
+    dominator.inverse().map(|inv| numinator * inv)
 }
 
 /// Macro to quickly label polynomials
@@ -46,7 +48,8 @@ macro_rules! label_polynomial {
     };
 }
 
-/// Macro to quickly label polynomial commitments
+/// Macro to quickly label polynomial commitments, optionally with the label of
+/// the committed polynomial as schemes like IPA require when opening
 #[macro_export]
 macro_rules! label_commitment {
     ($comm:expr) => {
@@ -56,6 +59,13 @@ macro_rules! label_commitment {
             None,
         )
     };
+    ($comm:expr, $label:expr) => {
+        ark_poly_commit::LabeledCommitment::new(
+            $label.to_owned(),
+            $comm.clone(),
+            None,
+        )
+    };
 }
 
 /// Evaluation Domain Extension Trait
@@ -117,6 +127,25 @@ where
     DensePolynomial::from_coefficients_vec(evals)
 }
 
+/// Add `sum_i delta_i L_i(X)` to a polynomial, given the deltas as `(i, delta_i)`.
+pub(crate) fn add_lagrange_deltas<F, D>(
+    domain: &D,
+    poly: &DensePolynomial<F>,
+    deltas: &[(usize, F)],
+) -> DensePolynomial<F>
+where
+    F: FftField,
+    D: EvaluationDomain<F>,
+{
+    let mut delta_evals = vec![F::zero(); domain.size()];
+    for &(i, delta) in deltas {
+        delta_evals[i] += delta;
+    }
+    let delta_poly = poly_from_evals(domain, delta_evals);
+
+    poly + &delta_poly
+}
+
 ///
 #[inline]
 pub(crate) fn poly_from_coset_evals<F, D>(
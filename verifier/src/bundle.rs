@@ -0,0 +1,167 @@
+//! Bundles of a bundle store, as published by the prover.
+//!
+//! A store holds a [`BundlesHeader`] with the tag and balance commitments,
+//! followed by a fixed-size [`Bundle`] per user in index order, all compressed.
+//! A user fetches the header and the bytes of their own bundle only.
+use ark_std::format;
+use ark_ec::PairingEngine;
+use ark_ff::Zero;
+use ark_serialize::{CanonicalSerialize, CanonicalDeserialize, Read, SerializationError, Write};
+
+use crate::{error::VerifierError, verify_inclusion, KZG10Commitment, KZG10Proof, KZG10VerifierKey};
+
+/// Commitments the bundles of a bundle store open, followed by the bundles of
+/// every user in index order.
+#[derive(Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct BundlesHeader<E: PairingEngine> {
+    pub users: u64,
+    pub tag_commit: KZG10Commitment<E>,
+    pub b_commit: KZG10Commitment<E>,
+}
+
+impl<E: PairingEngine> BundlesHeader<E> {
+    /// Read the header from the start of a bundle store.
+    pub fn read<R: Read>(reader: R) -> Result<Self, VerifierError> {
+        Self::deserialize(reader)
+            .map_err(|e| VerifierError::Serialization(format!("failed to read bundles header: {}", e)))
+    }
+
+    /// Byte offset of the bundle of user `i` in the store.
+    pub fn bundle_offset(&self, i: usize) -> Result<usize, VerifierError> {
+        if i as u64 >= self.users {
+            return Err(VerifierError::InputRange(format!("user index {} out of {} users", i, self.users)));
+        }
+
+        Ok(self.serialized_size() + i * Bundle::<E>::size())
+    }
+}
+
+/// Tag and balance of a user along with their separate openings, as
+/// `PoSolVerifier.individualVerify` checks them.
+#[derive(Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Bundle<E: PairingEngine> {
+    pub tag: E::Fr,
+    pub balance: u64,
+    pub tag_opening: E::G1Affine,
+    pub b_opening: E::G1Affine,
+}
+
+impl<E: PairingEngine> Bundle<E> {
+    /// Every bundle has the same compressed size, so that a user's bundle can be
+    /// read without the others.
+    pub fn size() -> usize {
+        Self {
+            tag: E::Fr::zero(),
+            balance: 0,
+            tag_opening: E::G1Affine::zero(),
+            b_opening: E::G1Affine::zero(),
+        }.serialized_size()
+    }
+
+    /// Read a bundle, checking that its points are in the prime order subgroup.
+    pub fn read<R: Read>(reader: R) -> Result<Self, VerifierError> {
+        Self::deserialize(reader)
+            .map_err(|e| VerifierError::Serialization(format!("failed to read bundle: {}", e)))
+    }
+
+    /// Verify the bundle of user `i` against the commitments of `header`, for
+    /// a domain of size `n`.
+    pub fn verify(
+        &self,
+        vk: &KZG10VerifierKey<E>,
+        n: usize,
+        i: usize,
+        header: &BundlesHeader<E>,
+    ) -> Result<(), VerifierError> {
+        header.bundle_offset(i)?;
+
+        verify_inclusion::<E>(
+            vk,
+            n,
+            i,
+            self.tag,
+            self.balance,
+            &header.tag_commit,
+            &header.b_commit,
+            &KZG10Proof::<E> { w: self.tag_opening, random_v: None },
+            &KZG10Proof::<E> { w: self.b_opening, random_v: None },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_bn254::{Bn254, Fr};
+    use ark_ff::{FromBytes, ToBytes, UniformRand};
+    use ark_poly::GeneralEvaluationDomain;
+    use ark_poly_commit::PolynomialCommitment;
+    use ark_std::{test_rng, rand::Rng};
+    use posol_core::{commitment::KZG10, tag};
+
+    use super::*;
+
+    #[test]
+    fn test_bundle() {
+        let rng = &mut test_rng();
+
+        let n = 16;
+        let pp = KZG10::<Bn254>::setup(n, None, rng).unwrap();
+        let (ck, cvk) = KZG10::<Bn254>::trim(&pp, n, 0, None).unwrap();
+
+        // commit to the tags and balances with posol-core
+        let tags = (0..n)
+            .map(|_| {
+                let mut bytes = [0u8; 32];
+                Fr::rand(rng).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect::<Vec<_>>();
+        let tags_ref = tags.iter().map(|t| &t[..]).collect::<Vec<_>>();
+        let balances = (0..n).map(|_| rng.gen_range(0..n as u64)).collect::<Vec<_>>();
+        let balance_evals = balances
+            .iter()
+            .map(|&b| {
+                let mut bytes = [0u8; 32];
+                Fr::from(b).write(&mut bytes[..]).unwrap();
+                bytes
+            })
+            .collect::<Vec<_>>();
+        let balances_ref = balance_evals.iter().map(|b| &b[..]).collect::<Vec<_>>();
+        let (tag_commit, tag_poly) =
+            tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &tags_ref).unwrap();
+        let (b_commit, b_poly) =
+            tag::commit::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(&ck, n, &balances_ref).unwrap();
+
+        // publish the store
+        let i = rng.gen_range(0..n);
+        let header = BundlesHeader::<Bn254> { users: n as u64, tag_commit, b_commit };
+        let bundle = Bundle::<Bn254> {
+            tag: Fr::read(&tags[i][..]).unwrap(),
+            balance: balances[i],
+            tag_opening: tag::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                &ck, n, i, &tag_poly, &tag_commit,
+            ).unwrap().w,
+            b_opening: tag::individual_open::<_, GeneralEvaluationDomain<_>, KZG10<Bn254>>(
+                &ck, n, i, &b_poly, &b_commit,
+            ).unwrap().w,
+        };
+        let mut bytes = Vec::new();
+        header.serialize(&mut bytes).unwrap();
+        let offset = bytes.len();
+        bundle.serialize(&mut bytes).unwrap();
+        assert_eq!(bytes.len() - offset, Bundle::<Bn254>::size());
+
+        // verify from the bytes
+        let header = BundlesHeader::<Bn254>::read(&bytes[..]).unwrap();
+        let bundle = Bundle::<Bn254>::read(&bytes[offset..]).unwrap();
+        bundle.verify(&cvk, n, i, &header).unwrap();
+
+        // at another index, with another balance, or out of the store
+        let j = (i + 1) % n;
+        assert!(bundle.verify(&cvk, n, j, &header).is_err());
+        let forged = Bundle::<Bn254> { balance: bundle.balance + 1, ..bundle };
+        assert!(forged.verify(&cvk, n, i, &header).is_err());
+        assert!(matches!(forged.verify(&cvk, n, n, &header), Err(VerifierError::InputRange(_))));
+        assert!(matches!(forged.verify(&cvk, 12, i, &header), Err(VerifierError::Domain { .. })));
+    }
+}
@@ -0,0 +1,36 @@
+//! Errors of the user-side verification.
+use core::fmt;
+use ark_std::string::String;
+
+/// Error of the user-side verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifierError {
+    /// No evaluation domain of this size: it must be a power of two, and at
+    /// most `2^two_adicity`.
+    Domain { size: usize, two_adicity: u32 },
+    /// An input is out of its valid range, e.g. an index out of the domain.
+    InputRange(String),
+    /// An input can not be deserialized.
+    Serialization(String),
+    /// An opening does not verify.
+    Verification(String),
+}
+
+impl fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Domain { size, two_adicity } => write!(
+                f,
+                "no domain of size {}, must be a power of two with 2-adicity: {}",
+                size,
+                two_adicity,
+            ),
+            Self::InputRange(msg) => write!(f, "input out of range: {}", msg),
+            Self::Serialization(msg) => write!(f, "serialization error: {}", msg),
+            Self::Verification(msg) => write!(f, "verification error: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifierError {}
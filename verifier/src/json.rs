@@ -0,0 +1,145 @@
+//! JSON bundles, carrying a user's bundle with everything needed to verify it.
+//!
+//! The verifier key, commitments and openings are hex-encoded compressed
+//! serializations. The tag is the big-endian `uint256` returned by
+//! `computeUserTag`, so that it can be compared with the tag derived from the
+//! user's id and salt.
+use ark_std::{format, string::String, vec::Vec};
+use ark_ec::PairingEngine;
+use ark_ff::{FromBytes, ToBytes};
+use ark_serialize::{CanonicalSerialize, CanonicalDeserialize};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    bundle::{Bundle, BundlesHeader},
+    error::VerifierError,
+    verify_inclusion,
+    KZG10Commitment,
+    KZG10Proof,
+    KZG10VerifierKey,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonBundle {
+    pub domain_size: usize,
+    pub index: usize,
+    pub vk: String,
+    pub tag_commit: String,
+    pub b_commit: String,
+    pub tag: String,
+    pub balance: u64,
+    pub tag_opening: String,
+    pub b_opening: String,
+}
+
+impl JsonBundle {
+    /// JSON bundle of user `i` of a store over a domain of size `n`.
+    pub fn new<E: PairingEngine>(
+        vk: &KZG10VerifierKey<E>,
+        n: usize,
+        i: usize,
+        header: &BundlesHeader<E>,
+        bundle: &Bundle<E>,
+    ) -> Self {
+        let mut tag = [0u8; 32];
+        bundle.tag.write(&mut tag[..]).expect("tag is 32 bytes");
+
+        Self {
+            domain_size: n,
+            index: i,
+            vk: to_hex(vk),
+            tag_commit: to_hex(&header.tag_commit),
+            b_commit: to_hex(&header.b_commit),
+            tag: tag_to_hex(&tag),
+            balance: bundle.balance,
+            tag_opening: to_hex(&bundle.tag_opening),
+            b_opening: to_hex(&bundle.b_opening),
+        }
+    }
+
+    /// Tag of the bundle, little-endian as [`crate::tag::derive`] returns it.
+    pub fn tag_bytes(&self) -> Result<[u8; 32], VerifierError> {
+        tag_from_hex(&self.tag)
+    }
+
+    /// Verify the openings of the bundle against the verifier key, the domain
+    /// size and the commitments published by the exchange, rather than against
+    /// those of the bundle, which must match them.
+    pub fn verify<E: PairingEngine>(
+        &self,
+        vk: &KZG10VerifierKey<E>,
+        n: usize,
+        tag_commit: &KZG10Commitment<E>,
+        b_commit: &KZG10Commitment<E>,
+    ) -> Result<(), VerifierError> {
+        if self.domain_size != n {
+            return Err(VerifierError::Verification(format!(
+                "bundle over a domain of size {} instead of {}",
+                self.domain_size,
+                n,
+            )));
+        }
+        let published = [
+            ("verifier key", &self.vk, to_hex(vk)),
+            ("tag commitment", &self.tag_commit, to_hex(tag_commit)),
+            ("balance commitment", &self.b_commit, to_hex(b_commit)),
+        ];
+        let bytes = |item: &str| hex::decode(item.trim_start_matches("0x")).ok();
+        for (name, item, expected) in published {
+            if bytes(item) != bytes(&expected) {
+                return Err(VerifierError::Verification(format!("{} of the bundle is not the published one", name)));
+            }
+        }
+
+        let tag = E::Fr::read(&self.tag_bytes()?[..])
+            .map_err(|e| VerifierError::Serialization(format!("invalid tag: {}", e)))?;
+        let tag_opening = KZG10Proof::<E> { w: from_hex(&self.tag_opening)?, random_v: None };
+        let b_opening = KZG10Proof::<E> { w: from_hex(&self.b_opening)?, random_v: None };
+
+        verify_inclusion::<E>(
+            vk,
+            n,
+            self.index,
+            tag,
+            self.balance,
+            tag_commit,
+            b_commit,
+            &tag_opening,
+            &b_opening,
+        )
+    }
+}
+
+/// Big-endian `0x` hex of a little-endian tag.
+pub fn tag_to_hex(tag: &[u8; 32]) -> String {
+    let mut bytes = *tag;
+    bytes.reverse();
+
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Little-endian tag of a big-endian hex, with or without the `0x` prefix.
+pub fn tag_from_hex(tag: &str) -> Result<[u8; 32], VerifierError> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(tag.trim_start_matches("0x"), &mut bytes)
+        .map_err(|e| VerifierError::Serialization(format!("invalid tag hex: {}", e)))?;
+    bytes.reverse();
+
+    Ok(bytes)
+}
+
+fn to_hex<S: CanonicalSerialize>(item: &S) -> String {
+    let mut bytes = Vec::new();
+    item.serialize(&mut bytes).expect("serialization to bytes failed");
+
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Decode an item from a hex-encoded compressed serialization, with or without
+/// the `0x` prefix, checking every point.
+pub fn from_hex<D: CanonicalDeserialize>(item: &str) -> Result<D, VerifierError> {
+    let bytes = hex::decode(item.trim_start_matches("0x"))
+        .map_err(|e| VerifierError::Serialization(format!("invalid hex: {}", e)))?;
+
+    D::deserialize(&bytes[..]).map_err(|e| VerifierError::Serialization(format!("{}", e)))
+}
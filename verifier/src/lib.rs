@@ -0,0 +1,113 @@
+//! User-side verifier of the inclusion of a user in the committed liabilities.
+//!
+//! A user only needs the KZG verifier key, the tag and balance commitments, their
+//! index, tag and balance, and the openings of tag(X) and B(X) at their domain
+//! point. This crate checks them with the same commitment scheme as
+//! `posol-core`, and builds without the default `std` feature for `no_std`
+//! targets with `alloc`, such as hardware wallets and mobile SDKs. A user also
+//! recomputes their tag from their id and salt with [`tag::derive`].
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(missing_docs)]
+
+pub mod bundle;
+pub mod error;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod tag;
+
+use ark_std::{format, string::ToString, vec};
+use ark_ec::PairingEngine;
+use ark_ff::{FftField, FftParameters, One};
+use ark_poly::{EvaluationDomain, GeneralEvaluationDomain, univariate::DensePolynomial};
+use ark_poly_commit::{
+    kzg10,
+    sonic_pc::{self, SonicKZG10},
+    LabeledCommitment,
+    PolynomialCommitment,
+};
+
+pub use bundle::{Bundle, BundlesHeader};
+pub use error::VerifierError;
+
+/// KZG10 as committed to by `posol-core`.
+pub type KZG10<E> = SonicKZG10<E, DensePolynomial<<E as PairingEngine>::Fr>>;
+/// KZG10 verifier key
+pub type KZG10VerifierKey<E> = sonic_pc::VerifierKey<E>;
+/// A single KZG10 commitment
+pub type KZG10Commitment<E> = kzg10::Commitment<E>;
+/// KZG10 Proof
+pub type KZG10Proof<E> = kzg10::Proof<E>;
+
+/// Check the opening `proof` of the committed polynomial to `evaluation` at the
+/// `i`-th element of the domain of size `n`.
+pub fn individual_verify<E: PairingEngine>(
+    vk: &KZG10VerifierKey<E>,
+    n: usize,
+    i: usize,
+    evaluation: E::Fr,
+    commit: &KZG10Commitment<E>,
+    proof: &KZG10Proof<E>,
+) -> Result<(), VerifierError> {
+    let point = domain_element::<E>(n, i)?;
+
+    check::<E>(vk, point, evaluation, commit, proof, "individual")
+}
+
+/// Verify the tag and balance of user `i` against the separate openings of
+/// tag(X) and B(X), as `PoSolVerifier.individualVerify` does on chain.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_inclusion<E: PairingEngine>(
+    vk: &KZG10VerifierKey<E>,
+    n: usize,
+    i: usize,
+    tag: E::Fr,
+    balance: u64,
+    tag_commit: &KZG10Commitment<E>,
+    b_commit: &KZG10Commitment<E>,
+    tag_opening: &KZG10Proof<E>,
+    b_opening: &KZG10Proof<E>,
+) -> Result<(), VerifierError> {
+    let point = domain_element::<E>(n, i)?;
+
+    check::<E>(vk, point, tag, tag_commit, tag_opening, "tag")?;
+    check::<E>(vk, point, E::Fr::from(balance), b_commit, b_opening, "balance")
+}
+
+/// The `i`-th element of the domain of size `n`.
+fn domain_element<E: PairingEngine>(n: usize, i: usize) -> Result<E::Fr, VerifierError> {
+    let domain = GeneralEvaluationDomain::<E::Fr>::new(n)
+        .filter(|_| n.is_power_of_two())
+        .ok_or(VerifierError::Domain {
+            size: n,
+            two_adicity: <<E::Fr as FftField>::FftParams as FftParameters>::TWO_ADICITY,
+        })?;
+    if i >= n {
+        return Err(VerifierError::InputRange(format!("index {} out of domain of size {}", i, n)));
+    }
+
+    Ok(domain.element(i))
+}
+
+fn check<E: PairingEngine>(
+    vk: &KZG10VerifierKey<E>,
+    point: E::Fr,
+    evaluation: E::Fr,
+    commit: &KZG10Commitment<E>,
+    proof: &KZG10Proof<E>,
+    name: &str,
+) -> Result<(), VerifierError> {
+    let labeled_commit = LabeledCommitment::new(name.to_string(), *commit, None);
+    match KZG10::<E>::check(
+        vk,
+        vec![&labeled_commit],
+        &point,
+        vec![evaluation],
+        proof,
+        E::Fr::one(),
+        None,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(VerifierError::Verification(format!("{} verification failed", name))),
+        Err(e) => Err(VerifierError::Verification(format!("check opening proof error: {}", e))),
+    }
+}
@@ -0,0 +1,21 @@
+//! Derivation of the user tags.
+use sha3::{Digest, Keccak256};
+
+/// Mask of the 253 low bits of a tag, so that it is below the scalar field
+/// modulus of BN254 and of the other supported curves.
+const TAG_MASK: u8 = 0x1f;
+
+/// Derive the tag of a user from its id and salt as `computeUserTag` of the
+/// contract does, `keccak256(id || salt)` with the top three bits cleared. The
+/// tag is returned little-endian, as read by `tag::commit` and the verifiers.
+pub fn derive(id: &[u8], salt: &[u8]) -> [u8; 32] {
+    let mut tag: [u8; 32] = Keccak256::new()
+        .chain_update(id)
+        .chain_update(salt)
+        .finalize()
+        .into();
+    tag[0] &= TAG_MASK;
+    tag.reverse();
+
+    tag
+}
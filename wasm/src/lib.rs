@@ -0,0 +1,153 @@
+//! WebAssembly bindings of the user-side verifier, for web pages.
+//!
+//! A user verifies a JSON bundle, as written by `supply-bundle --json-path`, over
+//! BN254 against the verifier key, domain size and commitments published by the
+//! exchange, and optionally that its tag derives from their id and salt.
+use ark_bn254::Bn254;
+use posol_verifier::{json::{self, JsonBundle}, tag, KZG10Commitment, KZG10VerifierKey, VerifierError};
+use wasm_bindgen::prelude::*;
+
+/// Outcome of the verification of a bundle. The other fields are those of the
+/// bundle, if it could be parsed.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub valid: bool,
+    pub index: Option<usize>,
+    pub balance: Option<u64>,
+    pub tag: Option<String>,
+    #[wasm_bindgen(js_name = domainSize)]
+    pub domain_size: Option<usize>,
+    pub vk: Option<String>,
+    #[wasm_bindgen(js_name = tagCommit)]
+    pub tag_commit: Option<String>,
+    #[wasm_bindgen(js_name = bCommit)]
+    pub b_commit: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Verdict {
+    fn new(bundle: Option<&JsonBundle>, error: Option<String>) -> Self {
+        Self {
+            valid: error.is_none(),
+            index: bundle.map(|b| b.index),
+            balance: bundle.map(|b| b.balance),
+            tag: bundle.map(|b| b.tag.clone()),
+            domain_size: bundle.map(|b| b.domain_size),
+            vk: bundle.map(|b| b.vk.clone()),
+            tag_commit: bundle.map(|b| b.tag_commit.clone()),
+            b_commit: bundle.map(|b| b.b_commit.clone()),
+            error,
+        }
+    }
+}
+
+/// Verify the openings of the tag and balance of a JSON bundle against the
+/// published hex-encoded verifier key and commitments.
+#[wasm_bindgen(js_name = verifyBundle)]
+pub fn verify_bundle(bundle: &str, vk: &str, domain_size: usize, tag_commit: &str, b_commit: &str) -> Verdict {
+    verdict(bundle, (vk, domain_size, tag_commit, b_commit), None)
+}
+
+/// Verify a JSON bundle as [`verify_bundle`] does, and that its tag derives
+/// from `id` and `salt`.
+#[wasm_bindgen(js_name = verifyUserBundle)]
+pub fn verify_user_bundle(
+    bundle: &str,
+    vk: &str,
+    domain_size: usize,
+    tag_commit: &str,
+    b_commit: &str,
+    id: &str,
+    salt: &str,
+) -> Verdict {
+    verdict(bundle, (vk, domain_size, tag_commit, b_commit), Some((id, salt)))
+}
+
+/// Tag of a user as `computeUserTag` returns it, big-endian hex.
+#[wasm_bindgen(js_name = deriveTag)]
+pub fn derive_tag(id: &str, salt: &str) -> String {
+    json::tag_to_hex(&tag::derive(id.as_bytes(), salt.as_bytes()))
+}
+
+fn verdict(bundle: &str, published: (&str, usize, &str, &str), user: Option<(&str, &str)>) -> Verdict {
+    let bundle: JsonBundle = match serde_json::from_str(bundle) {
+        Ok(bundle) => bundle,
+        Err(e) => return Verdict::new(None, Some(format!("invalid bundle: {}", e))),
+    };
+
+    let error = check(&bundle, published, user).err();
+    Verdict::new(Some(&bundle), error)
+}
+
+fn check(
+    bundle: &JsonBundle,
+    (vk, n, tag_commit, b_commit): (&str, usize, &str, &str),
+    user: Option<(&str, &str)>,
+) -> Result<(), String> {
+    if let Some((id, salt)) = user {
+        if bundle.tag_bytes().map_err(|e| e.to_string())? != tag::derive(id.as_bytes(), salt.as_bytes()) {
+            return Err("tag does not derive from id and salt".into());
+        }
+    }
+
+    let published = |name: &str, e: VerifierError| format!("invalid published {}: {}", name, e);
+    let vk: KZG10VerifierKey<Bn254> = json::from_hex(vk).map_err(|e| published("verifier key", e))?;
+    let tag_commit: KZG10Commitment<Bn254> = json::from_hex(tag_commit).map_err(|e| published("tag commitment", e))?;
+    let b_commit: KZG10Commitment<Bn254> = json::from_hex(b_commit).map_err(|e| published("balance commitment", e))?;
+
+    bundle.verify::<Bn254>(&vk, n, &tag_commit, &b_commit).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_vectors() {
+        let vectors: Value = serde_json::from_str(include_str!("../tests/vectors.json")).unwrap();
+
+        for vector in vectors["tags"].as_array().unwrap() {
+            let tag = derive_tag(vector["id"].as_str().unwrap(), vector["salt"].as_str().unwrap());
+            assert_eq!(tag, vector["tag"].as_str().unwrap());
+        }
+
+        let published = &vectors["published"];
+        let vk = published["vk"].as_str().unwrap();
+        let n = published["domain_size"].as_u64().unwrap() as usize;
+        let tag_commit = published["tag_commit"].as_str().unwrap();
+        let b_commit = published["b_commit"].as_str().unwrap();
+        for vector in vectors["bundles"].as_array().unwrap() {
+            let bundle = vector["bundle"].to_string();
+            let (id, salt) = (vector["id"].as_str().unwrap(), vector["salt"].as_str().unwrap());
+            let verdict = verify_user_bundle(&bundle, vk, n, tag_commit, b_commit, id, salt);
+            assert_eq!(verdict.valid, vector["valid"].as_bool().unwrap(), "{:?}", verdict);
+            assert_eq!(verdict.valid, verdict.error.is_none());
+            assert_eq!(verdict.balance, vector["bundle"]["balance"].as_u64());
+            assert_eq!(verdict.vk.as_deref(), vector["bundle"]["vk"].as_str());
+        }
+
+        // the openings of a bundle verify whatever the id and salt
+        let bundle = vectors["bundles"][4]["bundle"].to_string();
+        assert!(verify_bundle(&bundle, vk, n, tag_commit, b_commit).valid);
+
+        // a bundle consistent with its own keys and commitments, but not the published ones
+        let forged = &vectors["bundles"][5]["bundle"];
+        let verdict = verify_bundle(
+            &forged.to_string(),
+            forged["vk"].as_str().unwrap(),
+            n,
+            forged["tag_commit"].as_str().unwrap(),
+            forged["b_commit"].as_str().unwrap(),
+        );
+        assert!(verdict.valid);
+        assert!(!verify_bundle(&forged.to_string(), vk, n, tag_commit, b_commit).valid);
+        assert!(!verify_bundle(&bundle, vk, 2 * n, tag_commit, b_commit).valid);
+        assert!(!verify_bundle(&bundle, vk, n, b_commit, b_commit).valid);
+
+        let verdict = verify_bundle("{}", vk, n, tag_commit, b_commit);
+        assert!(!verdict.valid && verdict.index.is_none());
+    }
+}
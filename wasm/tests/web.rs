@@ -0,0 +1,38 @@
+//! The test vectors of the Rust tests, run on the wasm target with
+//! `cargo test --target wasm32-unknown-unknown`.
+#![cfg(target_arch = "wasm32")]
+
+use serde_json::Value;
+use wasm_bindgen_test::*;
+use posol_wasm::*;
+
+fn vectors() -> Value {
+    serde_json::from_str(include_str!("vectors.json")).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_derive_tag() {
+    for vector in vectors()["tags"].as_array().unwrap() {
+        let tag = derive_tag(vector["id"].as_str().unwrap(), vector["salt"].as_str().unwrap());
+        assert_eq!(tag, vector["tag"].as_str().unwrap());
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_verify_bundle() {
+    let vectors = vectors();
+    let published = &vectors["published"];
+    let vk = published["vk"].as_str().unwrap();
+    let n = published["domain_size"].as_u64().unwrap() as usize;
+    let tag_commit = published["tag_commit"].as_str().unwrap();
+    let b_commit = published["b_commit"].as_str().unwrap();
+    for vector in vectors["bundles"].as_array().unwrap() {
+        let bundle = vector["bundle"].to_string();
+        let (id, salt) = (vector["id"].as_str().unwrap(), vector["salt"].as_str().unwrap());
+        let verdict = verify_user_bundle(&bundle, vk, n, tag_commit, b_commit, id, salt);
+        assert_eq!(verdict.valid, vector["valid"].as_bool().unwrap(), "{:?}", verdict);
+        assert_eq!(verdict.balance, vector["bundle"]["balance"].as_u64());
+    }
+
+    assert!(!verify_bundle("{}", vk, n, tag_commit, b_commit).valid);
+}